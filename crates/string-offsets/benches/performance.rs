@@ -0,0 +1,231 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rand::prelude::*;
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use string_offsets::bitrank::BitSelect;
+use string_offsets::{BitRank, BitRankBuilder};
+use succinct::{BinSearchSelect, BitRankSupport, BitVecMut, BitVector, Rank9, Select1Support};
+use vers_vecs::{BitVec, RsVec};
+
+/// The number of bits and the fractions of them which are set that the rank and select queries
+/// are measured for.
+const SIZES: [usize; 2] = [1 << 16, 1 << 24];
+const DENSITIES: [f64; 4] = [0.001, 0.01, 0.1, 0.5];
+
+/// The same set in all the compared representations.
+struct Sets {
+    positions: Vec<usize>,
+    bit_rank: BitRank,
+    bit_select: BitSelect,
+    succinct: BinSearchSelect<Rank9<BitVector<u64>>>,
+    vers: RsVec,
+}
+
+impl Sets {
+    fn new(rng: &mut impl Rng, len: usize, density: f64) -> Self {
+        let positions: Vec<usize> = (0..len).filter(|_| rng.random_bool(density)).collect();
+        let bit_rank = positions.iter().copied().collect();
+        let bit_select = positions.iter().copied().collect();
+        let mut succinct = BitVector::with_fill(len as u64, false);
+        let mut vers = BitVec::from_zeros(len);
+        for &position in &positions {
+            succinct.set_bit(position as u64, true);
+            vers.flip_bit(position);
+        }
+        Self {
+            positions,
+            bit_rank,
+            bit_select,
+            succinct: BinSearchSelect::new(Rank9::new(succinct)),
+            vers: RsVec::from_bit_vec(vers),
+        }
+    }
+}
+
+fn rank_benchmark(c: &mut Criterion) {
+    let mut rng = ChaCha8Rng::seed_from_u64(1);
+    for len in SIZES {
+        for density in DENSITIES {
+            let sets = Sets::new(&mut rng, len, density);
+            let queries: Vec<usize> = (0..1000).map(|_| rng.random_range(0..len)).collect();
+
+            let mut group = c.benchmark_group(format!("rank:{len}:{density}"));
+            group.bench_function("bit_rank", |b| {
+                b.iter(|| {
+                    for &i in &queries {
+                        black_box(sets.bit_rank.rank(i));
+                    }
+                })
+            });
+            group.bench_function("binary_search", |b| {
+                b.iter(|| {
+                    for &i in &queries {
+                        black_box(sets.positions.partition_point(|&p| p < i));
+                    }
+                })
+            });
+            group.bench_function("succinct", |b| {
+                b.iter(|| {
+                    for &i in &queries {
+                        // The rank of succinct includes the queried position.
+                        black_box(
+                            i.checked_sub(1)
+                                .map_or(0, |i| sets.succinct.rank1(i as u64)),
+                        );
+                    }
+                })
+            });
+            group.bench_function("vers", |b| {
+                b.iter(|| {
+                    for &i in &queries {
+                        black_box(sets.vers.rank1(i));
+                    }
+                })
+            });
+            group.finish();
+        }
+    }
+}
+
+fn select_benchmark(c: &mut Criterion) {
+    let mut rng = ChaCha8Rng::seed_from_u64(2);
+    for len in SIZES {
+        for density in DENSITIES {
+            let sets = Sets::new(&mut rng, len, density);
+            let ranks: Vec<usize> = (0..1000)
+                .map(|_| rng.random_range(0..sets.positions.len()))
+                .collect();
+
+            let mut group = c.benchmark_group(format!("select:{len}:{density}"));
+            group.bench_function("bit_rank", |b| {
+                b.iter(|| {
+                    for &rank in &ranks {
+                        black_box(sets.bit_rank.select(rank));
+                    }
+                })
+            });
+            group.bench_function("bit_select", |b| {
+                b.iter(|| {
+                    for &rank in &ranks {
+                        black_box(sets.bit_select.select(rank));
+                    }
+                })
+            });
+            group.bench_function("sorted_vec", |b| {
+                b.iter(|| {
+                    for &rank in &ranks {
+                        black_box(sets.positions[rank]);
+                    }
+                })
+            });
+            group.bench_function("succinct", |b| {
+                b.iter(|| {
+                    for &rank in &ranks {
+                        black_box(sets.succinct.select1(rank as u64));
+                    }
+                })
+            });
+            group.bench_function("vers", |b| {
+                b.iter(|| {
+                    for &rank in &ranks {
+                        black_box(sets.vers.select1(rank));
+                    }
+                })
+            });
+            group.finish();
+        }
+    }
+}
+
+/// Compares looking up the bit establishing a rank inside the bit vector with the external
+/// lookup which `rank_and_prev` leaves to the caller when the bit is not in the same chunk.
+fn rank_and_prev_benchmark(c: &mut Criterion) {
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    for density in [0.001, 0.01, 0.1] {
+        let len = 1 << 24;
+        let positions: Vec<usize> = (0..len).filter(|_| rng.random_bool(density)).collect();
+        let br: BitRank = positions.iter().copied().collect();
+        let queries: Vec<usize> = (0..1000).map(|_| rng.random_range(0..len)).collect();
+
+        let mut group = c.benchmark_group(format!("rank_and_prev:{density}"));
+        group.bench_function("rank_and_prev_set_bit", |b| {
+            b.iter(|| {
+                for &i in &queries {
+                    black_box(br.rank_and_prev_set_bit(i));
+                }
+            })
+        });
+        group.bench_function("external_lookup", |b| {
+            b.iter(|| {
+                for &i in &queries {
+                    let rank_select = br.rank_and_prev(i);
+                    let prev = rank_select.prev_or_lookup(|rank| positions.get(rank).copied());
+                    black_box((rank_select.rank, prev));
+                }
+            })
+        });
+        group.bench_function("select", |b| {
+            b.iter(|| {
+                for &i in &queries {
+                    let rank_select = br.rank_and_prev(i);
+                    let prev = rank_select.prev_or_lookup(|rank| br.select(rank));
+                    black_box((rank_select.rank, prev));
+                }
+            })
+        });
+        group.finish();
+    }
+}
+
+/// Measures rank queries on a set which is much larger than the CPU caches, so that the number of
+/// cache lines a query touches dominates.
+fn cold_rank_benchmark(c: &mut Criterion) {
+    let mut rng = ChaCha8Rng::seed_from_u64(3);
+    let len = 1 << 30;
+    let mut builder = BitRankBuilder::with_capacity(len);
+    let mut position = 0;
+    while position < len {
+        builder.push(position);
+        position += rng.random_range(1..200);
+    }
+    let br = builder.finish();
+    let queries: Vec<usize> = (0..1000)
+        .map(|_| rng.random_range(0..br.universe_size()))
+        .collect();
+
+    let mut group = c.benchmark_group(format!("cold_rank:{len}"));
+    group.bench_function("rank", |b| {
+        b.iter(|| {
+            for &i in &queries {
+                black_box(br.rank(i));
+            }
+        })
+    });
+    group.bench_function("rank_unchecked", |b| {
+        b.iter(|| {
+            for &i in &queries {
+                // SAFETY: The queries are smaller than the universe size.
+                black_box(unsafe { br.rank_unchecked(i) });
+            }
+        })
+    });
+    group.bench_function("rank_and_prev", |b| {
+        b.iter(|| {
+            for &i in &queries {
+                black_box(br.rank_and_prev(i));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    rank_benchmark,
+    select_benchmark,
+    rank_and_prev_benchmark,
+    cold_rank_benchmark
+);
+criterion_main!(benches);
@@ -0,0 +1,50 @@
+//! Generates an arbitrary `BitRank`, with or without select samples, and compares its rank and
+//! select answers with the sorted list of its elements.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use string_offsets::bitrank::RankPrev;
+use string_offsets::BitRank;
+
+/// Every index up to this bound is queried. Beyond it, only the indices around the elements are,
+/// so that sets with large gaps stay fast to check. This still spans 16 blocks.
+const MAX_UNIVERSE: usize = 1 << 18;
+
+fuzz_target!(|br: BitRank| {
+    let ones: Vec<usize> = br.iter().collect();
+    assert!(ones.windows(2).all(|w| w[0] < w[1]));
+    assert_eq!(br.max_rank(), ones.len());
+    let end = ones.last().map_or(0, |&p| p + 1);
+
+    // Also query past the end, which is covered by an empty block or no block at all.
+    let around = ones.iter().flat_map(|&p| p.saturating_sub(1)..=p + 1);
+    for i in (0..=end.min(MAX_UNIVERSE) + 300).chain(around) {
+        let rank = ones.partition_point(|&p| p < i);
+        let prev = rank.checked_sub(1).map(|r| ones[r]);
+        let next = ones.get(rank).copied();
+        let contains = next == Some(i);
+        assert_eq!(br.rank(i), rank, "rank({i})");
+        assert_eq!(br.rank0(i), i - rank, "rank0({i})");
+        assert_eq!(br.contains(i), contains, "contains({i})");
+        assert_eq!(br.prev_set_bit(i), prev, "prev_set_bit({i})");
+        assert_eq!(br.next_set_bit(i), next, "next_set_bit({i})");
+        assert_eq!(
+            br.rank_and_prev_set_bit(i),
+            RankPrev { rank, prev },
+            "rank_and_prev_set_bit({i})"
+        );
+        let rank_select = br.rank_and_prev(i);
+        assert_eq!(rank_select.rank, rank, "rank_and_prev({i})");
+        if let Some(p) = rank_select.prev_set_in_chunk {
+            assert_eq!(Some(p), prev, "rank_and_prev({i})");
+        }
+        if !contains {
+            assert_eq!(br.select0(i - rank), Some(i), "select0({})", i - rank);
+        }
+    }
+    for (rank, &position) in ones.iter().enumerate() {
+        assert_eq!(br.select(rank), Some(position), "select({rank})");
+    }
+    assert_eq!(br.select(ones.len()), None);
+});
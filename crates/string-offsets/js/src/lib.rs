@@ -0,0 +1,7 @@
+//! The wasm module of the `string-offsets` npm package.
+//!
+//! The bindings are defined by the `wasm` feature of the `string-offsets` crate. This crate only
+//! links them into a `cdylib`, so that the library itself can be built without `std`, where a
+//! `cdylib` would need a panic handler and a global allocator.
+
+pub use string_offsets::*;
@@ -0,0 +1,210 @@
+//! Byte positions which are kept up to date when the string of a [`StringOffsets`] is edited.
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::StringOffsets;
+
+/// A handle for a byte position registered with [`StringOffsets::anchor`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Anchor {
+    /// The index of the slot in [`Anchors`].
+    slot: usize,
+    /// The generation of the slot when the anchor was registered.
+    generation: usize,
+}
+
+/// To which side an [`Anchor`] moves when text is inserted at its position, or when the text
+/// around it is replaced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Bias {
+    /// The anchor stays in front of inserted text, e.g. for the start of a highlighted range.
+    Left,
+    /// The anchor moves behind inserted text, e.g. for a cursor.
+    Right,
+}
+
+/// A slot of [`Anchors`], which holds the position of an anchor or is free.
+#[derive(Clone)]
+struct Slot {
+    /// Incremented whenever the anchor of the slot is removed, so that the handles of removed
+    /// anchors never refer to an anchor registered later in the same slot.
+    generation: usize,
+    anchor: Option<(usize, Bias)>,
+}
+
+/// The positions of the anchors of a [`StringOffsets`], indexed by the handles. The slots of
+/// removed anchors are reused for new ones.
+#[derive(Clone, Default)]
+pub(crate) struct Anchors {
+    slots: Vec<Slot>,
+    /// The indices of the free slots.
+    free: Vec<usize>,
+}
+
+impl Anchors {
+    /// Moves the anchors for the sorted, non-overlapping edits, given as the replaced byte range
+    /// and the length of the replacement.
+    fn apply_edits(&mut self, edits: &[(Range<usize>, usize)]) {
+        // The number of bytes added by the edits in front of every edit.
+        let shifts: Vec<isize> = edits
+            .iter()
+            .scan(0, |shift, (range, len)| {
+                let before = *shift;
+                *shift += *len as isize - range.len() as isize;
+                Some(before)
+            })
+            .collect();
+        for (position, bias) in self.slots.iter_mut().filter_map(|s| s.anchor.as_mut()) {
+            // The last edit starting at or before the position.
+            let Some(edit) = edits
+                .partition_point(|(range, _)| range.start <= *position)
+                .checked_sub(1)
+            else {
+                continue;
+            };
+            let (range, len) = &edits[edit];
+            let start = range.start.wrapping_add_signed(shifts[edit]);
+            *position = if *position > range.end {
+                position.wrapping_add_signed(shifts[edit] + *len as isize - range.len() as isize)
+            } else if !range.is_empty() && *position == range.start {
+                start
+            } else if !range.is_empty() && *position == range.end {
+                start + len
+            } else {
+                // Text was inserted at the position, or the position was replaced.
+                match bias {
+                    Bias::Left => start,
+                    Bias::Right => start + len,
+                }
+            };
+        }
+    }
+
+    /// The slot of the anchor, unless it was removed.
+    fn get(&self, anchor: Anchor) -> Option<&Slot> {
+        self.slots
+            .get(anchor.slot)
+            .filter(|slot| slot.generation == anchor.generation)
+    }
+
+    /// The number of bytes allocated on the heap.
+    pub(crate) fn heap_size_bytes(&self) -> usize {
+        self.slots.capacity() * core::mem::size_of::<Slot>()
+            + self.free.capacity() * core::mem::size_of::<usize>()
+    }
+}
+
+impl StringOffsets {
+    /// Registers the byte offset `position`, which is moved by [`StringOffsets::apply_edit`] and
+    /// [`StringOffsets::apply_edits`] so that it keeps pointing to the same place in the text.
+    ///
+    /// An edit which only inserts text at the position moves it in front of or behind the
+    /// inserted text depending on `bias`. A position at the start of replaced text stays in front
+    /// of the replacement, one at the end stays behind it, and one within the replaced text moves
+    /// to the side given by `bias`.
+    ///
+    /// Anchors aren't serialized.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_offsets::{Bias, StringOffsets};
+    ///
+    /// let content = "hello world";
+    /// let mut offsets = StringOffsets::new(content);
+    /// let start = offsets.anchor(6, Bias::Left);
+    /// let cursor = offsets.anchor(6, Bias::Right);
+    /// offsets.apply_edit(6..6, "big ", content);
+    /// assert_eq!(offsets.anchor_position(start), Some(6));
+    /// assert_eq!(offsets.anchor_position(cursor), Some(10));
+    /// ```
+    pub fn anchor(&mut self, position: usize, bias: Bias) -> Anchor {
+        let anchors = &mut self.anchors;
+        let slot = anchors.free.pop().unwrap_or_else(|| {
+            anchors.slots.push(Slot {
+                generation: 0,
+                anchor: None,
+            });
+            anchors.slots.len() - 1
+        });
+        anchors.slots[slot].anchor = Some((position, bias));
+        Anchor {
+            slot,
+            generation: anchors.slots[slot].generation,
+        }
+    }
+
+    /// The current byte offset of the anchor, or `None` if it was removed.
+    ///
+    /// An anchor must only be used with the converter which registered it.
+    pub fn anchor_position(&self, anchor: Anchor) -> Option<usize> {
+        self.anchors
+            .get(anchor)
+            .and_then(|slot| slot.anchor)
+            .map(|(position, _)| position)
+    }
+
+    /// Removes the anchor and returns its byte offset, or `None` if it was already removed. Its
+    /// slot is reused for anchors registered later.
+    pub fn remove_anchor(&mut self, anchor: Anchor) -> Option<usize> {
+        self.anchors.get(anchor)?;
+        let slot = &mut self.anchors.slots[anchor.slot];
+        let (position, _) = slot.anchor.take()?;
+        slot.generation += 1;
+        self.anchors.free.push(anchor.slot);
+        Some(position)
+    }
+
+    /// Moves the anchors for edits given as the replaced range and the replacement.
+    pub(crate) fn move_anchors(&mut self, edits: &[(Range<usize>, &str)]) {
+        if self.anchors.slots.len() == self.anchors.free.len() {
+            return;
+        }
+        let edits: Vec<_> = edits
+            .iter()
+            .map(|(range, replacement)| (range.clone(), replacement.len()))
+            .collect();
+        self.anchors.apply_edits(&edits);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Bias, StringOffsets};
+
+    #[test]
+    fn test_anchors() {
+        let content = "0123456789";
+        let mut offsets = StringOffsets::new(content);
+        let anchors: Vec<_> = [0, 2, 3, 4, 5, 6, 9, 10]
+            .into_iter()
+            .flat_map(|p| {
+                [
+                    offsets.anchor(p, Bias::Left),
+                    offsets.anchor(p, Bias::Right),
+                ]
+            })
+            .collect();
+        offsets.apply_edits(&[(2..2, "ab"), (3..6, "x"), (9..10, "")], content);
+        // The content is now "01ab2x678".
+        let positions: Vec<_> = anchors
+            .iter()
+            .map(|a| offsets.anchor_position(*a).unwrap())
+            .collect();
+        assert_eq!(positions, [0, 0, 2, 4, 5, 5, 5, 6, 5, 6, 6, 6, 9, 9, 9, 9]);
+
+        assert_eq!(offsets.remove_anchor(anchors[3]), Some(4));
+        assert_eq!(offsets.remove_anchor(anchors[3]), None);
+        assert_eq!(offsets.anchor_position(anchors[3]), None);
+        // The slot of the removed anchor is reused, but its handle stays invalid.
+        let replaced = offsets.anchor(1, Bias::Left);
+        assert_ne!(replaced, anchors[3]);
+        assert_eq!(offsets.anchors.slots.len(), anchors.len());
+        assert_eq!(offsets.anchor_position(anchors[3]), None);
+        assert_eq!(offsets.remove_anchor(anchors[3]), None);
+        offsets.apply_edit(0..1, "", "01ab2x678");
+        assert_eq!(offsets.anchor_position(replaced), Some(0));
+        assert_eq!(offsets.anchor_position(anchors[15]), Some(8));
+    }
+}
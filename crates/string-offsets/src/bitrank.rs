@@ -3,12 +3,88 @@
 //!
 //! See also: ["Succinct data structure"](https://en.wikipedia.org/wiki/Succinct_data_structure).
 
+use alloc::vec::Vec;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ops::Range;
+
+use select_samples::SelectSamples;
+
+mod adaptive;
+mod aligned;
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+#[cfg(feature = "rkyv")]
+mod archived;
+#[cfg(feature = "bitvec")]
+mod bitvec;
+mod blocks;
+mod concat;
+mod convert;
+mod cursor;
+mod debug;
+mod dynamic;
+mod elias_fano;
+mod gap;
+mod iter;
+mod multiset;
+mod ops;
+mod packed;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod partial;
+mod prefetch;
+mod rle;
+#[cfg(feature = "roaring")]
+mod roaring;
+mod rrr;
+mod select;
+mod select_samples;
+#[cfg(feature = "serde")]
+mod serde;
+mod serialize;
+#[cfg(feature = "simd")]
+mod simd;
+mod snapshot;
+mod sparse;
+mod stats;
+mod verify;
+mod view;
+mod wavelet;
+
+pub use adaptive::AdaptiveBitRank;
+pub use aligned::AlignedBitRank;
+pub use blocks::{BlockRef, Blocks};
+pub use cursor::BitRankCursor;
+pub use dynamic::DynamicBitRank;
+pub use elias_fano::EliasFano;
+pub use gap::GapBitRank;
+pub use iter::Iter;
+pub use multiset::MultiBitRank;
+pub use partial::PartialBitRank;
+pub use rle::RleBitRank;
+pub use rrr::RrrBitRank;
+pub use select::BitSelect;
+#[cfg(feature = "std")]
+pub use serialize::BitRankWriter;
+pub use serialize::FormatError;
+pub use snapshot::BitRankSnapshot;
+pub use sparse::SparseBitRank;
+pub use stats::BitRankStats;
+pub use verify::VerifyError;
+pub use view::BitRankRef;
+pub use wavelet::WaveletTree;
+
 type SubblockBits = u128;
 
-// Static sizing of the various components of the data structure.
-const BITS_PER_BLOCK: usize = 16384;
+// Sizing of the various components of the data structure. The number of sub-blocks per block can
+// be configured with the const generic parameter of `BitRank`.
 const BITS_PER_SUB_BLOCK: usize = SubblockBits::BITS as usize;
-const SUB_BLOCKS_PER_BLOCK: usize = BITS_PER_BLOCK / BITS_PER_SUB_BLOCK;
+/// The number of 128-bit sub-blocks per block of a [`BitRank`] unless configured otherwise. This
+/// results in blocks of 16384 bits.
+pub const DEFAULT_SUB_BLOCKS_PER_BLOCK: usize = 128;
+/// The number of bits per block in the default configuration.
+const BITS_PER_BLOCK: usize = DEFAULT_SUB_BLOCKS_PER_BLOCK * BITS_PER_SUB_BLOCK;
 
 /// A container for a portion of the total bit vector and the associated indices.
 /// The bits within each chunk are stored from most significant bit (msb) to least significant bit (lsb).
@@ -26,25 +102,59 @@ const SUB_BLOCKS_PER_BLOCK: usize = BITS_PER_BLOCK / BITS_PER_SUB_BLOCK;
 /// block rank:      [           0            ]
 /// sub-block rank:  [     0     ][     2     ]
 /// ```
-#[derive(Clone, Debug)]
-struct Block {
+///
+/// Blocks are aligned to cache lines and keep their fields in declaration order, so that the
+/// block rank shares a cache line with the first sub-block ranks and no 128-bit chunk straddles
+/// two cache lines. A rank query therefore touches at most three cache lines.
+#[derive(Clone)]
+#[repr(C, align(64))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+struct Block<const SUB_BLOCKS_PER_BLOCK: usize = DEFAULT_SUB_BLOCKS_PER_BLOCK> {
     /// Rank of the first bit in this block (that is, the number of bits set in previous blocks).
     rank: u64,
     /// Rank of the first bit (bit 0) of each subblock, relative to the start of the block.
     /// That is, `sub_blocks[i]` is the number of bits set in the `bits` representing
     /// sub-blocks `0..i`. `sub_blocks[0]` is always zero.
+    ///
+    /// With the `compact` feature, this table is omitted and the ranks are recomputed by counting
+    /// the bits of the preceding sub-blocks.
+    #[cfg(not(feature = "compact"))]
     sub_blocks: [u16; SUB_BLOCKS_PER_BLOCK],
     /// The bit-vector.
     bits: [SubblockBits; SUB_BLOCKS_PER_BLOCK],
 }
 
-impl Block {
+impl<const SUB_BLOCKS_PER_BLOCK: usize> Block<SUB_BLOCKS_PER_BLOCK> {
+    /// The number of bits per block.
+    const BITS: usize = SUB_BLOCKS_PER_BLOCK * BITS_PER_SUB_BLOCK;
+
+    /// A block without any bits set.
+    ///
+    /// Without this declared as a `const`, rustc 1.82 creates the Block value on the stack first,
+    /// then `memcpy`s it into the `Vec` of blocks.
+    const ZERO: Self = {
+        // The sub-block ranks are stored as `u16`.
+        assert!(
+            SUB_BLOCKS_PER_BLOCK > 0 && Self::BITS <= u16::MAX as usize,
+            "unsupported number of sub-blocks per block"
+        );
+        Self {
+            rank: 0,
+            #[cfg(not(feature = "compact"))]
+            sub_blocks: [0; SUB_BLOCKS_PER_BLOCK],
+            bits: [0; SUB_BLOCKS_PER_BLOCK],
+        }
+    };
+
     /// Set a bit without updating `self.sub_blocks`.
     ///
     /// This panics if the bit was already set, because that indicates that the original positions
     /// list is invalid/had duplicates.
     fn set(&mut self, index: usize) {
-        assert!(index < BITS_PER_BLOCK);
+        assert!(index < Self::BITS);
         let chunk_idx = index / BITS_PER_SUB_BLOCK;
         let bit_idx = index % BITS_PER_SUB_BLOCK;
         let mask = 1 << ((BITS_PER_SUB_BLOCK - 1) - bit_idx);
@@ -52,15 +162,86 @@ impl Block {
         self.bits[chunk_idx] ^= mask;
     }
 
+    /// Set all bits of `chunk` in the chunk with the given index without updating
+    /// `self.sub_blocks`.
+    ///
+    /// Like [`Block::set`], this panics if any of the bits was already set.
+    fn set_chunk(&mut self, chunk_idx: usize, chunk: SubblockBits) {
+        assert_eq!(self.bits[chunk_idx] & chunk, 0, "toggling bits off indicates that the original data was incorrect, most likely containing duplicate values.");
+        self.bits[chunk_idx] |= chunk;
+    }
+
+    /// Recomputes `self.sub_blocks` from the bits and returns the number of bits set in this
+    /// block.
+    #[cfg(not(feature = "compact"))]
+    fn update_sub_blocks(&mut self) -> u64 {
+        let mut local_rank = 0;
+        for (i, chunk) in self.bits.iter().enumerate() {
+            self.sub_blocks[i] = local_rank;
+            local_rank += chunk.count_ones() as u16;
+        }
+        local_rank as u64
+    }
+
+    /// Returns the number of bits set in this block. There is no sub-block table to update in the
+    /// compact representation.
+    #[cfg(feature = "compact")]
+    fn update_sub_blocks(&mut self) -> u64 {
+        count_ones(&self.bits) as u64
+    }
+
+    /// The number of bits set in the sub-blocks `0..sub_block`.
+    #[cfg(not(feature = "compact"))]
+    fn sub_block_rank(&self, sub_block: usize) -> usize {
+        self.sub_blocks[sub_block] as usize
+    }
+
+    /// The number of bits set in the sub-blocks `0..sub_block`.
+    #[cfg(feature = "compact")]
+    fn sub_block_rank(&self, sub_block: usize) -> usize {
+        count_ones(&self.bits[..sub_block])
+    }
+
+    /// The number of bits set in the sub-blocks `0..sub_block`, given that `rank` bits are set in
+    /// the sub-blocks `0..from` for some `from <= sub_block`.
+    #[cfg(not(feature = "compact"))]
+    fn sub_block_rank_from(&self, _from: usize, _rank: usize, sub_block: usize) -> usize {
+        self.sub_block_rank(sub_block)
+    }
+
+    /// The number of bits set in the sub-blocks `0..sub_block`, given that `rank` bits are set in
+    /// the sub-blocks `0..from` for some `from <= sub_block`.
+    #[cfg(feature = "compact")]
+    fn sub_block_rank_from(&self, from: usize, rank: usize, sub_block: usize) -> usize {
+        rank + count_ones(&self.bits[from..sub_block])
+    }
+
+    /// Prefetches the parts of the block which are read when computing the rank of the local
+    /// index.
+    fn prefetch(&self, local_idx: usize) {
+        let sub_block = local_idx / BITS_PER_SUB_BLOCK;
+        prefetch::prefetch(&self.rank);
+        #[cfg(not(feature = "compact"))]
+        prefetch::prefetch(&self.sub_blocks[sub_block]);
+        prefetch::prefetch(&self.bits[sub_block]);
+    }
+
+    /// Returns true if the bit at the local index is set.
+    fn get(&self, index: usize) -> bool {
+        let chunk_idx = index / BITS_PER_SUB_BLOCK;
+        let bit_idx = index % BITS_PER_SUB_BLOCK;
+        self.bits[chunk_idx] & (1 << ((BITS_PER_SUB_BLOCK - 1) - bit_idx)) != 0
+    }
+
     /// The **total rank** of the block relative local index, and the index of the one
     /// bit that establishes that rank (aka "select") **if** it occurs within that same
     /// chunk, otherwise ['None'].  The assumption is that if you would have to look back
     /// through previous chunks it would actually be cheaper to do a lookup in the original
     /// data structure that the bit vector was created from.
-    fn rank_select(&self, local_idx: usize) -> (usize, Option<usize>) {
+    fn rank_and_prev(&self, local_idx: usize) -> RankSelect {
         let mut rank = self.rank as usize;
         let sub_block = local_idx / BITS_PER_SUB_BLOCK;
-        rank += self.sub_blocks[sub_block] as usize;
+        rank += self.sub_block_rank(sub_block);
 
         let remainder = local_idx % BITS_PER_SUB_BLOCK;
 
@@ -76,66 +257,444 @@ impl Block {
         } else {
             Some(local_idx - masked.trailing_zeros() as usize - 1)
         };
-        (rank, select)
+        RankSelect {
+            rank,
+            prev_set_in_chunk: select,
+        }
+    }
+
+    /// The local index of the set bit with the given rank relative to the start of the block.
+    ///
+    /// `local_rank` must be smaller than the number of bits set in this block.
+    #[cfg(not(feature = "compact"))]
+    fn select(&self, local_rank: usize) -> usize {
+        // `sub_blocks[0]` is always zero, so the partition point is at least 1.
+        let sub_block = partition_point_le(&self.sub_blocks, local_rank as u16) - 1;
+        let remainder = local_rank - self.sub_blocks[sub_block] as usize;
+        sub_block * BITS_PER_SUB_BLOCK + select_in_chunk(self.bits[sub_block], remainder)
+    }
+
+    /// The local index of the set bit with the given rank relative to the start of the block.
+    ///
+    /// `local_rank` must be smaller than the number of bits set in this block.
+    #[cfg(feature = "compact")]
+    fn select(&self, local_rank: usize) -> usize {
+        self.scan(local_rank, |chunk| chunk)
+    }
+
+    /// Finds the sub-block containing the bit with the given rank by counting the bits of
+    /// `map(chunk)` for all chunks from the start of the block, and returns the local index of
+    /// that bit.
+    #[cfg(feature = "compact")]
+    fn scan(&self, mut local_rank: usize, map: impl Fn(SubblockBits) -> SubblockBits) -> usize {
+        for (i, &chunk) in self.bits.iter().enumerate() {
+            let chunk = map(chunk);
+            let ones = chunk.count_ones() as usize;
+            if local_rank < ones {
+                return i * BITS_PER_SUB_BLOCK + select_in_chunk(chunk, local_rank);
+            }
+            local_rank -= ones;
+        }
+        panic!("not enough bits in block");
+    }
+
+    /// The local index of the unset bit with the given rank relative to the start of the block.
+    ///
+    /// `local_rank0` must be smaller than the number of bits unset in this block.
+    #[cfg(not(feature = "compact"))]
+    fn select0(&self, local_rank0: usize) -> usize {
+        // Sub-block 0 starts with zero unset bits, so the partition point is at least 1.
+        let sub_block = partition_point(SUB_BLOCKS_PER_BLOCK, |i| {
+            i * BITS_PER_SUB_BLOCK - self.sub_blocks[i] as usize <= local_rank0
+        }) - 1;
+        let remainder =
+            local_rank0 - (sub_block * BITS_PER_SUB_BLOCK - self.sub_blocks[sub_block] as usize);
+        sub_block * BITS_PER_SUB_BLOCK + select_in_chunk(!self.bits[sub_block], remainder)
+    }
+
+    /// The local index of the unset bit with the given rank relative to the start of the block.
+    ///
+    /// `local_rank0` must be smaller than the number of bits unset in this block.
+    #[cfg(feature = "compact")]
+    fn select0(&self, local_rank0: usize) -> usize {
+        self.scan(local_rank0, |chunk| !chunk)
+    }
+
+    /// Returns true if no bit is set in this block.
+    fn is_empty(&self) -> bool {
+        self.total_rank() == self.rank as usize
     }
 
     fn total_rank(&self) -> usize {
-        self.sub_blocks[SUB_BLOCKS_PER_BLOCK - 1] as usize
+        self.sub_block_rank(SUB_BLOCKS_PER_BLOCK - 1)
             + self.rank as usize
-            + self.bits[SUB_BLOCKS_PER_BLOCK - 1..]
-                .iter()
-                .map(|c| c.count_ones() as usize)
-                .sum::<usize>()
+            + count_ones(&self.bits[SUB_BLOCKS_PER_BLOCK - 1..])
     }
 }
 
+/// The number of bits set in `chunks`.
+///
+/// With the `simd` feature, this uses AVX2 or NEON instructions if the CPU supports them.
+fn count_ones(chunks: &[SubblockBits]) -> usize {
+    #[cfg(feature = "simd")]
+    let count = simd::count_ones(chunks);
+    #[cfg(not(feature = "simd"))]
+    let count = chunks.iter().map(|c| c.count_ones() as usize).sum();
+    count
+}
+
+/// The number of entries of the increasing `ranks` which are smaller than or equal to `value`.
+///
+/// With the `simd` feature, this uses AVX2 or NEON instructions if the CPU supports them.
+#[cfg(not(feature = "compact"))]
+fn partition_point_le(ranks: &[u16], value: u16) -> usize {
+    #[cfg(feature = "simd")]
+    let count = simd::count_le(ranks, value);
+    #[cfg(not(feature = "simd"))]
+    let count = ranks.partition_point(|&r| r <= value);
+    count
+}
+
+/// The index (counted from the most significant bit) of the set bit with the given rank within
+/// `chunk`.
+///
+/// `rank` must be smaller than `chunk.count_ones()`.
+fn select_in_chunk(mut chunk: SubblockBits, rank: usize) -> usize {
+    debug_assert!(rank < chunk.count_ones() as usize);
+    for _ in 0..rank {
+        // Clear the most significant set bit.
+        chunk &= !(1 << (BITS_PER_SUB_BLOCK - 1 - chunk.leading_zeros() as usize));
+    }
+    chunk.leading_zeros() as usize
+}
+
+/// Returns the first index in `0..len` for which `pred` returns false, assuming that `pred` is
+/// true for a prefix of the indices and false for the rest (see [`slice::partition_point`]).
+fn partition_point(len: usize, pred: impl Fn(usize) -> bool) -> usize {
+    let (mut lo, mut hi) = (0, len);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(mid) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    lo
+}
+
+/// The result of [`BitRank::rank_and_prev`]: the rank at a position and the element establishing
+/// that rank, if it is cheap to find.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RankSelect {
+    /// The number of elements smaller than the queried position.
+    pub rank: usize,
+    /// The largest element smaller than the queried position **if** it falls into the same
+    /// 128-bit chunk as the position, otherwise `None`.
+    ///
+    /// `None` does not mean that there is no smaller element, see
+    /// [`RankSelect::prev_or_lookup`] and [`BitRank::rank_and_prev_set_bit`].
+    pub prev_set_in_chunk: Option<usize>,
+}
+
+impl RankSelect {
+    /// The largest element smaller than the queried position, or `None` if there is none.
+    ///
+    /// If the element isn't in the same chunk, it is looked up with `select`, which is called
+    /// with the rank of the element, i.e. `rank - 1`. This is usually [`BitRank::select`] or a
+    /// lookup in the data the set was created from.
+    pub fn prev_or_lookup(self, select: impl FnOnce(usize) -> Option<usize>) -> Option<usize> {
+        self.prev_set_in_chunk
+            .or_else(|| self.rank.checked_sub(1).and_then(select))
+    }
+}
+
+/// The result of [`BitRank::rank_and_prev_set_bit`]: the rank at a position and the element
+/// establishing that rank.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RankPrev {
+    /// The number of elements smaller than the queried position.
+    pub rank: usize,
+    /// The largest element smaller than the queried position, which is only `None` if `rank` is
+    /// zero.
+    pub prev: Option<usize>,
+}
+
+impl From<RankSelect> for (usize, Option<usize>) {
+    fn from(rank_select: RankSelect) -> Self {
+        (rank_select.rank, rank_select.prev_set_in_chunk)
+    }
+}
+
+/// An error which is returned by [`BitRankBuilder::try_push`] for invalid positions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PushError {
+    /// The position is smaller than the largest position added before.
+    OutOfOrder {
+        /// The rejected position.
+        position: usize,
+        /// The largest position added before.
+        last: usize,
+    },
+    /// The position was already added.
+    Duplicate(usize),
+}
+
+impl fmt::Display for PushError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfOrder { position, last } => {
+                write!(
+                    f,
+                    "position {position} is smaller than the previous position {last}"
+                )
+            }
+            Self::Duplicate(position) => write!(f, "position {position} was already added"),
+        }
+    }
+}
+
+impl core::error::Error for PushError {}
+
 /// Builder for creating a [`BitRank`].
 ///
 /// # Examples
 ///
-/// ```text
+/// ```
+/// use string_offsets::BitRankBuilder;
+///
 /// let mut builder = BitRankBuilder::new();
 /// builder.push(17);
 /// builder.push(23);
 /// builder.push(102);
 /// let set = builder.finish();
 /// assert_eq!(set.rank(100), 2);
+/// assert_eq!(set.select(2), Some(102));
 /// ```
-#[derive(Default)]
-pub struct BitRankBuilder {
-    blocks: Vec<Block>,
+///
+/// Builders for other block sizes are created with [`Default`], e.g.
+/// `BitRankBuilder::<32>::default()`.
+pub struct BitRankBuilder<const SUB_BLOCKS_PER_BLOCK: usize = DEFAULT_SUB_BLOCKS_PER_BLOCK> {
+    blocks: Vec<Block<SUB_BLOCKS_PER_BLOCK>>,
+    /// The largest position which was added so far.
+    last_position: Option<usize>,
+    /// Whether positions which were already added are skipped instead of causing a panic.
+    allow_duplicates: bool,
+    /// The number of positions which are expected to be added, see
+    /// [`BitRankBuilder::with_capacity_and_density`].
+    expected_ones: usize,
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> Default for BitRankBuilder<SUB_BLOCKS_PER_BLOCK> {
+    fn default() -> Self {
+        Self {
+            blocks: Vec::new(),
+            last_position: None,
+            allow_duplicates: false,
+            expected_ones: 0,
+        }
+    }
 }
 
 impl BitRankBuilder {
     /// Returns a new builder.
-    #[cfg(test)]
     pub fn new() -> Self {
         Self::default()
     }
 
     /// Returns a builder that can hold integers with values `0..cap`.
     pub fn with_capacity(cap: usize) -> Self {
+        Self::with_capacity_and_density(cap, 0)
+    }
+
+    /// Returns a builder that can hold integers with values `0..cap`, of which about
+    /// `expected_ones` are going to be added.
+    ///
+    /// Besides the blocks, this pre-allocates the structures which are built from the positions
+    /// when finishing the set in another representation, like the Elias–Fano encoding of
+    /// [`BitRankBuilder::finish_sparse`], so that huge builds don't reallocate them.
+    pub fn with_capacity_and_density(cap: usize, expected_ones: usize) -> Self {
         Self {
             blocks: Vec::with_capacity(cap.div_ceil(BITS_PER_BLOCK)),
+            last_position: None,
+            allow_duplicates: false,
+            expected_ones,
         }
     }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> BitRankBuilder<SUB_BLOCKS_PER_BLOCK> {
+    /// The number of bits per block.
+    const BITS_PER_BLOCK: usize = Block::<SUB_BLOCKS_PER_BLOCK>::BITS;
 
     fn finish_last_block(&mut self) -> u64 {
         if let Some(block) = self.blocks.last_mut() {
-            let mut local_rank = 0;
-            for (i, chunk) in block.bits.iter().enumerate() {
-                block.sub_blocks[i] = local_rank;
-                local_rank += chunk.count_ones() as u16;
-            }
-            block.rank + local_rank as u64
+            block.rank + block.update_sub_blocks()
         } else {
             0
         }
     }
 
+    /// Sets whether positions which were already added are silently skipped. By default, adding
+    /// a position twice panics (or returns [`PushError::Duplicate`] from
+    /// [`BitRankBuilder::try_push`]), since it usually indicates invalid input.
+    ///
+    /// Positions must still be added in increasing order apart from the duplicates.
+    pub fn allow_duplicates(mut self, allow: bool) -> Self {
+        self.allow_duplicates = allow;
+        self
+    }
+
+    /// Returns true if `position` was already added.
+    fn is_set(&self, position: usize) -> bool {
+        self.blocks
+            .get(position / Self::BITS_PER_BLOCK)
+            .is_some_and(|block| block.get(position % Self::BITS_PER_BLOCK))
+    }
+
     /// Adds a bit. Bits must be added in order of increasing `position`.
     pub fn push(&mut self, position: usize) {
-        let block_id = position / BITS_PER_BLOCK;
+        if self.allow_duplicates && self.is_set(position) {
+            return;
+        }
+        self.last_block_for(position)
+            .set(position % Self::BITS_PER_BLOCK);
+        self.last_position = self.last_position.max(Some(position));
+    }
+
+    /// Removes the largest position which was added, i.e. the most recently added one, and returns
+    /// it. Returns `None` if no position was added.
+    ///
+    /// Afterwards, positions can be added again starting after the new largest position.
+    pub fn pop(&mut self) -> Option<usize> {
+        let position = self.last_position?;
+        let local_idx = position % Self::BITS_PER_BLOCK;
+        let block = self.blocks.last_mut().expect("a position was added");
+        block.bits[local_idx / BITS_PER_SUB_BLOCK] &=
+            !(1 << (BITS_PER_SUB_BLOCK - 1 - local_idx % BITS_PER_SUB_BLOCK));
+        // Drop the blocks which became empty, including the ones which were created to skip over
+        // a range without elements.
+        while let Some(block) = self.blocks.last() {
+            if block.bits.iter().any(|&bits| bits != 0) {
+                break;
+            }
+            self.blocks.pop();
+        }
+        self.last_position = self.blocks.last().map(|block| {
+            let (sub_block, bits) = block
+                .bits
+                .iter()
+                .enumerate()
+                .rfind(|(_, &bits)| bits != 0)
+                .expect("empty blocks were dropped");
+            (self.blocks.len() - 1) * Self::BITS_PER_BLOCK
+                + sub_block * BITS_PER_SUB_BLOCK
+                + BITS_PER_SUB_BLOCK
+                - 1
+                - bits.trailing_zeros() as usize
+        });
+        Some(position)
+    }
+
+    /// Adds a bit like [`BitRankBuilder::push`], but returns an error instead of panicking if
+    /// `position` isn't larger than all previously added positions. The builder is unchanged in
+    /// that case.
+    pub fn try_push(&mut self, position: usize) -> Result<(), PushError> {
+        if self.is_set(position) {
+            return if self.allow_duplicates {
+                Ok(())
+            } else {
+                Err(PushError::Duplicate(position))
+            };
+        }
+        match self.last_position {
+            Some(last) if last > position => Err(PushError::OutOfOrder { position, last }),
+            _ => {
+                self.push(position);
+                Ok(())
+            }
+        }
+    }
+
+    /// Adds many bits at once. Bits must be added in order of increasing `position`.
+    ///
+    /// Instead of setting every bit individually, the positions falling into the same chunk are
+    /// collected into a single word first, which makes this considerably faster than calling
+    /// [`BitRankBuilder::push`] for every position of a dense set.
+    pub fn push_many<I: IntoIterator<Item = usize>>(&mut self, positions: I) {
+        let mut chunk_idx = 0;
+        let mut chunk: SubblockBits = 0;
+        let mut prev = None;
+        for position in positions {
+            if self.allow_duplicates && prev == Some(position) {
+                continue;
+            }
+            assert!(
+                prev.is_none_or(|prev| prev < position),
+                "positions must be increasing!"
+            );
+            prev = Some(position);
+            if position / BITS_PER_SUB_BLOCK != chunk_idx {
+                if chunk != 0 {
+                    self.set_chunk(chunk_idx, chunk);
+                }
+                chunk_idx = position / BITS_PER_SUB_BLOCK;
+                chunk = 0;
+            }
+            chunk |= 1 << (BITS_PER_SUB_BLOCK - 1 - position % BITS_PER_SUB_BLOCK);
+        }
+        if chunk != 0 {
+            self.set_chunk(chunk_idx, chunk);
+        }
+    }
+
+    /// Adds the `positions` in any order and with duplicates, by sorting and deduplicating them
+    /// first. All of them must still be larger than the previously added positions.
+    pub fn push_unsorted(&mut self, mut positions: Vec<usize>) {
+        positions.sort_unstable();
+        positions.dedup();
+        self.push_many(positions);
+    }
+
+    /// Adds all bits in `positions`. The run must start after all previously added bits.
+    ///
+    /// Chunks which are fully covered by the run are filled in a single step.
+    pub fn push_run(&mut self, positions: Range<usize>) {
+        if positions.is_empty() {
+            return;
+        }
+        let first_chunk = positions.start / BITS_PER_SUB_BLOCK;
+        let last_chunk = (positions.end - 1) / BITS_PER_SUB_BLOCK;
+        for chunk_idx in first_chunk..=last_chunk {
+            let mask = ops::prefix_mask(chunk_idx, positions.end)
+                & !ops::prefix_mask(chunk_idx, positions.start);
+            self.set_chunk(chunk_idx, mask);
+        }
+    }
+
+    /// Sets the bits of `chunk` in the chunk with the given index (counted over all blocks).
+    fn set_chunk(&mut self, chunk_idx: usize, chunk: SubblockBits) {
+        let position = chunk_idx * BITS_PER_SUB_BLOCK;
+        let allow_duplicates = self.allow_duplicates;
+        let block = self.last_block_for(position);
+        let local_chunk_idx = (position % Self::BITS_PER_BLOCK) / BITS_PER_SUB_BLOCK;
+        let chunk = if allow_duplicates {
+            chunk & !block.bits[local_chunk_idx]
+        } else {
+            chunk
+        };
+        if chunk == 0 {
+            return;
+        }
+        block.set_chunk(local_chunk_idx, chunk);
+        let last = position + BITS_PER_SUB_BLOCK - 1 - chunk.trailing_zeros() as usize;
+        self.last_position = self.last_position.max(Some(last));
+    }
+
+    /// Returns the block containing `position`, which must be the last block. Missing blocks are
+    /// created and the previous last block is finished.
+    fn last_block_for(&mut self, position: usize) -> &mut Block<SUB_BLOCKS_PER_BLOCK> {
+        let block_id = position / Self::BITS_PER_BLOCK;
         assert!(
             self.blocks.len() <= block_id + 1,
             "positions must be increasing!"
@@ -143,45 +702,282 @@ impl BitRankBuilder {
         if block_id >= self.blocks.len() {
             let curr_rank = self.finish_last_block();
             while block_id >= self.blocks.len() {
-                // Without this declared as a `const`, rustc 1.82 creates the Block value on the
-                // stack first, then `memcpy`s it into `self.blocks`.
-                const ZERO_BLOCK: Block = Block {
-                    rank: 0,
-                    sub_blocks: [0; SUB_BLOCKS_PER_BLOCK],
-                    bits: [0; SUB_BLOCKS_PER_BLOCK],
-                };
-                self.blocks.push(ZERO_BLOCK);
+                self.blocks.push(Block::ZERO);
                 self.blocks.last_mut().expect("just inserted").rank = curr_rank;
             }
         }
         self.blocks
             .last_mut()
             .expect("just ensured there are enough blocks")
-            .set(position % BITS_PER_BLOCK);
     }
 
     /// Finishes the `BitRank` by writing the last block of data.
-    pub fn finish(mut self) -> BitRank {
+    ///
+    /// Capacity which was reserved for blocks that weren't needed is released.
+    pub fn finish(mut self) -> BitRank<SUB_BLOCKS_PER_BLOCK> {
         self.finish_last_block();
+        self.blocks.shrink_to_fit();
         BitRank {
             blocks: self.blocks,
+            select_samples: None,
         }
     }
+
+    /// Finishes the `BitRank` like [`BitRankBuilder::finish`], but keeps the builder and its
+    /// allocation, so that it can be used for the next set without allocating again.
+    ///
+    /// The blocks are copied into an allocation of the exact size, and the builder is empty
+    /// afterwards.
+    pub fn finish_reuse(&mut self) -> BitRank<SUB_BLOCKS_PER_BLOCK> {
+        self.finish_last_block();
+        let bit_rank = BitRank {
+            blocks: self.blocks.as_slice().to_vec(),
+            select_samples: None,
+        };
+        self.clear();
+        bit_rank
+    }
+
+    /// Removes all bits which were added, keeping the allocated memory.
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+        self.last_position = None;
+    }
 }
 
 /// An immutable set of unsigned integers with an efficient `rank` method.
+///
+/// A `BitRank` is created with a [`BitRankBuilder`]. It stores the set as a bit vector together
+/// with a two-level rank index, so that [`BitRank::rank`] runs in O(1) time and
+/// [`BitRank::select`] in O(log n) time, or in constant time with
+/// [`BitRank::with_select_samples`]. The memory consumption is proportional to the largest
+/// element in the set, not to the number of elements.
+///
+/// The bit vector is split into blocks of `SUB_BLOCKS_PER_BLOCK * 128` bits (16384 by default).
+/// Smaller blocks need less memory for sets whose largest element isn't a multiple of the block
+/// size, larger blocks reduce the number of per-block ranks that have to be stored.
+/// `SUB_BLOCKS_PER_BLOCK` must be in the range `1..512`.
 #[derive(Clone)]
-pub struct BitRank {
-    blocks: Vec<Block>,
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    rkyv(bytecheck(verify))
+)]
+pub struct BitRank<const SUB_BLOCKS_PER_BLOCK: usize = DEFAULT_SUB_BLOCKS_PER_BLOCK> {
+    blocks: Vec<Block<SUB_BLOCKS_PER_BLOCK>>,
+    /// The optional select index, see [`BitRank::with_select_samples`]. It is not archived.
+    #[cfg_attr(feature = "rkyv", rkyv(with = rkyv::with::Skip))]
+    select_samples: Option<SelectSamples>,
 }
 
-impl BitRank {
+impl<const SUB_BLOCKS_PER_BLOCK: usize> BitRank<SUB_BLOCKS_PER_BLOCK> {
+    /// The number of bits per block.
+    const BITS_PER_BLOCK: usize = Block::<SUB_BLOCKS_PER_BLOCK>::BITS;
+
+    /// Creates a `BitRank` from blocks whose bits are set, but whose rank index still needs to be
+    /// computed.
+    ///
+    /// Trailing blocks without any bits set are dropped, since they don't affect any query.
+    fn from_blocks(mut blocks: Vec<Block<SUB_BLOCKS_PER_BLOCK>>) -> Self {
+        let mut rank = 0;
+        for block in &mut blocks {
+            block.rank = rank;
+            rank += block.update_sub_blocks();
+        }
+        while blocks.last().is_some_and(Block::is_empty) {
+            blocks.pop();
+        }
+        Self {
+            blocks,
+            select_samples: None,
+        }
+    }
+
     /// The rank at the specified index (exclusive).
     ///
     /// The (one) rank is defined as: `rank(i) = sum(b[j] for j in 0..i)`
     /// i.e. the number of elements less than `i`.
     pub fn rank(&self, idx: usize) -> usize {
-        self.rank_select(idx).0
+        self.rank_and_prev(idx).rank
+    }
+
+    /// The rank at the specified index (inclusive), i.e. the number of elements less than or equal
+    /// to `idx`. Unlike `rank(idx + 1)`, this doesn't overflow for `usize::MAX`.
+    pub fn rank_inclusive(&self, idx: usize) -> usize {
+        // No element can be `usize::MAX`, since the blocks would cover more than `usize::MAX`
+        // positions.
+        idx.checked_add(1)
+            .map_or(self.max_rank(), |next| self.rank(next))
+    }
+
+    /// The same as [`BitRank::rank`], but returns `None` instead of [`BitRank::max_rank`] for
+    /// indices past [`BitRank::universe_size`], so that queries beyond the positions the set was
+    /// built for can be detected. The universe size itself is still a valid (exclusive) index.
+    pub fn try_rank(&self, idx: usize) -> Option<usize> {
+        (idx <= self.universe_size()).then(|| self.rank(idx))
+    }
+
+    /// The same as [`BitRank::rank`], but without checking whether `idx` is covered by a block.
+    /// This saves a branch in hot loops whose indices were validated up front.
+    ///
+    /// # Safety
+    ///
+    /// `idx` must be smaller than [`BitRank::universe_size`], otherwise the behavior is undefined.
+    pub unsafe fn rank_unchecked(&self, idx: usize) -> usize {
+        debug_assert!(idx < self.universe_size(), "index out of bounds");
+        // SAFETY: The caller guarantees that `idx` is smaller than the universe size, which is the
+        // number of blocks times the bits per block.
+        let block = unsafe { self.blocks.get_unchecked(idx / Self::BITS_PER_BLOCK) };
+        block.rank_and_prev(idx % Self::BITS_PER_BLOCK).rank
+    }
+
+    /// The number of elements within the half-open `range`, i.e. `rank(range.end) -
+    /// rank(range.start)`.
+    ///
+    /// Returns 0 for empty (or reversed) ranges. Like [`BitRank::rank`], ends past the last
+    /// element are clamped.
+    pub fn rank_range(&self, range: Range<usize>) -> usize {
+        if range.start >= range.end {
+            return 0;
+        }
+        let block_num = range.start / Self::BITS_PER_BLOCK;
+        if block_num >= self.blocks.len() {
+            0
+        } else if block_num == range.end / Self::BITS_PER_BLOCK {
+            // Both ends fall into the same block, so only the in-block ranks need computing.
+            let block = &self.blocks[block_num];
+            block.rank_and_prev(range.end % Self::BITS_PER_BLOCK).rank
+                - block.rank_and_prev(range.start % Self::BITS_PER_BLOCK).rank
+        } else {
+            self.rank(range.end) - self.rank(range.start)
+        }
+    }
+
+    /// The ranks at all of the specified indices, i.e. the same as
+    /// `sorted_indices.iter().map(|&i| self.rank(i)).collect()`.
+    ///
+    /// The indices are processed in order while remembering the sub-block of the previous query,
+    /// so increasing indices which are close to each other are answered without locating their
+    /// sub-block from scratch. Indices which are not sorted are still handled correctly, they
+    /// only lose this benefit.
+    pub fn rank_many(&self, sorted_indices: &[usize]) -> Vec<usize> {
+        let max_rank = self.max_rank();
+        // The block and sub-block of the previous query together with the rank of the sub-block
+        // relative to its block.
+        let mut cursor = None;
+        sorted_indices
+            .iter()
+            .map(|&idx| {
+                let block_num = idx / Self::BITS_PER_BLOCK;
+                let Some(block) = self.blocks.get(block_num) else {
+                    return max_rank;
+                };
+                let local_idx = idx % Self::BITS_PER_BLOCK;
+                let sub_block = local_idx / BITS_PER_SUB_BLOCK;
+                let sub_block_rank = match cursor {
+                    Some((prev_block, prev_sub_block, prev_rank))
+                        if prev_block == block_num && prev_sub_block <= sub_block =>
+                    {
+                        block.sub_block_rank_from(prev_sub_block, prev_rank, sub_block)
+                    }
+                    _ => block.sub_block_rank(sub_block),
+                };
+                cursor = Some((block_num, sub_block, sub_block_rank));
+                let remainder = local_idx % BITS_PER_SUB_BLOCK;
+                let masked = if remainder == 0 {
+                    0
+                } else {
+                    block.bits[sub_block] >> (BITS_PER_SUB_BLOCK - remainder)
+                };
+                block.rank as usize + sub_block_rank + masked.count_ones() as usize
+            })
+            .collect()
+    }
+
+    /// The ranks at all of the specified indices in arbitrary order, i.e. the same as
+    /// `indices.iter().map(|&i| self.rank(i)).collect()`.
+    ///
+    /// While computing the rank of one index, the memory needed for a later index is prefetched.
+    /// For large sets with random queries, this hides most of the memory latency. Use
+    /// [`BitRank::rank_many`] for sorted indices instead.
+    pub fn rank_many_unordered(&self, indices: &[usize]) -> Vec<usize> {
+        // The number of queries to look ahead. Enough to cover the memory latency, but small
+        // enough not to evict prefetched lines before they are used.
+        const PREFETCH_DISTANCE: usize = 8;
+        for &idx in indices.iter().take(PREFETCH_DISTANCE) {
+            self.prefetch(idx);
+        }
+        indices
+            .iter()
+            .enumerate()
+            .map(|(i, &idx)| {
+                if let Some(&ahead) = indices.get(i + PREFETCH_DISTANCE) {
+                    self.prefetch(ahead);
+                }
+                self.rank(idx)
+            })
+            .collect()
+    }
+
+    /// Prefetches the memory needed for computing the rank at the specified index.
+    fn prefetch(&self, idx: usize) {
+        if let Some(block) = self.blocks.get(idx / Self::BITS_PER_BLOCK) {
+            block.prefetch(idx % Self::BITS_PER_BLOCK);
+        }
+    }
+
+    /// The zero rank at the specified index (exclusive).
+    ///
+    /// The zero rank is defined as: `rank0(i) = sum(1 - b[j] for j in 0..i)`
+    /// i.e. the number of integers less than `i` which are **not** in the set.
+    pub fn rank0(&self, idx: usize) -> usize {
+        idx - self.rank(idx)
+    }
+
+    /// Returns true if `idx` is an element of the set.
+    pub fn contains(&self, idx: usize) -> bool {
+        self.blocks
+            .get(idx / Self::BITS_PER_BLOCK)
+            .is_some_and(|b| b.get(idx % Self::BITS_PER_BLOCK))
+    }
+
+    /// Returns the smallest element of the set which is greater than or equal to `idx`.
+    ///
+    /// Elements within the same chunk are found by a single bit scan. Otherwise, the rank index is
+    /// used to jump directly to the next element without scanning empty chunks.
+    pub fn next_set_bit(&self, idx: usize) -> Option<usize> {
+        self.next_set_bit_in_chunk(idx)
+            .or_else(|| self.select(self.rank(idx)))
+    }
+
+    /// Returns the smallest element of the set which is greater than or equal to `idx` **if** it
+    /// occurs within the same chunk as `idx`.
+    fn next_set_bit_in_chunk(&self, idx: usize) -> Option<usize> {
+        let chunk_idx = idx / BITS_PER_SUB_BLOCK;
+        // Bits are stored from the most significant bit, so this keeps the bits `idx..`.
+        let bits = self.chunk(chunk_idx) & (!0 >> (idx % BITS_PER_SUB_BLOCK));
+        (bits != 0).then(|| chunk_idx * BITS_PER_SUB_BLOCK + bits.leading_zeros() as usize)
+    }
+
+    /// Returns the largest element of the set which is smaller than `idx`.
+    ///
+    /// This is the select component of [`BitRank::rank_and_prev_set_bit`].
+    pub fn prev_set_bit(&self, idx: usize) -> Option<usize> {
+        self.rank_and_prev_set_bit(idx).prev
+    }
+
+    /// Returns the elements enclosing the gap around `idx`, i.e. the same as
+    /// `(prev_set_bit(idx), next_set_bit(idx))`.
+    ///
+    /// This locates the block of `idx` only once, which makes it cheaper than the two individual
+    /// queries.
+    pub fn enclosing_set_bits(&self, idx: usize) -> (Option<usize>, Option<usize>) {
+        let rank_select = self.rank_and_prev(idx);
+        let prev = rank_select.prev_or_lookup(|rank| self.select(rank));
+        let next = self
+            .next_set_bit_in_chunk(idx)
+            .or_else(|| self.select(rank_select.rank));
+        (prev, next)
     }
 
     /// Returns the number of elements in the set.
@@ -192,28 +988,308 @@ impl BitRank {
             .unwrap_or_default() // fall back to 0 when the bitrank data structure is empty.
     }
 
-    /// The rank at the specified index(exclusive) and the index of the one bit that
-    /// establishes that rank (aka "select") **if** it occurs within that same chunk,
-    /// otherwise ['None'].  The assumption is that if you would have to look back
-    /// through previous chunks it would actually be cheaper to do a lookup in the original
-    /// data structure that the bit vector was created from.
-    pub fn rank_select(&self, idx: usize) -> (usize, Option<usize>) {
-        let block_num = idx / BITS_PER_BLOCK;
+    /// Returns the number of elements in the set, the same as [`BitRank::max_rank`].
+    pub fn len(&self) -> usize {
+        self.max_rank()
+    }
+
+    /// Returns the number of bytes allocated on the heap by the blocks and the select samples,
+    /// including unused capacity.
+    pub fn heap_size_bytes(&self) -> usize {
+        self.blocks.capacity() * core::mem::size_of::<Block<SUB_BLOCKS_PER_BLOCK>>()
+            + self
+                .select_samples
+                .as_ref()
+                .map_or(0, SelectSamples::heap_size_bytes)
+    }
+
+    /// Returns the number of blocks which are stored.
+    pub fn num_blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Returns the number of blocks which memory is allocated for. This is at least
+    /// [`BitRank::num_blocks`].
+    pub fn block_capacity(&self) -> usize {
+        self.blocks.capacity()
+    }
+
+    /// Releases the memory which is allocated for unused blocks, so that
+    /// [`BitRank::block_capacity`] equals [`BitRank::num_blocks`].
+    pub fn shrink_to_fit(&mut self) {
+        self.blocks.shrink_to_fit();
+    }
+
+    /// Returns the smallest element of the set, or `None` if the set is empty.
+    ///
+    /// Takes O(log n) time to find the first non-empty block, plus the scan of its chunks.
+    pub fn min_position(&self) -> Option<usize> {
+        // All blocks in front of the first element have rank 0, the ones after it don't.
+        let block_num = self
+            .blocks
+            .partition_point(|b| b.rank == 0)
+            .checked_sub(1)?;
+        let block = &self.blocks[block_num];
+        let (sub_block, bits) = block.bits.iter().enumerate().find(|(_, &b)| b != 0)?;
+        Some(
+            block_num * Self::BITS_PER_BLOCK
+                + sub_block * BITS_PER_SUB_BLOCK
+                + bits.leading_zeros() as usize,
+        )
+    }
+
+    /// Returns the largest element of the set, or `None` if the set is empty.
+    ///
+    /// Trailing empty blocks are dropped, so the element is found by scanning the chunks of the
+    /// last block.
+    pub fn max_position(&self) -> Option<usize> {
+        let block_num = self.blocks.len().checked_sub(1)?;
+        let block = &self.blocks[block_num];
+        let (sub_block, bits) = block.bits.iter().enumerate().rfind(|(_, &b)| b != 0)?;
+        Some(
+            block_num * Self::BITS_PER_BLOCK
+                + sub_block * BITS_PER_SUB_BLOCK
+                + (BITS_PER_SUB_BLOCK - 1 - bits.trailing_zeros() as usize),
+        )
+    }
+
+    /// Returns true if the set has no elements.
+    pub fn is_empty(&self) -> bool {
+        // Trailing blocks without any bits set are dropped, so only an empty set has no blocks.
+        self.blocks.is_empty()
+    }
+
+    /// One past the largest position covered by the blocks, i.e. the number of bits which are
+    /// stored. It is a multiple of the block size and at least one larger than the largest
+    /// element.
+    pub fn universe_size(&self) -> usize {
+        self.blocks.len() * Self::BITS_PER_BLOCK
+    }
+
+    /// The position of the set bit with the given (zero-based) rank, i.e. the inverse of
+    /// [`BitRank::rank`]: `rank(select(k)) == k`.
+    ///
+    /// Returns `None` if `rank` is greater than or equal to the number of elements in the set.
+    /// Runs in O(log n) time by binary searching the block and sub-block ranks. With
+    /// [`BitRank::with_select_samples`], only the blocks between two samples are searched.
+    pub fn select(&self, rank: usize) -> Option<usize> {
+        if rank >= self.max_rank() {
+            return None;
+        }
+        let candidates = match &self.select_samples {
+            Some(samples) => samples.block_range(rank),
+            None => 0..self.blocks.len(),
+        };
+        let start = candidates.start;
+        let candidates = &self.blocks[start..candidates.end.min(self.blocks.len())];
+        // The first candidate contains a rank `<= rank`, so the partition point is at least 1.
+        let block_num = start + candidates.partition_point(|b| b.rank as usize <= rank) - 1;
+        let block = &self.blocks[block_num];
+        Some(block_num * Self::BITS_PER_BLOCK + block.select(rank - block.rank as usize))
+    }
+
+    /// The positions of the elements with the ranks in `ranks`, i.e. the same as
+    /// `ranks.map(|r| self.select(r).unwrap()).collect()`. Ranks beyond the number of elements are
+    /// ignored.
+    ///
+    /// Only the first element is located with a binary search, the following ones are found by
+    /// scanning the bits from there.
+    pub fn select_many(&self, ranks: Range<usize>) -> Vec<usize> {
+        let end = ranks.end.min(self.max_rank());
+        let Some(first) = self.select(ranks.start).filter(|_| ranks.start < end) else {
+            return Vec::new();
+        };
+        self.iter_range(first..usize::MAX)
+            .take(end - ranks.start)
+            .collect()
+    }
+
+    /// The position of the unset bit with the given (zero-based) zero rank, i.e. the inverse of
+    /// [`BitRank::rank0`]: `rank0(select0(k)) == k`.
+    ///
+    /// All positions past the last element are unset, so this only returns `None` if the
+    /// resulting position would overflow `usize`.
+    pub fn select0(&self, rank0: usize) -> Option<usize> {
+        let block_rank0 = |i: usize| i * Self::BITS_PER_BLOCK - self.blocks[i].rank as usize;
+        let universe = self.universe_size();
+        let universe_rank0 = universe - self.max_rank();
+        if rank0 >= universe_rank0 {
+            return universe.checked_add(rank0 - universe_rank0);
+        }
+        // The first block starts with zero unset bits, so the partition point is at least 1.
+        let block_num = partition_point(self.blocks.len(), |i| block_rank0(i) <= rank0) - 1;
+        let block = &self.blocks[block_num];
+        Some(block_num * Self::BITS_PER_BLOCK + block.select0(rank0 - block_rank0(block_num)))
+    }
+
+    /// Returns a cursor which answers queries close to the previous one faster than the
+    /// individual queries on the set.
+    pub fn cursor(&self) -> BitRankCursor<'_, SUB_BLOCKS_PER_BLOCK> {
+        BitRankCursor::new(self)
+    }
+
+    /// Returns an iterator over the elements of the set in increasing order.
+    pub fn iter(&self) -> Iter<'_, SUB_BLOCKS_PER_BLOCK> {
+        Iter::new(self, 0..usize::MAX)
+    }
+
+    /// Returns the elements of the set in increasing order.
+    pub fn to_sorted_vec(&self) -> Vec<usize> {
+        let mut positions = Vec::with_capacity(self.max_rank());
+        positions.extend(self.iter());
+        positions
+    }
+
+    /// Returns an iterator over the elements of the set within the half-open `range` in
+    /// increasing order.
+    ///
+    /// The iteration starts directly at the block containing `range.start`, so the cost does not
+    /// depend on the number of elements before the range.
+    pub fn iter_range(&self, range: Range<usize>) -> Iter<'_, SUB_BLOCKS_PER_BLOCK> {
+        Iter::new(self, range)
+    }
+
+    /// Returns an iterator over every `step`-th element of the set, i.e. the elements with the
+    /// ranks `0, step, 2 * step, ...`, e.g. to build a sampling index.
+    ///
+    /// Every element is located with [`BitRank::select`], so the cost depends on the number of
+    /// returned elements rather than on all elements of the set.
+    ///
+    /// This panics if `step` is zero.
+    pub fn iter_step(&self, step: usize) -> impl DoubleEndedIterator<Item = usize> + '_ {
+        assert!(step > 0, "step must be positive");
+        (0..self.max_rank())
+            .step_by(step)
+            .map(|rank| self.select(rank).expect("rank is smaller than max_rank"))
+    }
+
+    /// The bits of the chunk with the given index (counted over all blocks), or zero if the
+    /// chunk lies past the last block.
+    fn chunk(&self, chunk_idx: usize) -> SubblockBits {
+        self.blocks
+            .get(chunk_idx / SUB_BLOCKS_PER_BLOCK)
+            .map_or(0, |b| b.bits[chunk_idx % SUB_BLOCKS_PER_BLOCK])
+    }
+
+    /// The rank at the specified index (exclusive) and the largest element smaller than `idx` if
+    /// it falls into the same 128-bit chunk, see [`RankSelect`].
+    ///
+    /// Finding an element in a previous chunk means looking back through the chunks, which is
+    /// often more expensive than a lookup in the data the set was created from, see
+    /// [`RankSelect::prev_or_lookup`]. [`BitRank::rank_and_prev_set_bit`] finds the element in
+    /// any case.
+    pub fn rank_and_prev(&self, idx: usize) -> RankSelect {
+        let block_num = idx / Self::BITS_PER_BLOCK;
         // assert!(block_num < self.blocks.len(), "index out of bounds");
         if block_num >= self.blocks.len() {
-            (
-                self.max_rank(), // fall back to 0 when the bitrank data structure is empty.
-                None,
-            )
+            RankSelect {
+                rank: self.max_rank(), // fall back to 0 when the bitrank data structure is empty.
+                prev_set_in_chunk: None,
+            }
         } else {
-            let (rank, b_idx) = self.blocks[block_num].rank_select(idx % BITS_PER_BLOCK);
-            (rank, b_idx.map(|i| (block_num * BITS_PER_BLOCK) + i))
+            let local = self.blocks[block_num].rank_and_prev(idx % Self::BITS_PER_BLOCK);
+            RankSelect {
+                rank: local.rank,
+                prev_set_in_chunk: local
+                    .prev_set_in_chunk
+                    .map(|i| (block_num * Self::BITS_PER_BLOCK) + i),
+            }
+        }
+    }
+
+    /// The same as [`BitRank::rank_and_prev`] as a tuple.
+    #[deprecated(note = "use `rank_and_prev`, which returns a named `RankSelect`")]
+    pub fn rank_select(&self, idx: usize) -> (usize, Option<usize>) {
+        self.rank_and_prev(idx).into()
+    }
+
+    /// The rank at the specified index (exclusive) and the position of the one bit that
+    /// establishes that rank, i.e. the same as `rank(idx)` and `prev_set_bit(idx)`. Unlike
+    /// [`BitRank::rank_and_prev`], the position is only `None` if the rank is zero.
+    ///
+    /// If the establishing bit is not in the chunk of `idx`, it is looked up with the sub-block
+    /// rank table of its block. Only if that block has no bits set in front of `idx`, the previous
+    /// blocks are binary searched.
+    pub fn rank_and_prev_set_bit(&self, idx: usize) -> RankPrev {
+        let RankSelect {
+            rank,
+            prev_set_in_chunk,
+        } = self.rank_and_prev(idx);
+        if prev_set_in_chunk.is_some() || rank == 0 {
+            return RankPrev {
+                rank,
+                prev: prev_set_in_chunk,
+            };
+        }
+        let prev_rank = rank - 1;
+        // The rank is non-zero, so there is at least one block.
+        let last = (idx / Self::BITS_PER_BLOCK).min(self.blocks.len() - 1);
+        let block_num = if self.blocks[last].rank as usize <= prev_rank {
+            last
+        } else {
+            // The first block has rank 0, so the partition point is at least 1.
+            self.blocks[..last].partition_point(|b| b.rank as usize <= prev_rank) - 1
+        };
+        let block = &self.blocks[block_num];
+        let prev = block_num * Self::BITS_PER_BLOCK + block.select(prev_rank - block.rank as usize);
+        RankPrev {
+            rank,
+            prev: Some(prev),
+        }
+    }
+}
+
+/// Collects strictly increasing positions into a `BitRank`.
+///
+/// Like [`BitRankBuilder::push`], this panics if the positions are not strictly increasing.
+impl<const SUB_BLOCKS_PER_BLOCK: usize> FromIterator<usize> for BitRank<SUB_BLOCKS_PER_BLOCK> {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut builder = BitRankBuilder::default();
+        builder.push_many(iter);
+        builder.finish()
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> BitRank<SUB_BLOCKS_PER_BLOCK> {
+    /// Creates a `BitRank` from positions in any order, which may contain duplicates.
+    ///
+    /// See [`BitRankBuilder::push_unsorted`].
+    pub fn from_unsorted(positions: Vec<usize>) -> Self {
+        let mut builder = BitRankBuilder::default();
+        builder.push_unsorted(positions);
+        builder.finish()
+    }
+}
+
+/// Two sets are equal if they contain the same elements. The comparison ignores the capacity and
+/// the select samples.
+impl<const SUB_BLOCKS_PER_BLOCK: usize> PartialEq for BitRank<SUB_BLOCKS_PER_BLOCK> {
+    fn eq(&self, other: &Self) -> bool {
+        // Trailing empty blocks are dropped, so equal sets have the same blocks. The rank index
+        // follows from the bits.
+        self.blocks.len() == other.blocks.len()
+            && self
+                .blocks
+                .iter()
+                .zip(&other.blocks)
+                .all(|(a, b)| a.bits == b.bits)
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> Eq for BitRank<SUB_BLOCKS_PER_BLOCK> {}
+
+/// Hashes the bits of the set, consistent with its [`PartialEq`] implementation.
+impl<const SUB_BLOCKS_PER_BLOCK: usize> Hash for BitRank<SUB_BLOCKS_PER_BLOCK> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.blocks.len().hash(state);
+        for block in &self.blocks {
+            block.bits.hash(state);
         }
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use rand::distr::Uniform;
     use rand::prelude::*;
@@ -230,6 +1306,13 @@ mod tests {
         builder.finish()
     }
 
+    /// Like [`bitrank`], but adds the integers with [`BitRankBuilder::push_many`].
+    pub fn bitrank_push_many<I: IntoIterator<Item = usize>>(iter: I) -> BitRank {
+        let mut builder = BitRankBuilder::new();
+        builder.push_many(iter);
+        builder.finish()
+    }
+
     #[test]
     fn test_rank_zero() {
         let br = bitrank([0]);
@@ -282,23 +1365,42 @@ mod tests {
         let mut positions: Vec<usize> = (0..132).collect();
         positions.append(&mut vec![138usize, 140, 146]);
         let br = bitrank(positions);
-        assert_eq!(br.rank_select(135), (132, Some(131)));
+        let rank_select = |rank, prev_set_in_chunk| RankSelect {
+            rank,
+            prev_set_in_chunk,
+        };
+        assert_eq!(br.rank_and_prev(135), rank_select(132, Some(131)));
 
         let bits2: Vec<usize> = (0..BITS_PER_BLOCK - 5).collect();
         let br2 = bitrank(bits2);
-        assert_eq!(br2.rank_select(169), (169, Some(168)));
+        assert_eq!(br2.rank_and_prev(169), rank_select(169, Some(168)));
 
         let bits3: Vec<usize> = (0..BITS_PER_BLOCK + 5).collect();
         let br3 = bitrank(bits3);
-        assert_eq!(br3.rank_select(BITS_PER_BLOCK), (BITS_PER_BLOCK, None));
+        assert_eq!(
+            br3.rank_and_prev(BITS_PER_BLOCK),
+            rank_select(BITS_PER_BLOCK, None)
+        );
 
         let bits4: Vec<usize> = vec![1, 1000, 9999, BITS_PER_BLOCK + 1];
         let br4 = bitrank(bits4);
-        assert_eq!(br4.rank_select(10000), (3, Some(9999)));
+        assert_eq!(br4.rank_and_prev(10000), rank_select(3, Some(9999)));
+        assert_eq!(
+            br4.rank_and_prev(10000).prev_or_lookup(|_| None),
+            Some(9999)
+        );
 
         let bits5: Vec<usize> = vec![1, 1000, 9999, BITS_PER_BLOCK + 1];
         let br5 = bitrank(bits5);
-        assert_eq!(br5.rank_select(BITS_PER_BLOCK), (3, None));
+        assert_eq!(br5.rank_and_prev(BITS_PER_BLOCK), rank_select(3, None));
+        assert_eq!(
+            br5.rank_and_prev(BITS_PER_BLOCK)
+                .prev_or_lookup(|r| br5.select(r)),
+            Some(9999)
+        );
+        #[allow(deprecated)]
+        let tuple = br5.rank_select(BITS_PER_BLOCK);
+        assert_eq!(tuple, (3, None));
     }
 
     #[test]
@@ -320,7 +1422,13 @@ mod tests {
             if i % BITS_PER_SUB_BLOCK == 0 {
                 select = None;
             }
-            assert_eq!(br.rank_select(i), (rank, select));
+            assert_eq!(
+                br.rank_and_prev(i),
+                RankSelect {
+                    rank,
+                    prev_set_in_chunk: select
+                }
+            );
             if i == random_bits[rank] {
                 rank += 1;
                 select = Some(i);
@@ -353,6 +1461,330 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_select() {
+        let br = bitrank([]);
+        assert_eq!(br.select(0), None);
+
+        let positions = [
+            0,
+            1,
+            127,
+            128,
+            1000,
+            BITS_PER_BLOCK - 1,
+            BITS_PER_BLOCK * 5 + 3,
+        ];
+        let br = bitrank(positions);
+        for (k, &pos) in positions.iter().enumerate() {
+            assert_eq!(br.select(k), Some(pos));
+        }
+        assert_eq!(br.select(positions.len()), None);
+    }
+
+    #[test]
+    fn test_select_large_random() {
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let uniform = Uniform::new(0, 1_000_000).unwrap();
+        let mut random_bits: Vec<usize> = (0..100_000).map(|_| uniform.sample(&mut rng)).collect();
+        random_bits.sort_unstable();
+        random_bits.dedup();
+        let br = bitrank(random_bits.iter().copied());
+        for (k, &pos) in random_bits.iter().enumerate() {
+            assert_eq!(br.select(k), Some(pos));
+            assert_eq!(br.rank(pos), k);
+        }
+        assert_eq!(br.select(random_bits.len()), None);
+    }
+
+    #[test]
+    fn test_rank_many() {
+        let mut rng = ChaCha8Rng::seed_from_u64(21);
+        let positions: Vec<usize> = (0..5 * BITS_PER_BLOCK)
+            .filter(|_| rng.random_bool(0.1))
+            .collect();
+        let br = bitrank(positions);
+        let mut indices: Vec<usize> = (0..1000)
+            .map(|_| rng.random_range(0..6 * BITS_PER_BLOCK))
+            .collect();
+        indices.sort_unstable();
+        let expected: Vec<usize> = indices.iter().map(|&i| br.rank(i)).collect();
+        assert_eq!(br.rank_many(&indices), expected);
+
+        // Nearly sorted indices.
+        indices.swap(100, 400);
+        indices.swap(500, 501);
+        let expected: Vec<usize> = indices.iter().map(|&i| br.rank(i)).collect();
+        assert_eq!(br.rank_many(&indices), expected);
+
+        assert!(br.rank_many(&[]).is_empty());
+        assert_eq!(bitrank([]).rank_many(&[0, 5]), vec![0, 0]);
+    }
+
+    #[test]
+    fn test_rank_many_unordered() {
+        let mut rng = ChaCha8Rng::seed_from_u64(22);
+        let positions: Vec<usize> = (0..5 * BITS_PER_BLOCK)
+            .filter(|_| rng.random_bool(0.3))
+            .collect();
+        let br = bitrank(positions);
+        for len in [0, 3, 1000] {
+            let indices: Vec<usize> = (0..len)
+                .map(|_| rng.random_range(0..6 * BITS_PER_BLOCK))
+                .collect();
+            let expected: Vec<usize> = indices.iter().map(|&i| br.rank(i)).collect();
+            assert_eq!(br.rank_many_unordered(&indices), expected);
+        }
+    }
+
+    #[test]
+    fn test_rank0_select0() {
+        let br = bitrank([]);
+        assert_eq!(br.rank0(10), 10);
+        assert_eq!(br.select0(10), Some(10));
+
+        let br = bitrank((0..132).chain([138, 140, BITS_PER_BLOCK * 2 + 1]));
+        assert_eq!(br.rank0(132), 0);
+        assert_eq!(br.rank0(139), 6);
+        assert_eq!(br.select0(0), Some(132));
+        assert_eq!(br.select0(6), Some(139));
+        assert_eq!(br.select0(7), Some(141));
+        let universe_rank0 = BITS_PER_BLOCK * 3 - br.max_rank();
+        assert_eq!(br.select0(universe_rank0 - 1), Some(BITS_PER_BLOCK * 3 - 1));
+        assert_eq!(br.select0(universe_rank0), Some(BITS_PER_BLOCK * 3));
+        assert_eq!(br.select0(usize::MAX), None);
+    }
+
+    #[test]
+    fn test_select0_large_random() {
+        let mut rng = ChaCha8Rng::seed_from_u64(4);
+        let uniform = Uniform::new(0, 100_000).unwrap();
+        let mut random_bits: Vec<usize> = (0..50_000).map(|_| uniform.sample(&mut rng)).collect();
+        random_bits.sort_unstable();
+        random_bits.dedup();
+        let br = bitrank(random_bits.iter().copied());
+        let mut rank0 = 0;
+        for i in 0..BITS_PER_BLOCK * br.blocks.len() + 10 {
+            assert_eq!(br.rank0(i), rank0);
+            if random_bits.binary_search(&i).is_err() {
+                assert_eq!(br.select0(rank0), Some(i));
+                rank0 += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn test_rank_range() {
+        let br = bitrank([]);
+        assert_eq!(br.rank_range(0..10), 0);
+
+        let br = bitrank((0..132).chain([138, 140, BITS_PER_BLOCK * 2 + 1]));
+        assert_eq!(br.rank_range(0..0), 0);
+        assert_eq!(br.rank_range(0..132), 132);
+        assert_eq!(br.rank_range(100..139), 33);
+        assert_eq!(br.rank_range(131..BITS_PER_BLOCK * 2 + 1), 3);
+        assert_eq!(br.rank_range(131..BITS_PER_BLOCK * 2 + 2), 4);
+        assert_eq!(br.rank_range(139..usize::MAX), 2);
+        assert_eq!(br.rank_range(usize::MAX - 1..usize::MAX), 0);
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = 139..100;
+        assert_eq!(br.rank_range(reversed), 0);
+    }
+
+    #[test]
+    fn test_contains() {
+        let positions = [
+            0,
+            1,
+            127,
+            128,
+            1000,
+            BITS_PER_BLOCK - 1,
+            BITS_PER_BLOCK * 5 + 3,
+        ];
+        let br = bitrank(positions);
+        for i in 0..BITS_PER_BLOCK * 6 + 10 {
+            assert_eq!(br.contains(i), positions.contains(&i), "position {i}");
+        }
+        assert!(!br.contains(usize::MAX));
+        assert!(!bitrank([]).contains(0));
+    }
+
+    #[test]
+    fn test_next_set_bit() {
+        let positions = [
+            0,
+            1,
+            127,
+            128,
+            1000,
+            BITS_PER_BLOCK - 1,
+            BITS_PER_BLOCK * 5 + 3,
+        ];
+        let br = bitrank(positions);
+        for i in 0..BITS_PER_BLOCK * 6 + 10 {
+            let expected = positions.iter().copied().find(|&p| p >= i);
+            assert_eq!(br.next_set_bit(i), expected, "position {i}");
+        }
+        assert_eq!(br.next_set_bit(usize::MAX), None);
+        assert_eq!(bitrank([]).next_set_bit(0), None);
+    }
+
+    #[test]
+    fn test_prev_set_bit() {
+        let positions = [
+            0,
+            1,
+            127,
+            128,
+            1000,
+            BITS_PER_BLOCK - 1,
+            BITS_PER_BLOCK * 5 + 3,
+        ];
+        let br = bitrank(positions);
+        for i in 0..BITS_PER_BLOCK * 6 + 10 {
+            let expected = positions.iter().copied().rev().find(|&p| p < i);
+            assert_eq!(br.prev_set_bit(i), expected, "position {i}");
+        }
+        assert_eq!(br.prev_set_bit(usize::MAX), Some(BITS_PER_BLOCK * 5 + 3));
+        assert_eq!(bitrank([]).prev_set_bit(10), None);
+    }
+
+    #[test]
+    fn test_rank_and_prev_set_bit() {
+        let mut rng = ChaCha8Rng::seed_from_u64(34);
+        for density in [0.0001, 0.01, 0.5] {
+            let positions: Vec<usize> = (0..BITS_PER_BLOCK * 8)
+                .filter(|_| rng.random_bool(density))
+                .collect();
+            let br = bitrank(positions.iter().copied());
+            for _ in 0..2000 {
+                let i = rng.random_range(0..BITS_PER_BLOCK * 9);
+                let rank = positions.partition_point(|&p| p < i);
+                let prev = rank.checked_sub(1).map(|r| positions[r]);
+                assert_eq!(
+                    br.rank_and_prev_set_bit(i),
+                    RankPrev { rank, prev },
+                    "position {i}"
+                );
+            }
+        }
+        let rank_prev = |rank, prev| RankPrev { rank, prev };
+        let br = bitrank([3, BITS_PER_BLOCK * 4 + 1]);
+        assert_eq!(br.rank_and_prev_set_bit(3), rank_prev(0, None));
+        assert_eq!(br.rank_and_prev_set_bit(4), rank_prev(1, Some(3)));
+        assert_eq!(
+            br.rank_and_prev_set_bit(BITS_PER_BLOCK * 4),
+            rank_prev(1, Some(3))
+        );
+        assert_eq!(
+            br.rank_and_prev_set_bit(usize::MAX),
+            rank_prev(2, Some(BITS_PER_BLOCK * 4 + 1))
+        );
+        assert_eq!(bitrank([]).rank_and_prev_set_bit(10), rank_prev(0, None));
+    }
+
+    #[test]
+    fn test_enclosing_set_bits() {
+        let positions = [
+            0,
+            1,
+            127,
+            128,
+            1000,
+            BITS_PER_BLOCK - 1,
+            BITS_PER_BLOCK * 5 + 3,
+        ];
+        let br = bitrank(positions);
+        for i in 0..BITS_PER_BLOCK * 6 + 10 {
+            assert_eq!(
+                br.enclosing_set_bits(i),
+                (br.prev_set_bit(i), br.next_set_bit(i)),
+                "position {i}"
+            );
+        }
+        assert_eq!(br.enclosing_set_bits(500), (Some(128), Some(1000)));
+        assert_eq!(bitrank([]).enclosing_set_bits(10), (None, None));
+    }
+
+    #[test]
+    fn test_from_iter() {
+        let br: BitRank = [3, 17, BITS_PER_BLOCK + 1].into_iter().collect();
+        assert_eq!(br.rank(18), 2);
+        assert_eq!(br.select(2), Some(BITS_PER_BLOCK + 1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_from_iter_decreasing() {
+        let _: BitRank = [BITS_PER_BLOCK + 1, 3].into_iter().collect();
+    }
+
+    #[test]
+    fn test_push_many() {
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let uniform = Uniform::new(0, 100_000).unwrap();
+        let mut random_bits: Vec<usize> = (0..50_000).map(|_| uniform.sample(&mut rng)).collect();
+        random_bits.sort_unstable();
+        random_bits.dedup();
+        let (head, tail) = random_bits.split_at(20_000);
+        let mut builder = BitRankBuilder::new();
+        builder.push_many(head.iter().copied());
+        builder.push(tail[0]);
+        builder.push_many(tail[1..].iter().copied());
+        let br = builder.finish();
+        assert_eq!(br.iter().collect::<Vec<_>>(), random_bits);
+        let expected = bitrank(random_bits.iter().copied());
+        for i in 0..100_010 {
+            assert_eq!(br.rank(i), expected.rank(i));
+        }
+        assert!(bitrank_push_many(random_bits.iter().copied()) == expected);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_push_many_duplicate() {
+        let mut builder = BitRankBuilder::new();
+        builder.push(5);
+        builder.push_many([5, 6]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_push_many_decreasing() {
+        BitRankBuilder::new().push_many([5, 3]);
+    }
+
+    #[test]
+    fn test_push_run() {
+        let runs = [
+            0..132,
+            140..141,
+            200..200,
+            300..BITS_PER_BLOCK * 2 + 5,
+            BITS_PER_BLOCK * 3 - 1..BITS_PER_BLOCK * 3 + 128,
+        ];
+        let mut builder = BitRankBuilder::new();
+        for run in runs.clone() {
+            builder.push_run(run);
+        }
+        builder.push(BITS_PER_BLOCK * 4);
+        let br = builder.finish();
+        let expected = bitrank(runs.into_iter().flatten().chain([BITS_PER_BLOCK * 4]));
+        assert_eq!(br.max_rank(), expected.max_rank());
+        for i in 0..BITS_PER_BLOCK * 4 + 10 {
+            assert_eq!(br.rank(i), expected.rank(i), "position {i}");
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_push_run_overlapping() {
+        let mut builder = BitRankBuilder::new();
+        builder.push_run(0..10);
+        builder.push_run(9..20);
+    }
+
     #[test]
     fn test_with_capacity() {
         let mut b = BitRankBuilder::with_capacity(BITS_PER_BLOCK * 3 - 1);
@@ -367,4 +1799,330 @@ mod tests {
         b.push(BITS_PER_BLOCK * 3); // should not have to grow
         assert_eq!(b.blocks.capacity(), initial_capacity);
     }
+
+    #[test]
+    fn test_small_blocks() {
+        let mut rng = ChaCha8Rng::seed_from_u64(278);
+        let uniform = Uniform::new(0, 20_000).unwrap();
+        let mut positions: Vec<usize> = (0..2_000).map(|_| uniform.sample(&mut rng)).collect();
+        positions.extend(5_000..5_600);
+        positions.sort_unstable();
+        positions.dedup();
+        let br = bitrank(positions.iter().copied());
+        // Blocks of 256 bits.
+        let small: BitRank<2> = positions.iter().copied().collect();
+        assert_eq!(small.blocks.len(), positions.last().unwrap() / 256 + 1);
+        assert_eq!(small.max_rank(), br.max_rank());
+        for i in 0..21_000 {
+            assert_eq!(small.rank_and_prev(i), br.rank_and_prev(i), "position {i}");
+            assert_eq!(small.next_set_bit(i), br.next_set_bit(i), "position {i}");
+            assert_eq!(small.select0(i), br.select0(i), "rank {i}");
+        }
+        for rank in 0..=positions.len() {
+            assert_eq!(small.select(rank), br.select(rank), "rank {rank}");
+        }
+        assert_eq!(
+            small.iter().rev().collect::<Vec<_>>(),
+            br.iter().rev().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            small.iter_range(300..9_000).collect::<Vec<_>>(),
+            br.iter_range(300..9_000).collect::<Vec<_>>()
+        );
+
+        let mut builder = BitRankBuilder::<2>::default();
+        builder.push_run(100..1_000);
+        builder.push(1_000_000);
+        let runs = builder.finish();
+        assert_eq!(runs.rank(600), 500);
+        assert_eq!(runs.max_rank(), 901);
+        assert_eq!(runs.complement(2_000).rank(2_000), 1_100);
+        let (left, right) = runs.split_at(500);
+        assert_eq!((left.max_rank(), right.select(0)), (400, Some(0)));
+    }
+
+    #[test]
+    fn test_len() {
+        let empty = bitrank([]);
+        assert_eq!(empty.len(), 0);
+        assert!(empty.is_empty());
+        assert_eq!(empty.universe_size(), 0);
+
+        let br = bitrank([3, BITS_PER_BLOCK + 5]);
+        assert_eq!(br.len(), 2);
+        assert!(!br.is_empty());
+        assert_eq!(br.universe_size(), 2 * BITS_PER_BLOCK);
+
+        let small: BitRank<1> = [0, 127].into_iter().collect();
+        assert_eq!(small.universe_size(), 128);
+    }
+
+    #[test]
+    fn test_min_max_position() {
+        assert_eq!(bitrank([]).min_position(), None);
+        assert_eq!(bitrank([]).max_position(), None);
+        let br = bitrank([0]);
+        assert_eq!((br.min_position(), br.max_position()), (Some(0), Some(0)));
+        let positions = [
+            3 * BITS_PER_BLOCK + 200,
+            3 * BITS_PER_BLOCK + 201,
+            7 * BITS_PER_BLOCK - 1,
+        ];
+        let br = bitrank(positions);
+        assert_eq!(br.min_position(), Some(positions[0]));
+        assert_eq!(br.max_position(), Some(positions[2]));
+
+        let mut rng = ChaCha8Rng::seed_from_u64(30);
+        for _ in 0..20 {
+            let positions: Vec<usize> = (0..4 * BITS_PER_BLOCK)
+                .filter(|_| rng.random_bool(0.0005))
+                .collect();
+            let br = bitrank(positions.iter().copied());
+            assert_eq!(br.min_position(), positions.first().copied());
+            assert_eq!(br.max_position(), positions.last().copied());
+        }
+    }
+
+    #[test]
+    fn test_eq_hash() {
+        use std::collections::hash_map::DefaultHasher;
+
+        let hash = |br: &BitRank| {
+            let mut hasher = DefaultHasher::new();
+            br.hash(&mut hasher);
+            hasher.finish()
+        };
+        let a = bitrank([1, 5, BITS_PER_BLOCK + 3]);
+        // Unused capacity and select samples don't affect equality.
+        let mut builder = BitRankBuilder::with_capacity(4 * BITS_PER_BLOCK);
+        builder.push_many([1, 5, BITS_PER_BLOCK + 3]);
+        let b = builder.finish_with_select_samples(2);
+        assert!(a == b);
+        assert_eq!(hash(&a), hash(&b));
+        assert!(a != bitrank([1, 5, BITS_PER_BLOCK + 4]));
+        assert!(a != bitrank([1, 5]));
+        assert!(bitrank([]) == bitrank([]));
+    }
+
+    #[test]
+    fn test_rank_inclusive() {
+        let br = bitrank([0, 5, BITS_PER_BLOCK - 1, BITS_PER_BLOCK]);
+        for i in 0..2 * BITS_PER_BLOCK {
+            assert_eq!(br.rank_inclusive(i), br.rank(i + 1), "rank_inclusive({i})");
+        }
+        assert_eq!(br.rank_inclusive(BITS_PER_BLOCK - 1), 3);
+        assert_eq!(br.rank_inclusive(usize::MAX), 4);
+        assert_eq!(bitrank([]).rank_inclusive(usize::MAX), 0);
+    }
+
+    #[test]
+    fn test_try_rank() {
+        let br = bitrank([3, BITS_PER_BLOCK + 1]);
+        assert_eq!(br.try_rank(4), Some(1));
+        assert_eq!(br.try_rank(2 * BITS_PER_BLOCK), Some(2));
+        assert_eq!(br.try_rank(2 * BITS_PER_BLOCK + 1), None);
+        assert_eq!(br.try_rank(usize::MAX), None);
+        assert_eq!(bitrank([]).try_rank(0), Some(0));
+        assert_eq!(bitrank([]).try_rank(1), None);
+    }
+
+    #[test]
+    fn test_rank_unchecked() {
+        let br = bitrank([0, 5, 200, BITS_PER_BLOCK * 2 + 3, BITS_PER_BLOCK * 3 - 1]);
+        for i in 0..br.universe_size() {
+            // SAFETY: `i` is smaller than the universe size.
+            assert_eq!(unsafe { br.rank_unchecked(i) }, br.rank(i), "rank({i})");
+        }
+    }
+
+    #[test]
+    fn test_heap_size_bytes() {
+        assert_eq!(BitRank::<128>::from_blocks(Vec::new()).heap_size_bytes(), 0);
+        let block_size = core::mem::size_of::<Block>();
+        let br = bitrank([1, 2 * BITS_PER_BLOCK]);
+        assert_eq!(br.heap_size_bytes(), 3 * block_size);
+        let sampled = br.clone().with_select_samples(1);
+        assert_eq!(
+            sampled.heap_size_bytes(),
+            3 * block_size + 2 * core::mem::size_of::<usize>()
+        );
+    }
+
+    #[test]
+    fn test_block_alignment() {
+        let br = bitrank([1, 2 * BITS_PER_BLOCK]);
+        for block in &br.blocks {
+            assert_eq!(block as *const Block as usize % 64, 0);
+            assert_eq!(block.bits.as_ptr() as usize % 16, 0);
+        }
+        assert_eq!(core::mem::offset_of!(Block, rank), 0);
+    }
+
+    #[test]
+    fn test_block_capacity() {
+        let mut builder = BitRankBuilder::with_capacity(10 * BITS_PER_BLOCK);
+        builder.push(BITS_PER_BLOCK);
+        let mut br = builder.finish();
+        assert_eq!(br.num_blocks(), 2);
+        assert_eq!(br.block_capacity(), 2);
+
+        br.blocks.reserve(10);
+        assert!(br.block_capacity() > 2);
+        br.shrink_to_fit();
+        assert_eq!(br.block_capacity(), 2);
+    }
+
+    #[test]
+    fn test_finish_reuse() {
+        let mut builder = BitRankBuilder::with_capacity(4 * BITS_PER_BLOCK);
+        builder.push_many([1, 3 * BITS_PER_BLOCK]);
+        let first = builder.finish_reuse();
+        assert_eq!(first.iter().collect::<Vec<_>>(), [1, 3 * BITS_PER_BLOCK]);
+        assert_eq!(first.block_capacity(), 4);
+
+        // The builder starts over and may add smaller positions again.
+        let capacity = builder.blocks.capacity();
+        builder.push(5);
+        let second = builder.finish_reuse();
+        assert_eq!(second.iter().collect::<Vec<_>>(), [5]);
+        assert_eq!(builder.blocks.capacity(), capacity);
+
+        builder.push(7);
+        builder.clear();
+        assert!(builder.finish().is_empty());
+    }
+
+    #[test]
+    fn test_try_push() {
+        let mut builder = BitRankBuilder::new();
+        assert_eq!(builder.try_push(5), Ok(()));
+        assert_eq!(builder.try_push(BITS_PER_BLOCK + 3), Ok(()));
+        assert_eq!(
+            builder.try_push(BITS_PER_BLOCK + 3),
+            Err(PushError::Duplicate(BITS_PER_BLOCK + 3))
+        );
+        assert_eq!(builder.try_push(5), Err(PushError::Duplicate(5)));
+        assert_eq!(
+            builder.try_push(BITS_PER_BLOCK + 2),
+            Err(PushError::OutOfOrder {
+                position: BITS_PER_BLOCK + 2,
+                last: BITS_PER_BLOCK + 3
+            })
+        );
+        builder.push_many([BITS_PER_BLOCK + 200, BITS_PER_BLOCK + 300]);
+        assert!(matches!(
+            builder.try_push(BITS_PER_BLOCK + 250),
+            Err(PushError::OutOfOrder { last, .. }) if last == BITS_PER_BLOCK + 300
+        ));
+        assert_eq!(builder.try_push(BITS_PER_BLOCK + 301), Ok(()));
+        let br = builder.finish();
+        assert_eq!(
+            br.iter().collect::<Vec<_>>(),
+            [
+                5,
+                BITS_PER_BLOCK + 3,
+                BITS_PER_BLOCK + 200,
+                BITS_PER_BLOCK + 300,
+                BITS_PER_BLOCK + 301
+            ]
+        );
+    }
+
+    #[test]
+    fn test_allow_duplicates() {
+        let mut builder = BitRankBuilder::new().allow_duplicates(true);
+        builder.push(3);
+        builder.push(3);
+        builder.push_many([3, 5, 5, 200, BITS_PER_BLOCK, BITS_PER_BLOCK]);
+        builder.push_run(BITS_PER_BLOCK..BITS_PER_BLOCK + 300);
+        assert_eq!(builder.try_push(5), Ok(()));
+        assert_eq!(
+            builder.try_push(BITS_PER_BLOCK + 300),
+            Ok(()),
+            "new positions are still accepted"
+        );
+        let br = builder.finish();
+        let expected: Vec<usize> = [3, 5, 200]
+            .into_iter()
+            .chain(BITS_PER_BLOCK..BITS_PER_BLOCK + 301)
+            .collect();
+        assert_eq!(br.iter().collect::<Vec<_>>(), expected);
+        assert_eq!(br.max_rank(), expected.len());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_duplicates_panic_by_default() {
+        let mut builder = BitRankBuilder::new();
+        builder.push(3);
+        builder.push(3);
+    }
+
+    #[test]
+    fn test_from_unsorted() {
+        let mut rng = ChaCha8Rng::seed_from_u64(31);
+        let positions: Vec<usize> = (0..1000)
+            .map(|_| rng.random_range(0..3 * BITS_PER_BLOCK))
+            .collect();
+        let br: BitRank = BitRank::from_unsorted(positions.clone());
+        let mut expected = positions;
+        expected.sort_unstable();
+        expected.dedup();
+        assert_eq!(br.iter().collect::<Vec<_>>(), expected);
+
+        let mut builder = BitRankBuilder::new();
+        builder.push(1);
+        builder.push_unsorted(vec![9, 4, 9, 2]);
+        assert_eq!(builder.finish().iter().collect::<Vec<_>>(), [1, 2, 4, 9]);
+    }
+
+    #[test]
+    fn test_pop() {
+        let mut builder = BitRankBuilder::new();
+        assert_eq!(builder.pop(), None);
+        builder.push_many([1, 130, 3 * BITS_PER_BLOCK + 7]);
+        assert_eq!(builder.pop(), Some(3 * BITS_PER_BLOCK + 7));
+        // Positions after the new largest position can be added again.
+        builder.push(BITS_PER_BLOCK + 2);
+        assert_eq!(builder.pop(), Some(BITS_PER_BLOCK + 2));
+        assert_eq!(builder.pop(), Some(130));
+        builder.push(200);
+        let br = builder.finish();
+        assert_eq!(br.iter().collect::<Vec<_>>(), [1, 200]);
+        assert_eq!(br.num_blocks(), 1);
+
+        let mut builder = BitRankBuilder::new();
+        builder.push(5);
+        assert_eq!(builder.pop(), Some(5));
+        assert_eq!(builder.pop(), None);
+        assert!(builder.finish().is_empty());
+    }
+
+    #[test]
+    fn test_to_sorted_vec() {
+        assert_eq!(bitrank([]).to_sorted_vec(), Vec::<usize>::new());
+        let positions = vec![0, 17, 127, 128, BITS_PER_BLOCK, 5 * BITS_PER_BLOCK + 3];
+        let br = bitrank(positions.iter().copied());
+        assert_eq!(br.to_sorted_vec(), positions);
+    }
+
+    #[test]
+    fn test_select_many() {
+        let mut rng = ChaCha8Rng::seed_from_u64(33);
+        let positions: Vec<usize> = (0..4 * BITS_PER_BLOCK)
+            .filter(|_| rng.random_bool(0.01))
+            .collect();
+        let br = bitrank(positions.iter().copied());
+        for (start, end) in [(0, 0), (0, 10), (5, 6), (100, 400), (200, positions.len())] {
+            assert_eq!(br.select_many(start..end), positions[start..end]);
+        }
+        assert_eq!(
+            br.select_many(positions.len() - 2..positions.len() + 5),
+            positions[positions.len() - 2..]
+        );
+        assert!(br
+            .select_many(positions.len()..positions.len() + 5)
+            .is_empty());
+        assert!(bitrank([]).select_many(0..3).is_empty());
+    }
 }
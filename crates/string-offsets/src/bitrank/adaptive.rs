@@ -0,0 +1,345 @@
+//! A rank data structure which picks the most compact representation for every block.
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::packed::{low_mask, read_bits, write_bits};
+use super::{
+    select_in_chunk, BitRank, BitRankBuilder, Block, RankSelect, BITS_PER_BLOCK, BITS_PER_SUB_BLOCK,
+};
+
+/// The position of the `k`-th set bit in the bit-vector `words`, where bit `i` is bit `i % 64` of
+/// `words[i / 64]`. If `ones` is false, the unset bits are considered instead.
+pub(super) fn select_in_words(words: &[u64], mut k: usize, ones: bool) -> usize {
+    for (i, &word) in words.iter().enumerate() {
+        let word = if ones { word } else { !word };
+        let count = word.count_ones() as usize;
+        if k < count {
+            let mut word = word;
+            for _ in 0..k {
+                // Clear the least significant set bit.
+                word &= word - 1;
+            }
+            return i * 64 + word.trailing_zeros() as usize;
+        }
+        k -= count;
+    }
+    // All bits past the end are unset.
+    assert!(!ones, "not enough bits set");
+    words.len() * 64 + k
+}
+
+/// The Elias–Fano encoding of the positions within a single sparse block.
+///
+/// Unlike [`super::elias_fano::EliasFano`], the high bits are stored in a plain bit-vector which
+/// is scanned linearly, since it only consists of a few words.
+#[derive(Clone)]
+struct EliasFanoBlock {
+    len: usize,
+    low_len: u32,
+    low_bits: Vec<u64>,
+    high_bits: Vec<u64>,
+}
+
+impl EliasFanoBlock {
+    /// The low bit length used for `len` positions within a block.
+    fn low_len(len: usize) -> u32 {
+        BITS_PER_BLOCK.checked_div(len).map_or(0, usize::ilog2)
+    }
+
+    /// The number of bytes needed to encode `len` positions within a block.
+    fn estimated_size(len: usize) -> usize {
+        let low_len = Self::low_len(len) as usize;
+        let high_len = len + (BITS_PER_BLOCK >> low_len);
+        ((len * low_len).div_ceil(64) + high_len.div_ceil(64)) * 8
+    }
+
+    fn new(positions: &[usize]) -> Self {
+        let len = positions.len();
+        let low_len = Self::low_len(len);
+        let mut low_bits = vec![0; (len * low_len as usize).div_ceil(64)];
+        let mut high_bits = vec![0; (len + (BITS_PER_BLOCK >> low_len)).div_ceil(64)];
+        for (i, &position) in positions.iter().enumerate() {
+            write_bits(
+                &mut low_bits,
+                i * low_len as usize,
+                low_len,
+                position as u64,
+            );
+            let high_pos = (position >> low_len) + i;
+            high_bits[high_pos / 64] |= 1 << (high_pos % 64);
+        }
+        Self {
+            len,
+            low_len,
+            low_bits,
+            high_bits,
+        }
+    }
+
+    fn get(&self, idx: usize) -> usize {
+        let high = select_in_words(&self.high_bits, idx, true) - idx;
+        let low = read_bits(&self.low_bits, idx * self.low_len as usize, self.low_len);
+        (high << self.low_len) | low as usize
+    }
+
+    fn rank(&self, local_idx: usize) -> usize {
+        let high = local_idx >> self.low_len;
+        let low = local_idx as u64 & low_mask(self.low_len);
+        let (mut idx, mut high_pos) = if high == 0 {
+            (0, 0)
+        } else {
+            let pos = select_in_words(&self.high_bits, high - 1, false);
+            (pos - (high - 1), pos + 1)
+        };
+        while idx < self.len
+            && self.high_bits.get(high_pos / 64).unwrap_or(&0) & (1 << (high_pos % 64)) != 0
+            && read_bits(&self.low_bits, idx * self.low_len as usize, self.low_len) < low
+        {
+            idx += 1;
+            high_pos += 1;
+        }
+        idx
+    }
+}
+
+/// The runs of consecutive positions within a single block.
+#[derive(Clone)]
+struct RleBlock {
+    /// The start and (exclusive) end of every run.
+    runs: Vec<(u16, u16)>,
+}
+
+impl RleBlock {
+    fn estimated_size(runs: usize) -> usize {
+        runs * core::mem::size_of::<(u16, u16)>()
+    }
+
+    fn rank(&self, local_idx: usize) -> usize {
+        self.runs
+            .iter()
+            .take_while(|&&(start, _)| (start as usize) < local_idx)
+            .map(|&(start, end)| (end as usize).min(local_idx) - start as usize)
+            .sum()
+    }
+
+    fn select(&self, mut local_rank: usize) -> usize {
+        for &(start, end) in &self.runs {
+            let len = (end - start) as usize;
+            if local_rank < len {
+                return start as usize + local_rank;
+            }
+            local_rank -= len;
+        }
+        panic!("not enough bits set");
+    }
+}
+
+#[derive(Clone)]
+enum Repr {
+    Plain(Box<Block>),
+    Sparse(EliasFanoBlock),
+    Rle(RleBlock),
+}
+
+#[derive(Clone)]
+struct AdaptiveBlock {
+    /// Rank of the first bit in this block (that is, the number of bits set in previous blocks).
+    rank: u64,
+    /// The number of bits set in this block.
+    len: u16,
+    repr: Repr,
+}
+
+impl AdaptiveBlock {
+    /// Picks the smallest representation for the given block of a [`BitRank`].
+    fn new(block: &Block) -> Self {
+        let mut positions = Vec::new();
+        let mut runs: Vec<(u16, u16)> = Vec::new();
+        for (i, &chunk) in block.bits.iter().enumerate() {
+            let mut chunk = chunk;
+            while chunk != 0 {
+                let position = i * BITS_PER_SUB_BLOCK + select_in_chunk(chunk, 0);
+                chunk &= !(1 << (BITS_PER_SUB_BLOCK - 1 - position % BITS_PER_SUB_BLOCK));
+                positions.push(position);
+                match runs.last_mut() {
+                    Some((_, end)) if *end as usize == position => *end += 1,
+                    _ => runs.push((position as u16, position as u16 + 1)),
+                }
+            }
+        }
+        let plain_size = core::mem::size_of::<Block>();
+        let sparse_size = EliasFanoBlock::estimated_size(positions.len());
+        let rle_size = RleBlock::estimated_size(runs.len());
+        let repr = if rle_size <= sparse_size && rle_size < plain_size {
+            Repr::Rle(RleBlock { runs })
+        } else if sparse_size < plain_size {
+            Repr::Sparse(EliasFanoBlock::new(&positions))
+        } else {
+            let mut plain = Box::new(block.clone());
+            plain.rank = 0;
+            Repr::Plain(plain)
+        };
+        Self {
+            rank: block.rank,
+            len: positions.len() as u16,
+            repr,
+        }
+    }
+
+    fn rank(&self, local_idx: usize) -> usize {
+        match &self.repr {
+            Repr::Plain(block) => block.rank_and_prev(local_idx).rank,
+            Repr::Sparse(ef) => ef.rank(local_idx),
+            Repr::Rle(rle) => rle.rank(local_idx),
+        }
+    }
+
+    fn select(&self, local_rank: usize) -> usize {
+        match &self.repr {
+            Repr::Plain(block) => block.select(local_rank),
+            Repr::Sparse(ef) => ef.get(local_rank),
+            Repr::Rle(rle) => rle.select(local_rank),
+        }
+    }
+}
+
+/// An immutable set of unsigned integers with the same `rank` API as [`BitRank`], which stores
+/// every block in the most compact of three representations: a plain bit-vector, an Elias–Fano
+/// encoded list of positions (for sparse blocks), or a list of runs (for blocks with long runs of
+/// identical bits).
+///
+/// Created with [`BitRankBuilder::finish_adaptive`]. Queries dispatch to the representation of the
+/// block containing the queried position.
+#[derive(Clone)]
+pub struct AdaptiveBitRank {
+    blocks: Vec<AdaptiveBlock>,
+}
+
+impl AdaptiveBitRank {
+    /// The rank at the specified index (exclusive), i.e. the number of elements less than `idx`.
+    pub fn rank(&self, idx: usize) -> usize {
+        match self.blocks.get(idx / BITS_PER_BLOCK) {
+            Some(block) => block.rank as usize + block.rank(idx % BITS_PER_BLOCK),
+            None => self.max_rank(),
+        }
+    }
+
+    /// Returns the number of elements in the set.
+    pub fn max_rank(&self) -> usize {
+        self.blocks
+            .last()
+            .map_or(0, |b| b.rank as usize + b.len as usize)
+    }
+
+    /// The rank at the specified index (exclusive) and the largest element smaller than `idx`
+    /// **if** it falls into the same 128-bit chunk as `idx`, otherwise `None`.
+    ///
+    /// This returns exactly the same results as [`BitRank::rank_and_prev`].
+    pub fn rank_and_prev(&self, idx: usize) -> RankSelect {
+        let rank = self.rank(idx);
+        let chunk_start = idx - idx % BITS_PER_SUB_BLOCK;
+        let select = rank
+            .checked_sub(1)
+            .and_then(|r| self.select(r))
+            .filter(|&prev| prev >= chunk_start);
+        RankSelect {
+            rank,
+            prev_set_in_chunk: select,
+        }
+    }
+
+    /// The element with the given (zero-based) rank, or `None` if `rank` is greater than or equal
+    /// to the number of elements in the set.
+    pub fn select(&self, rank: usize) -> Option<usize> {
+        if rank >= self.max_rank() {
+            return None;
+        }
+        // The first block has rank 0, so the partition point is at least 1.
+        let block_num = self.blocks.partition_point(|b| b.rank as usize <= rank) - 1;
+        let block = &self.blocks[block_num];
+        Some(block_num * BITS_PER_BLOCK + block.select(rank - block.rank as usize))
+    }
+
+    /// Returns true if `idx` is an element of the set.
+    pub fn contains(&self, idx: usize) -> bool {
+        self.rank(idx.saturating_add(1)) > self.rank(idx)
+    }
+
+    /// Returns the number of blocks stored as plain bit-vectors, as Elias–Fano encoded positions,
+    /// and as runs, respectively.
+    pub fn representations(&self) -> (usize, usize, usize) {
+        let mut counts = (0, 0, 0);
+        for block in &self.blocks {
+            match block.repr {
+                Repr::Plain(_) => counts.0 += 1,
+                Repr::Sparse(_) => counts.1 += 1,
+                Repr::Rle(_) => counts.2 += 1,
+            }
+        }
+        counts
+    }
+}
+
+impl From<&BitRank> for AdaptiveBitRank {
+    fn from(bit_rank: &BitRank) -> Self {
+        Self {
+            blocks: bit_rank.blocks.iter().map(AdaptiveBlock::new).collect(),
+        }
+    }
+}
+
+impl BitRankBuilder {
+    /// Finishes the set like [`BitRankBuilder::finish`], but picks the most compact representation
+    /// for every block based on its density and number of runs.
+    pub fn finish_adaptive(self) -> AdaptiveBitRank {
+        AdaptiveBitRank::from(&self.finish())
+    }
+}
+
+// Positions within a block are stored as `u16`.
+const _: () = assert!(BITS_PER_BLOCK <= u16::MAX as usize);
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::bitrank::BITS_PER_BLOCK;
+    use crate::BitRankBuilder;
+
+    #[test]
+    fn test_adaptive_matches_bitrank() {
+        let mut rng = ChaCha8Rng::seed_from_u64(13);
+        let mut builder = BitRankBuilder::new();
+        let mut positions = Vec::new();
+        // A dense random block.
+        positions.extend((0..BITS_PER_BLOCK).filter(|_| rng.random_bool(0.5)));
+        // A sparse block.
+        positions.extend((BITS_PER_BLOCK..BITS_PER_BLOCK * 2).filter(|_| rng.random_bool(0.01)));
+        // An empty block followed by a block with a few long runs.
+        positions.extend(BITS_PER_BLOCK * 3 + 10..BITS_PER_BLOCK * 3 + 5000);
+        positions.extend(BITS_PER_BLOCK * 3 + 7000..BITS_PER_BLOCK * 4 + 10);
+        builder.push_many(positions.iter().copied());
+        let br = builder.finish();
+
+        let mut builder = BitRankBuilder::new();
+        builder.push_many(positions.iter().copied());
+        let adaptive = builder.finish_adaptive();
+        assert_eq!(adaptive.representations(), (1, 1, 3));
+        assert_eq!(adaptive.max_rank(), br.max_rank());
+        for i in 0..BITS_PER_BLOCK * 5 {
+            assert_eq!(
+                adaptive.rank_and_prev(i),
+                br.rank_and_prev(i),
+                "position {i}"
+            );
+            assert_eq!(adaptive.contains(i), br.contains(i), "position {i}");
+        }
+        for k in (0..=positions.len()).step_by(3) {
+            assert_eq!(adaptive.select(k), br.select(k));
+        }
+    }
+}
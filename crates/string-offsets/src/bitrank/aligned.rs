@@ -0,0 +1,126 @@
+//! A serialized [`BitRank`] in a single page-aligned allocation.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::serialize::{block_len, header};
+use super::{BitRank, BitRankRef, DEFAULT_SUB_BLOCKS_PER_BLOCK};
+
+/// The alignment of the buffer, which is the page size of most platforms.
+const PAGE_SIZE: usize = 4096;
+
+#[derive(Clone)]
+#[repr(C, align(4096))]
+struct Page([u8; PAGE_SIZE]);
+
+/// A [`BitRank`] in the serialized format of [`BitRank::to_bytes`], stored in one contiguous buffer
+/// which is aligned to 4096 bytes and created with a single allocation.
+///
+/// The block ranks, the sub-block ranks and the bits of all blocks live in the same buffer, so
+/// it can be written to a file as is with [`AlignedBitRank::as_bytes`] and queried through
+/// [`AlignedBitRank::view`] in the same way as a memory-mapped copy of that file.
+///
+/// # Examples
+///
+/// ```
+/// use string_offsets::bitrank::AlignedBitRank;
+/// use string_offsets::BitRank;
+///
+/// let set: BitRank = [17, 100_000].into_iter().collect();
+/// let aligned = AlignedBitRank::from(&set);
+/// assert_eq!(aligned.as_bytes().as_ptr() as usize % 4096, 0);
+/// assert_eq!(aligned.view().rank(1000), 1);
+/// assert_eq!(BitRank::from_bytes(aligned.as_bytes()), Ok(set));
+/// ```
+#[derive(Clone)]
+pub struct AlignedBitRank<const SUB_BLOCKS_PER_BLOCK: usize = DEFAULT_SUB_BLOCKS_PER_BLOCK> {
+    pages: Vec<Page>,
+    /// The number of bytes of the serialized set, the rest of the last page is zero.
+    len: usize,
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> AlignedBitRank<SUB_BLOCKS_PER_BLOCK> {
+    /// The serialized set, starting at a page boundary.
+    pub fn as_bytes(&self) -> &[u8] {
+        // SAFETY: `Page` consists of `PAGE_SIZE` bytes without padding, the pages are contiguous
+        // and `len` doesn't exceed the size of all pages.
+        unsafe { core::slice::from_raw_parts(self.pages.as_ptr().cast::<u8>(), self.len) }
+    }
+
+    /// Returns a view which answers queries directly on the buffer.
+    pub fn view(&self) -> BitRankRef<'_, SUB_BLOCKS_PER_BLOCK> {
+        BitRankRef::with_bytes(self.as_bytes()).expect("buffer is in the serialized format")
+    }
+
+    /// Copies `bytes` into the pages at the end of the serialized data.
+    fn extend_from_slice(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            let page = &mut self.pages[self.len / PAGE_SIZE].0;
+            let offset = self.len % PAGE_SIZE;
+            let n = bytes.len().min(PAGE_SIZE - offset);
+            page[offset..offset + n].copy_from_slice(&bytes[..n]);
+            bytes = &bytes[n..];
+            self.len += n;
+        }
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> From<&BitRank<SUB_BLOCKS_PER_BLOCK>>
+    for AlignedBitRank<SUB_BLOCKS_PER_BLOCK>
+{
+    fn from(bit_rank: &BitRank<SUB_BLOCKS_PER_BLOCK>) -> Self {
+        let header = header(SUB_BLOCKS_PER_BLOCK);
+        let block_len = block_len(SUB_BLOCKS_PER_BLOCK);
+        let len = header.len() + bit_rank.blocks.len() * block_len;
+        let mut aligned = Self {
+            pages: vec![Page([0; PAGE_SIZE]); len.div_ceil(PAGE_SIZE)],
+            len: 0,
+        };
+        aligned.extend_from_slice(&header);
+        let mut bytes = Vec::with_capacity(block_len);
+        for block in &bit_rank.blocks {
+            bytes.clear();
+            block.encode_into(&mut bytes);
+            aligned.extend_from_slice(&bytes);
+        }
+        aligned
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> AsRef<[u8]> for AlignedBitRank<SUB_BLOCKS_PER_BLOCK> {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AlignedBitRank, PAGE_SIZE};
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::BITS_PER_BLOCK;
+    use crate::BitRank;
+
+    #[test]
+    fn test_aligned_bit_rank() {
+        // Several blocks, so that the blocks cross page boundaries.
+        let positions = [3, 500, BITS_PER_BLOCK * 2 + 7, BITS_PER_BLOCK * 5 - 1];
+        let br = bitrank(positions);
+        let aligned = AlignedBitRank::from(&br);
+        assert_eq!(aligned.as_bytes().as_ptr() as usize % PAGE_SIZE, 0);
+        assert_eq!(aligned.as_bytes(), br.to_bytes());
+        assert_eq!(aligned.pages.len(), br.to_bytes().len().div_ceil(PAGE_SIZE));
+        let view = aligned.view();
+        for i in (0..BITS_PER_BLOCK * 6).step_by(97).chain(positions) {
+            assert_eq!(view.rank(i as u64), br.rank(i) as u64, "rank({i})");
+        }
+        assert_eq!(BitRank::from_bytes(aligned.as_ref()), Ok(br));
+    }
+
+    #[test]
+    fn test_aligned_bit_rank_empty() {
+        let br = bitrank([]);
+        let aligned = AlignedBitRank::from(&br);
+        assert_eq!(aligned.as_bytes(), br.to_bytes());
+        assert_eq!(aligned.view().rank(100), 0);
+    }
+}
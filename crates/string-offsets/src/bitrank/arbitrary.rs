@@ -0,0 +1,53 @@
+//! Generation of structured [`BitRank`] instances for fuzzing.
+
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+
+use super::{BitRank, BitRankBuilder};
+
+/// Generates the positions from arbitrary gaps, so that they are increasing by construction.
+/// The gaps are 16-bit, which is large enough to produce empty blocks in between elements but
+/// keeps the universe small enough to build the instance quickly.
+impl<'a, const SUB_BLOCKS_PER_BLOCK: usize> Arbitrary<'a> for BitRank<SUB_BLOCKS_PER_BLOCK> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut builder = BitRankBuilder::<SUB_BLOCKS_PER_BLOCK>::default();
+        let mut next = 0usize;
+        for gap in u.arbitrary_iter::<u16>()? {
+            let Some(position) = next.checked_add(gap? as usize) else {
+                break;
+            };
+            builder.push(position);
+            next = position + 1;
+        }
+        let bit_rank = builder.finish();
+        Ok(match u.int_in_range(0..=1024)? {
+            0 => bit_rank,
+            rate => bit_rank.with_select_samples(rate),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::arbitrary::{Arbitrary, Unstructured};
+    use rand::prelude::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::BitRank;
+
+    #[test]
+    fn test_arbitrary_is_consistent() {
+        let mut rng = ChaCha8Rng::seed_from_u64(35);
+        for len in [0, 1, 2, 3, 10, 100, 1000] {
+            let data: Vec<u8> = (0..len).map(|_| rng.random()).collect();
+            let br: BitRank = BitRank::arbitrary(&mut Unstructured::new(&data)).unwrap();
+            let positions: Vec<usize> = br.iter().collect();
+            assert!(positions.windows(2).all(|w| w[0] < w[1]));
+            assert_eq!(br.max_rank(), positions.len());
+            for (rank, &position) in positions.iter().enumerate() {
+                assert_eq!(br.rank(position), rank);
+                assert_eq!(br.select(rank), Some(position));
+            }
+        }
+    }
+}
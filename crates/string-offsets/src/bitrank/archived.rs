@@ -0,0 +1,250 @@
+//! Queries on [`BitRank`]s archived with [`rkyv`](::rkyv).
+//!
+//! With the `rkyv` feature, [`BitRank`] implements [`rkyv::Archive`](::rkyv::Archive). The
+//! archived set can be queried in place, without deserializing it. Validating it with
+//! [`rkyv::access`](::rkyv::access) also checks the rank index against the bits, like
+//! [`BitRank::from_bytes`] does, so that the queries on validated archives can't panic.
+//! Archives accessed without validation have to come from a trusted source.
+//!
+//! A [`GapBitRank`] is archived as the [`BitRank`] returned by [`GapBitRank::to_bit_rank`], so
+//! its archived form is an [`ArchivedBitRank`] as well.
+
+use rkyv::bytecheck::Verify;
+use rkyv::rancor::{Fallible, Source};
+use rkyv::{Archive, Deserialize, Place, Serialize};
+
+use super::serialize::FormatError;
+use super::{
+    partition_point, select_in_chunk, ArchivedBitRank, ArchivedBlock, BitRank, GapBitRank,
+    SubblockBits, BITS_PER_SUB_BLOCK,
+};
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> ArchivedBlock<SUB_BLOCKS_PER_BLOCK> {
+    /// The rank of the first bit of the block.
+    fn rank(&self) -> usize {
+        self.rank.to_native() as usize
+    }
+
+    /// The bits of the sub-block.
+    fn chunk(&self, sub_block: usize) -> SubblockBits {
+        self.bits[sub_block].to_native()
+    }
+
+    /// The number of bits set in the sub-blocks `0..sub_block`.
+    #[cfg(not(feature = "compact"))]
+    fn sub_block_rank(&self, sub_block: usize) -> usize {
+        self.sub_blocks[sub_block].to_native() as usize
+    }
+
+    /// The number of bits set in the sub-blocks `0..sub_block`.
+    #[cfg(feature = "compact")]
+    fn sub_block_rank(&self, sub_block: usize) -> usize {
+        (0..sub_block)
+            .map(|i| self.chunk(i).count_ones() as usize)
+            .sum()
+    }
+
+    /// Whether the stored sub-block ranks match the bits.
+    #[cfg(not(feature = "compact"))]
+    fn has_valid_sub_blocks(&self) -> bool {
+        let mut rank = 0;
+        (0..SUB_BLOCKS_PER_BLOCK).all(|i| {
+            let valid = self.sub_block_rank(i) == rank;
+            rank += self.chunk(i).count_ones() as usize;
+            valid
+        })
+    }
+
+    /// Without sub-block ranks, there is nothing to check.
+    #[cfg(feature = "compact")]
+    fn has_valid_sub_blocks(&self) -> bool {
+        true
+    }
+
+    /// The number of bits set in this and all previous blocks.
+    fn total_rank(&self) -> usize {
+        let last = SUB_BLOCKS_PER_BLOCK - 1;
+        self.rank() + self.sub_block_rank(last) + self.chunk(last).count_ones() as usize
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> ArchivedBitRank<SUB_BLOCKS_PER_BLOCK> {
+    /// The number of bits per block.
+    const BITS_PER_BLOCK: usize = SUB_BLOCKS_PER_BLOCK * BITS_PER_SUB_BLOCK;
+
+    /// The rank at the specified index (exclusive), see [`super::BitRank::rank`].
+    pub fn rank(&self, idx: usize) -> usize {
+        let Some(block) = self.blocks.get(idx / Self::BITS_PER_BLOCK) else {
+            return self.max_rank();
+        };
+        let local_idx = idx % Self::BITS_PER_BLOCK;
+        let sub_block = local_idx / BITS_PER_SUB_BLOCK;
+        let remainder = local_idx % BITS_PER_SUB_BLOCK;
+        let masked = if remainder == 0 {
+            0
+        } else {
+            block.chunk(sub_block) >> (BITS_PER_SUB_BLOCK - remainder)
+        };
+        block.rank() + block.sub_block_rank(sub_block) + masked.count_ones() as usize
+    }
+
+    /// Returns true if `idx` is an element of the set.
+    pub fn contains(&self, idx: usize) -> bool {
+        self.blocks
+            .get(idx / Self::BITS_PER_BLOCK)
+            .is_some_and(|block| {
+                let local_idx = idx % Self::BITS_PER_BLOCK;
+                let mask = 1 << (BITS_PER_SUB_BLOCK - 1 - local_idx % BITS_PER_SUB_BLOCK);
+                block.chunk(local_idx / BITS_PER_SUB_BLOCK) & mask != 0
+            })
+    }
+
+    /// Returns the number of elements in the set.
+    pub fn max_rank(&self) -> usize {
+        self.blocks.last().map_or(0, ArchivedBlock::total_rank)
+    }
+
+    /// The position of the set bit with the given (zero-based) rank, see
+    /// [`super::BitRank::select`].
+    pub fn select(&self, rank: usize) -> Option<usize> {
+        if rank >= self.max_rank() {
+            return None;
+        }
+        // The first block has rank 0, so the partition point is at least 1.
+        let block_num = self.blocks.partition_point(|b| b.rank() <= rank) - 1;
+        let block = &self.blocks[block_num];
+        let local_rank = rank - block.rank();
+        // The same holds for the first sub-block.
+        let sub_block = partition_point(SUB_BLOCKS_PER_BLOCK, |i| {
+            block.sub_block_rank(i) <= local_rank
+        }) - 1;
+        let remainder = local_rank - block.sub_block_rank(sub_block);
+        Some(
+            block_num * Self::BITS_PER_BLOCK
+                + sub_block * BITS_PER_SUB_BLOCK
+                + select_in_chunk(block.chunk(sub_block), remainder),
+        )
+    }
+}
+
+// SAFETY: `verify` only rejects archives, it doesn't rely on anything besides the checked bytes.
+unsafe impl<C: Fallible + ?Sized, const SUB_BLOCKS_PER_BLOCK: usize> Verify<C>
+    for ArchivedBitRank<SUB_BLOCKS_PER_BLOCK>
+where
+    C::Error: Source,
+{
+    fn verify(&self, _context: &mut C) -> Result<(), C::Error> {
+        let mut rank = 0;
+        for (i, block) in self.blocks.iter().enumerate() {
+            if block.rank() != rank || !block.has_valid_sub_blocks() {
+                return Err(C::Error::new(FormatError::InvalidIndex(i)));
+            }
+            rank = block.total_rank();
+        }
+        // Like `BitRank::from_blocks`, which trims them, the archive must not end with empty
+        // blocks, since equality and `is_empty` compare the blocks.
+        match self.blocks.last() {
+            Some(block) if block.total_rank() == block.rank() => Err(C::Error::new(
+                FormatError::EmptyLastBlock(self.blocks.len() - 1),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> Archive for GapBitRank<SUB_BLOCKS_PER_BLOCK> {
+    type Archived = ArchivedBitRank<SUB_BLOCKS_PER_BLOCK>;
+    type Resolver = <BitRank<SUB_BLOCKS_PER_BLOCK> as Archive>::Resolver;
+
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        // The set is converted a second time, since the resolver only refers to the serialized
+        // blocks.
+        self.to_bit_rank().resolve(resolver, out);
+    }
+}
+
+impl<S: Fallible + ?Sized, const SUB_BLOCKS_PER_BLOCK: usize> Serialize<S>
+    for GapBitRank<SUB_BLOCKS_PER_BLOCK>
+where
+    BitRank<SUB_BLOCKS_PER_BLOCK>: Serialize<S>,
+{
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        self.to_bit_rank().serialize(serializer)
+    }
+}
+
+impl<D: Fallible + ?Sized, const SUB_BLOCKS_PER_BLOCK: usize>
+    Deserialize<GapBitRank<SUB_BLOCKS_PER_BLOCK>, D> for ArchivedBitRank<SUB_BLOCKS_PER_BLOCK>
+where
+    ArchivedBitRank<SUB_BLOCKS_PER_BLOCK>: Deserialize<BitRank<SUB_BLOCKS_PER_BLOCK>, D>,
+{
+    fn deserialize(
+        &self,
+        deserializer: &mut D,
+    ) -> Result<GapBitRank<SUB_BLOCKS_PER_BLOCK>, D::Error> {
+        let bit_rank: BitRank<SUB_BLOCKS_PER_BLOCK> = Deserialize::deserialize(self, deserializer)?;
+        Ok(bit_rank.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::{ArchivedBitRank, BitRank, BITS_PER_BLOCK, DEFAULT_SUB_BLOCKS_PER_BLOCK};
+
+    #[test]
+    fn test_archived_matches_bitrank() {
+        let mut rng = ChaCha8Rng::seed_from_u64(29);
+        let positions: Vec<usize> = (0..4 * BITS_PER_BLOCK)
+            .filter(|_| rng.random_bool(0.02))
+            .collect();
+        let br = bitrank(positions.iter().copied());
+        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&br).unwrap();
+        let archived = rkyv::access::<ArchivedBitRank, rkyv::rancor::Error>(&bytes).unwrap();
+        assert_eq!(archived.max_rank(), br.max_rank());
+        for i in (0..5 * BITS_PER_BLOCK).step_by(11) {
+            assert_eq!(archived.rank(i), br.rank(i));
+            assert_eq!(archived.contains(i), br.contains(i));
+        }
+        for rank in 0..positions.len() + 1 {
+            assert_eq!(archived.select(rank), br.select(rank));
+        }
+
+        let decoded: BitRank = rkyv::deserialize::<_, rkyv::rancor::Error>(archived).unwrap();
+        assert_eq!(decoded.iter().collect::<Vec<_>>(), positions);
+    }
+
+    #[test]
+    fn test_archived_corrupted() {
+        let br = bitrank([1, 5, BITS_PER_BLOCK + 7]);
+        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&br).unwrap();
+        let archived = rkyv::access::<ArchivedBitRank, rkyv::rancor::Error>(&bytes).unwrap();
+        let offset = |field: *const u8| field as usize - bytes.as_ptr() as usize;
+        let rank = offset(core::ptr::from_ref(&archived.blocks[1].rank).cast());
+        let bits = offset(core::ptr::from_ref(&archived.blocks[0].bits[1]).cast());
+
+        // The rank of the second block doesn't match the bits of the first one.
+        let mut corrupted = bytes.clone();
+        corrupted[rank] += 1;
+        assert!(rkyv::access::<ArchivedBitRank, rkyv::rancor::Error>(&corrupted).is_err());
+        // Setting a bit in the second sub-block invalidates the ranks behind it.
+        let mut corrupted = bytes.clone();
+        corrupted[bits] |= 1;
+        assert!(rkyv::access::<ArchivedBitRank, rkyv::rancor::Error>(&corrupted).is_err());
+
+        // Clearing the only bit of the last block leaves an empty block at the end.
+        let last = DEFAULT_SUB_BLOCKS_PER_BLOCK - 1;
+        let br = bitrank([1, 2 * BITS_PER_BLOCK - 1]);
+        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&br).unwrap();
+        let archived = rkyv::access::<ArchivedBitRank, rkyv::rancor::Error>(&bytes).unwrap();
+        let bits =
+            core::ptr::from_ref(&archived.blocks[1].bits[last]) as usize - bytes.as_ptr() as usize;
+        let mut corrupted = bytes.clone();
+        corrupted[bits..bits + 16].fill(0);
+        assert!(rkyv::access::<ArchivedBitRank, rkyv::rancor::Error>(&corrupted).is_err());
+    }
+}
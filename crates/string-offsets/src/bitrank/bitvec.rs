@@ -0,0 +1,55 @@
+//! Interoperability with the [`bitvec`](::bitvec) crate.
+
+use ::bitvec::order::BitOrder;
+use ::bitvec::slice::BitSlice;
+use ::bitvec::store::BitStore;
+use ::bitvec::vec::BitVec;
+
+use super::{BitRank, BitRankBuilder};
+
+/// Creates a `BitRank` containing the indices of the bits set in the slice.
+impl<T: BitStore, O: BitOrder> From<&BitSlice<T, O>> for BitRank {
+    fn from(bits: &BitSlice<T, O>) -> Self {
+        let mut builder = BitRankBuilder::with_capacity(bits.len());
+        builder.push_many(bits.iter_ones());
+        builder.finish()
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> BitRank<SUB_BLOCKS_PER_BLOCK> {
+    /// Returns a bit-vector which has the bits at the elements of the set set.
+    ///
+    /// The length of the bit-vector is one past the largest element.
+    pub fn to_bitvec(&self) -> BitVec {
+        let len = self.iter().next_back().map_or(0, |max| max + 1);
+        let mut bits = BitVec::repeat(false, len);
+        for position in self.iter() {
+            bits.set(position, true);
+        }
+        bits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::bitvec::prelude::*;
+
+    use crate::BitRank;
+
+    #[test]
+    fn test_bitvec_round_trip() {
+        let bits = bitvec![0, 1, 1, 0, 0, 0, 1, 0, 0];
+        let br = BitRank::from(bits.as_bitslice());
+        assert_eq!(br.iter().collect::<Vec<_>>(), [1, 2, 6]);
+        assert_eq!(br.to_bitvec(), bits[..7]);
+
+        let bits = bitvec![u8, Msb0; 1; 20000];
+        let br = BitRank::from(&bits[3..]);
+        assert_eq!(br.max_rank(), 19997);
+        assert_eq!(br.to_bitvec(), bits[3..]);
+
+        assert!(BitRank::from(BitSlice::<usize>::empty())
+            .to_bitvec()
+            .is_empty());
+    }
+}
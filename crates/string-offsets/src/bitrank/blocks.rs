@@ -0,0 +1,162 @@
+//! Block-level access to a [`BitRank`] for custom storage formats.
+
+use alloc::vec::Vec;
+use core::iter::{Enumerate, FusedIterator};
+use core::slice;
+
+use super::{BitRank, Block, DEFAULT_SUB_BLOCKS_PER_BLOCK};
+
+/// A read-only view of a block of a [`BitRank`].
+///
+/// A block covers `128 * SUB_BLOCKS_PER_BLOCK` consecutive positions, which are stored in
+/// `SUB_BLOCKS_PER_BLOCK` words of 128 bits. Besides the bits, a block stores the number
+/// of elements in front of it and, within the block, in front of every word.
+#[derive(Clone, Copy)]
+pub struct BlockRef<'a, const SUB_BLOCKS_PER_BLOCK: usize = DEFAULT_SUB_BLOCKS_PER_BLOCK> {
+    index: usize,
+    block: &'a Block<SUB_BLOCKS_PER_BLOCK>,
+}
+
+impl<'a, const SUB_BLOCKS_PER_BLOCK: usize> BlockRef<'a, SUB_BLOCKS_PER_BLOCK> {
+    /// The index of the block within the set.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The first position covered by the block.
+    pub fn start(&self) -> usize {
+        self.index * Block::<SUB_BLOCKS_PER_BLOCK>::BITS
+    }
+
+    /// The number of elements in front of the block.
+    pub fn rank(&self) -> u64 {
+        self.block.rank
+    }
+
+    /// The number of elements in the block which are in front of the word with the given index.
+    ///
+    /// With the `compact` feature, the sub-block ranks aren't stored, so they are counted.
+    pub fn sub_block_rank(&self, sub_block: usize) -> usize {
+        self.block.sub_block_rank(sub_block)
+    }
+
+    /// The number of elements in the block.
+    pub fn count_ones(&self) -> usize {
+        self.block.total_rank() - self.block.rank as usize
+    }
+
+    /// Returns true if the block has no elements. Only blocks in front of other blocks can be
+    /// empty.
+    pub fn is_empty(&self) -> bool {
+        self.block.is_empty()
+    }
+
+    /// The bits of the block. Position `start() + i` is stored in `words()[i / 128]` as the bit
+    /// `127 - i % 128`, i.e. counting from the most significant bit.
+    pub fn words(&self) -> &'a [u128; SUB_BLOCKS_PER_BLOCK] {
+        &self.block.bits
+    }
+}
+
+/// An iterator over the blocks of a [`BitRank`], created by [`BitRank::blocks`].
+#[derive(Clone)]
+pub struct Blocks<'a, const SUB_BLOCKS_PER_BLOCK: usize = DEFAULT_SUB_BLOCKS_PER_BLOCK> {
+    blocks: Enumerate<slice::Iter<'a, Block<SUB_BLOCKS_PER_BLOCK>>>,
+}
+
+impl<'a, const SUB_BLOCKS_PER_BLOCK: usize> Iterator for Blocks<'a, SUB_BLOCKS_PER_BLOCK> {
+    type Item = BlockRef<'a, SUB_BLOCKS_PER_BLOCK>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.blocks
+            .next()
+            .map(|(index, block)| BlockRef { index, block })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.blocks.size_hint()
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> DoubleEndedIterator for Blocks<'_, SUB_BLOCKS_PER_BLOCK> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.blocks
+            .next_back()
+            .map(|(index, block)| BlockRef { index, block })
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> ExactSizeIterator for Blocks<'_, SUB_BLOCKS_PER_BLOCK> {}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> FusedIterator for Blocks<'_, SUB_BLOCKS_PER_BLOCK> {}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> BitRank<SUB_BLOCKS_PER_BLOCK> {
+    /// Returns an iterator over the blocks of the set, e.g. to store them in a custom format.
+    ///
+    /// The words of the blocks can be turned back into a set with [`BitRank::from_block_words`],
+    /// which recomputes the ranks.
+    pub fn blocks(&self) -> Blocks<'_, SUB_BLOCKS_PER_BLOCK> {
+        Blocks {
+            blocks: self.blocks.iter().enumerate(),
+        }
+    }
+
+    /// Creates a `BitRank` from the words of consecutive blocks as returned by
+    /// [`BlockRef::words`], starting at position 0.
+    pub fn from_block_words(words: impl IntoIterator<Item = [u128; SUB_BLOCKS_PER_BLOCK]>) -> Self {
+        let blocks = words
+            .into_iter()
+            .map(|bits| {
+                let mut block = Block::ZERO;
+                block.bits = bits;
+                block
+            })
+            .collect::<Vec<_>>();
+        Self::from_blocks(blocks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::BITS_PER_BLOCK;
+    use crate::BitRank;
+
+    #[test]
+    fn test_blocks() {
+        let positions = [0, 5, 200, BITS_PER_BLOCK * 2 + 3, BITS_PER_BLOCK * 2 + 130];
+        let br = bitrank(positions);
+        let blocks: Vec<_> = br.blocks().collect();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(
+            blocks.iter().map(|b| b.rank()).collect::<Vec<_>>(),
+            [0, 3, 3]
+        );
+        assert_eq!(
+            blocks.iter().map(|b| b.count_ones()).collect::<Vec<_>>(),
+            [3, 0, 2]
+        );
+        assert!(blocks[1].is_empty());
+        assert_eq!(blocks[2].start(), BITS_PER_BLOCK * 2);
+        assert_eq!(blocks[2].index(), 2);
+        assert_eq!(blocks[0].sub_block_rank(1), 2);
+        assert_eq!(blocks[0].sub_block_rank(2), 3);
+        assert_eq!(blocks[2].sub_block_rank(1), 1);
+        assert_eq!(blocks[0].words()[0], 1 << 127 | 1 << 122);
+        assert_eq!(blocks[0].words()[1], 1 << (127 - 72));
+        assert_eq!(br.blocks().next_back().map(|b| b.index()), Some(2));
+    }
+
+    #[test]
+    fn test_from_block_words() {
+        let br = bitrank([0, 5, 200, BITS_PER_BLOCK * 2 + 3, BITS_PER_BLOCK * 2 + 130]);
+        let copy = BitRank::from_block_words(br.blocks().map(|b| *b.words()));
+        assert_eq!(copy, br);
+        assert_eq!(copy.verify(), Ok(()));
+
+        // Trailing empty blocks are dropped.
+        let words = br.blocks().map(|b| *b.words()).chain([[0; 128]; 2]);
+        assert_eq!(BitRank::from_block_words(words), br);
+        assert!(BitRank::<128>::from_block_words([]).is_empty());
+    }
+}
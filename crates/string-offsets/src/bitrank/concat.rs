@@ -0,0 +1,369 @@
+//! Concatenation, splitting and splicing of [`BitRank`]s.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use super::ops::prefix_mask;
+use super::{BitRank, BitRankBuilder, Block, SubblockBits, BITS_PER_SUB_BLOCK};
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> BitRank<SUB_BLOCKS_PER_BLOCK> {
+    /// Appends the elements of `other`, shifted by `offset`, to this set.
+    ///
+    /// When `offset` is a multiple of the block size (16384 by default) and doesn't fall into the last block
+    /// of `self`, the blocks of `other` are copied as a whole and only their ranks are fixed up.
+    /// Otherwise, the shifted elements are added one by one.
+    ///
+    /// This panics if the shifted elements of `other` are not all greater than the elements of
+    /// `self`.
+    pub fn append(&mut self, other: &Self, offset: usize) {
+        let Some(other_min) = other.iter().next() else {
+            return;
+        };
+        if let Some(self_max) = self.iter().next_back() {
+            assert!(
+                other_min + offset > self_max,
+                "positions must be increasing!"
+            );
+        }
+        let first_block = offset / Self::BITS_PER_BLOCK;
+        if offset.is_multiple_of(Self::BITS_PER_BLOCK) && first_block >= self.blocks.len() {
+            let base_rank = self.max_rank() as u64;
+            self.select_samples = None;
+            while self.blocks.len() < first_block {
+                self.blocks.push(Block::ZERO);
+                self.blocks.last_mut().expect("just inserted").rank = base_rank;
+            }
+            self.blocks.extend(other.blocks.iter().map(|b| Block {
+                rank: b.rank + base_rank,
+                ..b.clone()
+            }));
+        } else {
+            // The builder recomputes the index of the last block, so it can continue from the
+            // blocks of a finished `BitRank`.
+            let mut builder = BitRankBuilder {
+                last_position: self.max_position(),
+                allow_duplicates: false,
+                expected_ones: 0,
+                blocks: core::mem::take(&mut self.blocks),
+            };
+            for position in other.iter() {
+                builder.push(position + offset);
+            }
+            *self = builder.finish();
+        }
+    }
+
+    /// Splits the set into the elements smaller than `position` and the elements greater than or
+    /// equal to `position`. The elements of the second set are shifted down by `position`, so that
+    /// `position` becomes 0.
+    ///
+    /// This is the inverse of [`BitRank::append`]: appending the second set to the first one at
+    /// offset `position` reproduces the original set.
+    pub fn split_at(&self, position: usize) -> (Self, Self) {
+        let universe = self.blocks.len() * Self::BITS_PER_BLOCK;
+        if position >= universe {
+            return (self.clone(), Self::from_blocks(vec![]));
+        }
+        let left = Self::from_chunks(position.div_ceil(Self::BITS_PER_BLOCK), |c| {
+            self.chunk(c) & prefix_mask(c, position)
+        });
+        let right = Self::from_chunks((universe - position).div_ceil(Self::BITS_PER_BLOCK), |c| {
+            self.bits_from(position + c * BITS_PER_SUB_BLOCK)
+        });
+        (left, right)
+    }
+
+    /// Replaces the positions in `range` with the first `len` positions of `replacement` and
+    /// shifts the elements behind the range by `len - range.len()`.
+    ///
+    /// Elements of `replacement` which are greater than or equal to `len` are ignored. The bits
+    /// are moved chunk by chunk, so the cost is proportional to the size of the bit-vector and
+    /// independent of the number of elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_offsets::BitRank;
+    ///
+    /// let set: BitRank = [1, 5, 9].into_iter().collect();
+    /// let replacement: BitRank = [0, 2].into_iter().collect();
+    /// let spliced = set.splice(4..6, &replacement, 3);
+    /// assert_eq!(spliced.iter().collect::<Vec<_>>(), [1, 4, 6, 10]);
+    /// ```
+    pub fn splice(&self, range: Range<usize>, replacement: &Self, len: usize) -> Self {
+        self.splice_many(&[(range, replacement, len)])
+    }
+
+    /// Applies several [`BitRank::splice`]s in one pass. Each edit consists of the range of
+    /// positions to replace, the replacement and the number of positions it covers.
+    ///
+    /// The ranges refer to the positions before any edit and have to be sorted and
+    /// non-overlapping, otherwise this panics.
+    pub fn splice_many(&self, edits: &[(Range<usize>, &Self, usize)]) -> Self {
+        // The new positions of the parts of the result, their source and the old position of
+        // their first element.
+        let mut parts = Vec::with_capacity(2 * edits.len() + 1);
+        let (mut old, mut new) = (0, 0);
+        for (range, replacement, len) in edits {
+            assert!(
+                old <= range.start && range.start <= range.end,
+                "invalid range {range:?}"
+            );
+            parts.push((new..new + range.start - old, self, old));
+            new += range.start - old;
+            parts.push((new..new + len, *replacement, 0));
+            new += len;
+            old = range.end;
+        }
+        parts.push((new..usize::MAX, self, old));
+        let universe = self.universe_size().max(old) - old + new;
+        let mut first_part = 0;
+        Self::from_chunks(universe.div_ceil(Self::BITS_PER_BLOCK), |c| {
+            let chunk_start = c * BITS_PER_SUB_BLOCK;
+            let chunk_end = chunk_start + BITS_PER_SUB_BLOCK;
+            // The chunks are created in increasing order, so earlier parts are never needed again.
+            while parts[first_part].0.end <= chunk_start {
+                first_part += 1;
+            }
+            parts[first_part..]
+                .iter()
+                .take_while(|(part, _, _)| part.start < chunk_end)
+                .map(|(part, source, source_start)| {
+                    let from = part.start.max(chunk_start);
+                    let to = part.end.min(chunk_end);
+                    if from >= to {
+                        return 0;
+                    }
+                    // Bits are stored from the most significant bit, so this moves the bits of the
+                    // part to the offset `from - chunk_start` and keeps the bits `from..to`.
+                    let mask = (SubblockBits::MAX >> (from - chunk_start))
+                        & !SubblockBits::MAX
+                            .checked_shr((to - chunk_start) as u32)
+                            .unwrap_or(0);
+                    (source.bits_from(from - part.start + source_start) >> (from - chunk_start))
+                        & mask
+                })
+                .fold(0, |a, b| a | b)
+        })
+    }
+
+    /// The bits of the positions `position..position + 128`.
+    pub(super) fn bits_from(&self, position: usize) -> SubblockBits {
+        let chunk_idx = position / BITS_PER_SUB_BLOCK;
+        let shift = position % BITS_PER_SUB_BLOCK;
+        let chunk = self.chunk(chunk_idx);
+        if shift == 0 {
+            chunk
+        } else {
+            // Bits are stored from the most significant bit, so shifting left moves them to
+            // smaller positions.
+            (chunk << shift) | (self.chunk(chunk_idx + 1) >> (BITS_PER_SUB_BLOCK - shift))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::BITS_PER_BLOCK;
+
+    /// Checks that appending `b` to `a` with every offset in `offsets` agrees with building the
+    /// concatenated set directly.
+    fn check_append(a: &[usize], b: &[usize], offsets: &[usize]) {
+        for &offset in offsets {
+            let mut br = bitrank(a.iter().copied());
+            br.append(&bitrank(b.iter().copied()), offset);
+            let expected: Vec<_> = a
+                .iter()
+                .copied()
+                .chain(b.iter().map(|p| p + offset))
+                .collect();
+            assert_eq!(br.iter().collect::<Vec<_>>(), expected, "offset {offset}");
+            let expected_br = bitrank(expected.iter().copied());
+            for i in (0..BITS_PER_BLOCK * 8).step_by(61) {
+                assert_eq!(
+                    br.rank(i),
+                    expected_br.rank(i),
+                    "offset {offset}, position {i}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_append() {
+        let a = [0, 5, 1000, BITS_PER_BLOCK + 7];
+        let b = [0, 3, BITS_PER_BLOCK - 1, BITS_PER_BLOCK * 2 + 100];
+        check_append(
+            &a,
+            &b,
+            &[
+                BITS_PER_BLOCK * 2,
+                BITS_PER_BLOCK * 4,
+                BITS_PER_BLOCK + 8,
+                BITS_PER_BLOCK * 2 + 1,
+            ],
+        );
+        check_append(&[], &b, &[0, 1, BITS_PER_BLOCK]);
+        check_append(&a, &[], &[0]);
+    }
+
+    #[test]
+    fn test_split_at() {
+        let positions = [
+            0,
+            5,
+            127,
+            128,
+            1000,
+            BITS_PER_BLOCK - 1,
+            BITS_PER_BLOCK * 3 + 7,
+        ];
+        let br = bitrank(positions);
+        for position in [
+            0,
+            1,
+            5,
+            6,
+            127,
+            128,
+            129,
+            BITS_PER_BLOCK - 1,
+            BITS_PER_BLOCK,
+            BITS_PER_BLOCK * 2 + 50,
+            BITS_PER_BLOCK * 3 + 7,
+            BITS_PER_BLOCK * 4,
+            usize::MAX,
+        ] {
+            let (left, right) = br.split_at(position);
+            let expected_left: Vec<_> = positions
+                .iter()
+                .copied()
+                .filter(|&p| p < position)
+                .collect();
+            let expected_right: Vec<_> = positions
+                .iter()
+                .filter(|&&p| p >= position)
+                .map(|p| p - position)
+                .collect();
+            assert_eq!(
+                left.iter().collect::<Vec<_>>(),
+                expected_left,
+                "position {position}"
+            );
+            assert_eq!(
+                right.iter().collect::<Vec<_>>(),
+                expected_right,
+                "position {position}"
+            );
+            assert_eq!(right.rank(usize::MAX), expected_right.len());
+
+            let mut joined = left;
+            joined.append(&right, position);
+            assert_eq!(joined.iter().collect::<Vec<_>>(), positions);
+        }
+    }
+
+    #[test]
+    fn test_splice() {
+        let positions = [
+            0,
+            5,
+            127,
+            128,
+            1000,
+            BITS_PER_BLOCK - 1,
+            BITS_PER_BLOCK * 3 + 7,
+        ];
+        let br = bitrank(positions);
+        let replacement = [1, 2, 130, BITS_PER_BLOCK + 3];
+        for range in [
+            0..0,
+            0..1,
+            4..6,
+            100..1001,
+            127..128,
+            BITS_PER_BLOCK - 1..BITS_PER_BLOCK * 2,
+            5..BITS_PER_BLOCK * 3 + 7,
+            BITS_PER_BLOCK * 3 + 8..BITS_PER_BLOCK * 5,
+        ] {
+            for len in [0, 2, 3, 129, 131, BITS_PER_BLOCK + 4] {
+                let spliced = br.splice(range.clone(), &bitrank(replacement), len);
+                let expected: Vec<_> = positions
+                    .iter()
+                    .copied()
+                    .filter(|&p| p < range.start)
+                    .chain(
+                        replacement
+                            .iter()
+                            .filter(|&&p| p < len)
+                            .map(|p| p + range.start),
+                    )
+                    .chain(
+                        positions
+                            .iter()
+                            .filter(|&&p| p >= range.end)
+                            .map(|p| p - range.end + range.start + len),
+                    )
+                    .collect();
+                assert_eq!(
+                    spliced.iter().collect::<Vec<_>>(),
+                    expected,
+                    "range {range:?}, len {len}"
+                );
+                assert_eq!(spliced, bitrank(expected.iter().copied()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_splice_many() {
+        let positions = [
+            0,
+            5,
+            127,
+            128,
+            1000,
+            BITS_PER_BLOCK - 1,
+            BITS_PER_BLOCK * 3 + 7,
+        ];
+        let br = bitrank(positions);
+        let a = bitrank([0, 3]);
+        let b = bitrank([1, 200]);
+        let spliced = br.splice_many(&[
+            (5..5, &a, 4),
+            (127..1000, &b, 201),
+            (BITS_PER_BLOCK * 2..BITS_PER_BLOCK * 2, &a, 1),
+        ]);
+        // The first edit shifts the following elements by 4, the second one by 201 - 873.
+        let expected = bitrank([
+            0,
+            5,
+            8,
+            9,
+            132,
+            331,
+            332,
+            BITS_PER_BLOCK - 669,
+            BITS_PER_BLOCK * 2 - 668,
+            BITS_PER_BLOCK * 3 + 7 - 667,
+        ]);
+        assert_eq!(spliced, expected);
+        assert_eq!(br.splice_many(&[]), br);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_splice_many_overlapping() {
+        let br = bitrank([0, 5, 1000]);
+        br.splice_many(&[(0..10, &br, 0), (5..6, &br, 0)]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_append_overlapping() {
+        let mut br = bitrank([0, 5, 1000]);
+        br.append(&bitrank([0]), 1000);
+    }
+}
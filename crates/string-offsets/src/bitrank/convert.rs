@@ -0,0 +1,97 @@
+//! Conversions between [`BitRank`] and other bit-vector representations.
+
+use super::ops::prefix_mask;
+use super::{BitRank, SubblockBits, BITS_PER_SUB_BLOCK};
+
+impl BitRank {
+    /// Creates a `BitRank` from the first `len` bits of a packed bitmap.
+    ///
+    /// Bit `i` of the bitmap is bit `i % 64` of `words[i / 64]`, counting from the least
+    /// significant bit. This is the layout used by most bitset crates. Bits at positions `len..`
+    /// are ignored.
+    ///
+    /// This panics if `words` contains fewer than `len` bits.
+    pub fn from_words(words: &[u64], len: usize) -> Self {
+        assert!(len <= words.len() * 64, "not enough words for {len} bits");
+        let word = |i: usize| words.get(i).copied().unwrap_or_default();
+        Self::from_chunks(len.div_ceil(Self::BITS_PER_BLOCK), |c| {
+            // Chunks store bits from the most significant bit, so the bit order of the words has
+            // to be reversed.
+            let chunk = ((word(2 * c).reverse_bits() as SubblockBits) << 64)
+                | word(2 * c + 1).reverse_bits() as SubblockBits;
+            chunk & prefix_mask(c, len)
+        })
+    }
+
+    /// Creates a `BitRank` containing the positions `i` in `0..len` for which `f(i)` returns true.
+    ///
+    /// `f` is called once for every position in increasing order. The results are collected into
+    /// 128-bit chunks, which are written in bulk, e.g. to build a mask of the ASCII bytes of a
+    /// text with `BitRank::from_fn(text.len(), |i| text[i].is_ascii())`.
+    pub fn from_fn(len: usize, mut f: impl FnMut(usize) -> bool) -> Self {
+        Self::from_chunks(len.div_ceil(Self::BITS_PER_BLOCK), |c| {
+            let start = c * BITS_PER_SUB_BLOCK;
+            let end = (start + BITS_PER_SUB_BLOCK).min(len);
+            // Chunks store bits from the most significant bit.
+            (start..end).fold(0, |chunk, i| {
+                chunk | (SubblockBits::from(f(i)) << (BITS_PER_SUB_BLOCK - 1 - (i - start)))
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bitrank::tests::bitrank;
+    use crate::BitRank;
+
+    #[test]
+    fn test_from_words() {
+        let words = [0b1011, 0, 1 << 63, u64::MAX];
+        let expected: Vec<_> = [0, 1, 3, 191].into_iter().chain(192..256).collect();
+        let br = BitRank::from_words(&words, 256);
+        assert_eq!(br.iter().collect::<Vec<_>>(), expected);
+        assert_eq!(br.rank(200), bitrank(expected.iter().copied()).rank(200));
+
+        let br = BitRank::from_words(&words, 195);
+        assert_eq!(br.iter().collect::<Vec<_>>(), [0, 1, 3, 191, 192, 193, 194]);
+        assert_eq!(BitRank::from_words(&[], 0).max_rank(), 0);
+    }
+
+    #[test]
+    fn test_from_words_large() {
+        let words: Vec<u64> = (0..1000u64)
+            .map(|i| i.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+            .collect();
+        let br = BitRank::from_words(&words, words.len() * 64);
+        let expected: Vec<_> = (0..words.len() * 64)
+            .filter(|i| words[i / 64] & (1 << (i % 64)) != 0)
+            .collect();
+        assert_eq!(br.iter().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_from_fn() {
+        let text = "añb€cd".repeat(5000);
+        let bytes = text.as_bytes();
+        let br = BitRank::from_fn(bytes.len(), |i| bytes[i].is_ascii());
+        let expected: Vec<_> = (0..bytes.len()).filter(|&i| bytes[i].is_ascii()).collect();
+        assert_eq!(br.iter().collect::<Vec<_>>(), expected);
+        assert_eq!(br.verify(), Ok(()));
+
+        let mut calls = Vec::new();
+        let br = BitRank::from_fn(3, |i| {
+            calls.push(i);
+            i != 1
+        });
+        assert_eq!(calls, [0, 1, 2]);
+        assert_eq!(br.iter().collect::<Vec<_>>(), [0, 2]);
+        assert!(BitRank::from_fn(1000, |_| false).is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_from_words_too_short() {
+        BitRank::from_words(&[0], 65);
+    }
+}
@@ -0,0 +1,155 @@
+//! A cursor for answering queries which are close to each other.
+
+use super::{select_in_chunk, BitRank, BITS_PER_SUB_BLOCK, DEFAULT_SUB_BLOCKS_PER_BLOCK};
+
+/// The maximum number of chunks a [`BitRankCursor`] walks over before jumping directly to the
+/// queried position with the rank index.
+const MAX_WALK: usize = 4;
+
+/// A cursor over a [`BitRank`] which remembers the chunk touched by the last query.
+///
+/// Created by [`BitRank::cursor`]. Queries close to the previous one are answered by counting the
+/// bits of the few chunks in between instead of locating the block and sub-block from scratch.
+/// Queries far away from the previous one fall back to the regular [`BitRank`] queries, so the
+/// cursor is never much slower than the underlying set.
+///
+/// # Examples
+///
+/// ```
+/// use string_offsets::BitRank;
+///
+/// let set: BitRank = [3, 17, 130, 1000].into_iter().collect();
+/// let mut cursor = set.cursor();
+/// assert_eq!(cursor.rank(20), 2);
+/// assert_eq!(cursor.rank(131), 3);
+/// assert_eq!(cursor.select(1), Some(17));
+/// assert_eq!(cursor.select(4), None);
+/// ```
+#[derive(Clone)]
+pub struct BitRankCursor<'a, const SUB_BLOCKS_PER_BLOCK: usize = DEFAULT_SUB_BLOCKS_PER_BLOCK> {
+    bit_rank: &'a BitRank<SUB_BLOCKS_PER_BLOCK>,
+    /// The number of elements in `bit_rank`.
+    max_rank: usize,
+    /// Index of the chunk (counted over all blocks) touched by the last query.
+    chunk: usize,
+    /// The rank of the first bit of `chunk`.
+    chunk_rank: usize,
+}
+
+impl<'a, const SUB_BLOCKS_PER_BLOCK: usize> BitRankCursor<'a, SUB_BLOCKS_PER_BLOCK> {
+    /// Returns a cursor positioned at the start of `bit_rank`.
+    pub(super) fn new(bit_rank: &'a BitRank<SUB_BLOCKS_PER_BLOCK>) -> Self {
+        Self {
+            bit_rank,
+            max_rank: bit_rank.max_rank(),
+            chunk: 0,
+            chunk_rank: 0,
+        }
+    }
+
+    /// The number of elements within the current chunk.
+    fn chunk_ones(&self) -> usize {
+        self.bit_rank.chunk(self.chunk).count_ones() as usize
+    }
+
+    /// Moves the cursor one chunk forward.
+    fn step_forward(&mut self) {
+        self.chunk_rank += self.chunk_ones();
+        self.chunk += 1;
+    }
+
+    /// Moves the cursor one chunk backward.
+    fn step_backward(&mut self) {
+        self.chunk -= 1;
+        self.chunk_rank -= self.chunk_ones();
+    }
+
+    /// The rank at the specified index (exclusive), see [`BitRank::rank`].
+    pub fn rank(&mut self, idx: usize) -> usize {
+        let target = idx / BITS_PER_SUB_BLOCK;
+        if target.abs_diff(self.chunk) <= MAX_WALK {
+            while self.chunk < target {
+                self.step_forward();
+            }
+            while self.chunk > target {
+                self.step_backward();
+            }
+        } else {
+            self.chunk = target;
+            self.chunk_rank = self.bit_rank.rank(target * BITS_PER_SUB_BLOCK);
+        }
+        let remainder = idx % BITS_PER_SUB_BLOCK;
+        let masked = if remainder == 0 {
+            0
+        } else {
+            self.bit_rank.chunk(target) >> (BITS_PER_SUB_BLOCK - remainder)
+        };
+        self.chunk_rank + masked.count_ones() as usize
+    }
+
+    /// The position of the set bit with the given (zero-based) rank, see [`BitRank::select`].
+    pub fn select(&mut self, rank: usize) -> Option<usize> {
+        if rank >= self.max_rank {
+            return None;
+        }
+        for _ in 0..=MAX_WALK {
+            if rank < self.chunk_rank {
+                self.step_backward();
+            } else if rank >= self.chunk_rank + self.chunk_ones() {
+                self.step_forward();
+            } else {
+                let bits = self.bit_rank.chunk(self.chunk);
+                return Some(
+                    self.chunk * BITS_PER_SUB_BLOCK + select_in_chunk(bits, rank - self.chunk_rank),
+                );
+            }
+        }
+        let position = self.bit_rank.select(rank)?;
+        self.chunk = position / BITS_PER_SUB_BLOCK;
+        // Bits are stored from the most significant bit, so this keeps the bits before `position`.
+        let before = self.bit_rank.chunk(self.chunk) & !(!0 >> (position % BITS_PER_SUB_BLOCK));
+        self.chunk_rank = rank - before.count_ones() as usize;
+        Some(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::BITS_PER_BLOCK;
+
+    #[test]
+    fn test_cursor_matches_bitrank() {
+        let mut rng = ChaCha8Rng::seed_from_u64(23);
+        let positions: Vec<usize> = (0..3 * BITS_PER_BLOCK)
+            .filter(|_| rng.random_bool(0.05))
+            .collect();
+        let br = bitrank(positions.iter().copied());
+        let mut cursor = br.cursor();
+        let mut idx = 0usize;
+        for _ in 0..10_000 {
+            // Mostly small moves around the previous query, with occasional jumps.
+            idx = if rng.random_bool(0.05) {
+                rng.random_range(0..4 * BITS_PER_BLOCK)
+            } else {
+                (idx + rng.random_range(0..600)).saturating_sub(300)
+            };
+            assert_eq!(cursor.rank(idx), br.rank(idx), "rank({idx})");
+            let rank = idx % (positions.len() + 10);
+            assert_eq!(cursor.select(rank), br.select(rank), "select({rank})");
+        }
+    }
+
+    #[test]
+    fn test_cursor_empty() {
+        let br = bitrank([]);
+        let mut cursor = br.cursor();
+        assert_eq!(cursor.rank(0), 0);
+        assert_eq!(cursor.rank(1000), 0);
+        assert_eq!(cursor.select(0), None);
+    }
+}
@@ -0,0 +1,87 @@
+//! Debug output of a [`BitRank`].
+
+use core::fmt;
+
+use super::{BitRank, BITS_PER_SUB_BLOCK};
+
+/// Prints a summary of the set instead of its blocks, which take kilobytes per block. Use
+/// [`BitRank::dump`] to print the blocks.
+impl<const SUB_BLOCKS_PER_BLOCK: usize> fmt::Debug for BitRank<SUB_BLOCKS_PER_BLOCK> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let density = match self.universe_size() {
+            0 => 0.0,
+            universe => self.len() as f64 / universe as f64,
+        };
+        f.debug_struct("BitRank")
+            .field("blocks", &self.blocks.len())
+            .field("len", &self.len())
+            .field("density", &density)
+            .field("min_position", &self.min_position())
+            .field("max_position", &self.max_position())
+            .finish()
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> BitRank<SUB_BLOCKS_PER_BLOCK> {
+    /// Writes every block with its rank followed by the bits of its non-empty sub-blocks in
+    /// hexadecimal, most significant bit (i.e. the smallest position) first.
+    ///
+    /// This is meant for debugging the rank index and prints one line per block and non-empty
+    /// sub-block.
+    pub fn dump(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        for (block_num, block) in self.blocks.iter().enumerate() {
+            writeln!(
+                out,
+                "block {block_num} at {}: rank {}",
+                block_num * Self::BITS_PER_BLOCK,
+                block.rank
+            )?;
+            for (sub_block, bits) in block.bits.iter().enumerate() {
+                if *bits != 0 {
+                    writeln!(
+                        out,
+                        "  {:>6}: {bits:032x} (rank +{})",
+                        block_num * Self::BITS_PER_BLOCK + sub_block * BITS_PER_SUB_BLOCK,
+                        block.sub_block_rank(sub_block)
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::BITS_PER_BLOCK;
+
+    #[test]
+    fn test_debug_summary() {
+        let br = bitrank([3, 2 * BITS_PER_BLOCK + 1]);
+        assert_eq!(
+            format!("{br:?}"),
+            "BitRank { blocks: 3, len: 2, density: 4.0690104166666664e-5, \
+             min_position: Some(3), max_position: Some(32769) }"
+        );
+        assert_eq!(
+            format!("{:?}", bitrank([])),
+            "BitRank { blocks: 0, len: 0, density: 0.0, min_position: None, max_position: None }"
+        );
+    }
+
+    #[test]
+    fn test_dump() {
+        let mut out = String::new();
+        bitrank([0, 3, BITS_PER_BLOCK + 130])
+            .dump(&mut out)
+            .unwrap();
+        assert_eq!(
+            out,
+            "block 0 at 0: rank 0\n\
+             \x20      0: 90000000000000000000000000000000 (rank +0)\n\
+             block 1 at 16384: rank 2\n\
+             \x20  16512: 20000000000000000000000000000000 (rank +0)\n"
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! A [`BitRank`] which supports insertions and removals.
+
+use alloc::vec::Vec;
+
+use super::{BitRank, Block, Iter, BITS_PER_SUB_BLOCK, DEFAULT_SUB_BLOCKS_PER_BLOCK};
+
+/// A set of unsigned integers with the rank and select queries of a [`BitRank`], which can be
+/// modified after it was built.
+///
+/// An insertion or removal updates the sub-block ranks of the affected block and shifts the ranks
+/// of all following blocks, so it takes O(`SUB_BLOCKS_PER_BLOCK` + number of blocks) time. Queries
+/// are answered by the underlying [`BitRank`] and are just as fast. This suits sets which are
+/// queried much more often than they change, e.g. markers in a document that is being edited.
+///
+/// # Examples
+///
+/// ```
+/// use string_offsets::bitrank::DynamicBitRank;
+///
+/// let mut set = DynamicBitRank::new();
+/// set.insert(17);
+/// set.insert(100_000);
+/// set.insert(23);
+/// assert_eq!(set.rank(50), 2);
+/// set.remove(17);
+/// assert_eq!(set.select(0), Some(23));
+/// ```
+#[derive(Clone)]
+pub struct DynamicBitRank<const SUB_BLOCKS_PER_BLOCK: usize = DEFAULT_SUB_BLOCKS_PER_BLOCK> {
+    bit_rank: BitRank<SUB_BLOCKS_PER_BLOCK>,
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> Default for DynamicBitRank<SUB_BLOCKS_PER_BLOCK> {
+    fn default() -> Self {
+        Self {
+            bit_rank: BitRank::from_blocks(Vec::new()),
+        }
+    }
+}
+
+impl DynamicBitRank {
+    /// Returns an empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> DynamicBitRank<SUB_BLOCKS_PER_BLOCK> {
+    /// Adds `position` to the set. Returns false if it was already an element.
+    pub fn insert(&mut self, position: usize) -> bool {
+        let block_num = position / Block::<SUB_BLOCKS_PER_BLOCK>::BITS;
+        let local_idx = position % Block::<SUB_BLOCKS_PER_BLOCK>::BITS;
+        if self
+            .bit_rank
+            .blocks
+            .get(block_num)
+            .is_some_and(|block| block.get(local_idx))
+        {
+            return false;
+        }
+        // The select samples would have to be shifted as well.
+        self.bit_rank.select_samples = None;
+        let blocks = &mut self.bit_rank.blocks;
+        if block_num >= blocks.len() {
+            let rank = blocks.last().map_or(0, Block::total_rank) as u64;
+            let mut empty = Block::ZERO;
+            empty.rank = rank;
+            blocks.resize(block_num + 1, empty);
+        }
+        let block = &mut blocks[block_num];
+        block.set(local_idx);
+        block.update_sub_blocks();
+        for block in &mut blocks[block_num + 1..] {
+            block.rank += 1;
+        }
+        true
+    }
+
+    /// Removes `position` from the set. Returns false if it wasn't an element.
+    pub fn remove(&mut self, position: usize) -> bool {
+        let block_num = position / Block::<SUB_BLOCKS_PER_BLOCK>::BITS;
+        let local_idx = position % Block::<SUB_BLOCKS_PER_BLOCK>::BITS;
+        let blocks = &mut self.bit_rank.blocks;
+        let Some(block) = blocks.get_mut(block_num).filter(|b| b.get(local_idx)) else {
+            return false;
+        };
+        self.bit_rank.select_samples = None;
+        block.bits[local_idx / BITS_PER_SUB_BLOCK] &=
+            !(1 << (BITS_PER_SUB_BLOCK - 1 - local_idx % BITS_PER_SUB_BLOCK));
+        block.update_sub_blocks();
+        for block in &mut blocks[block_num + 1..] {
+            block.rank -= 1;
+        }
+        // Like in a `BitRank`, there are no trailing empty blocks.
+        while blocks.last().is_some_and(Block::is_empty) {
+            blocks.pop();
+        }
+        true
+    }
+
+    /// The rank at the specified index (exclusive), see [`BitRank::rank`].
+    pub fn rank(&self, idx: usize) -> usize {
+        self.bit_rank.rank(idx)
+    }
+
+    /// The position of the element with the given (zero-based) rank, see [`BitRank::select`].
+    pub fn select(&self, rank: usize) -> Option<usize> {
+        self.bit_rank.select(rank)
+    }
+
+    /// Returns true if `idx` is an element of the set.
+    pub fn contains(&self, idx: usize) -> bool {
+        self.bit_rank.contains(idx)
+    }
+
+    /// Returns the number of elements in the set.
+    pub fn max_rank(&self) -> usize {
+        self.bit_rank.max_rank()
+    }
+
+    /// Returns an iterator over the elements of the set in increasing order.
+    pub fn iter(&self) -> Iter<'_, SUB_BLOCKS_PER_BLOCK> {
+        self.bit_rank.iter()
+    }
+
+    /// Returns the current set as a [`BitRank`], which supports all queries.
+    pub fn as_bit_rank(&self) -> &BitRank<SUB_BLOCKS_PER_BLOCK> {
+        &self.bit_rank
+    }
+
+    /// Returns the current set as a [`BitRank`].
+    pub fn into_bit_rank(self) -> BitRank<SUB_BLOCKS_PER_BLOCK> {
+        self.bit_rank
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> From<BitRank<SUB_BLOCKS_PER_BLOCK>>
+    for DynamicBitRank<SUB_BLOCKS_PER_BLOCK>
+{
+    fn from(bit_rank: BitRank<SUB_BLOCKS_PER_BLOCK>) -> Self {
+        Self { bit_rank }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use rand::prelude::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::DynamicBitRank;
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::BITS_PER_BLOCK;
+
+    #[test]
+    fn test_matches_btree_set() {
+        let mut rng = ChaCha8Rng::seed_from_u64(32);
+        let initial: BTreeSet<usize> = (0..200)
+            .map(|_| rng.random_range(0..3 * BITS_PER_BLOCK))
+            .collect();
+        let mut set = DynamicBitRank::from(bitrank(initial.iter().copied()));
+        let mut expected = initial;
+        for _ in 0..2000 {
+            let position = rng.random_range(0..5 * BITS_PER_BLOCK);
+            if rng.random_bool(0.5) {
+                assert_eq!(set.insert(position), expected.insert(position));
+            } else {
+                assert_eq!(set.remove(position), expected.remove(&position));
+            }
+        }
+        assert_eq!(set.max_rank(), expected.len());
+        assert!(set.iter().eq(expected.iter().copied()));
+        for (rank, &position) in expected.iter().enumerate() {
+            assert_eq!(set.select(rank), Some(position));
+            assert_eq!(set.rank(position), rank);
+            assert!(set.contains(position));
+        }
+        // The result is the same as building the set from scratch.
+        assert!(set.into_bit_rank() == bitrank(expected.iter().copied()));
+    }
+
+    #[test]
+    fn test_remove_all() {
+        let mut set = DynamicBitRank::new();
+        assert!(set.insert(3 * BITS_PER_BLOCK));
+        assert!(!set.insert(3 * BITS_PER_BLOCK));
+        assert!(set.insert(5));
+        assert_eq!(set.rank(3 * BITS_PER_BLOCK + 1), 2);
+        assert!(set.remove(3 * BITS_PER_BLOCK));
+        assert!(!set.remove(3 * BITS_PER_BLOCK));
+        assert!(set.remove(5));
+        assert_eq!(set.max_rank(), 0);
+        assert!(set.as_bit_rank().is_empty());
+    }
+
+    #[test]
+    fn test_select_samples_dropped() {
+        let sampled = bitrank([1, 2, BITS_PER_BLOCK]).with_select_samples(1);
+        let mut set = DynamicBitRank::from(sampled);
+        set.insert(0);
+        assert_eq!(set.select(3), Some(BITS_PER_BLOCK));
+    }
+}
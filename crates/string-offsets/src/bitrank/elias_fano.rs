@@ -0,0 +1,225 @@
+//! The [Elias–Fano encoding](https://www.antoniomallia.it/sorted-integers-compression-with-elias-fano-encoding.html)
+//! of a monotone sequence of integers.
+//!
+//! Every value is split into `low_len` low bits, which are stored verbatim, and the remaining high
+//! bits, which are stored in unary in a bit-vector: the `i`-th value sets the bit at position
+//! `(value >> low_len) + i`. Choosing `low_len = log2(universe / n)` results in a total size of
+//! about `n * (2 + log2(universe / n))` bits.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::packed::{low_mask, read_bits, write_bits};
+use super::{BitRank, BitRankBuilder};
+
+/// An immutable, strictly increasing sequence of `u64` values, e.g. the start offsets of the lines
+/// of a huge file, stored in about `2 + log2(universe / n)` bits per value instead of 64.
+///
+/// [`EliasFano::access`] takes O(1) time, whereas [`EliasFano::rank`] and
+/// [`EliasFano::predecessor`] take O(1) time plus a scan over the values which share their high
+/// bits, of which there are O(1) on average.
+///
+/// # Examples
+///
+/// ```
+/// use string_offsets::bitrank::EliasFano;
+///
+/// let line_starts: EliasFano = [0, 12, 13, 40].into_iter().collect();
+/// assert_eq!(line_starts.access(2), Some(13));
+/// // The line containing offset 20 is the one starting at the predecessor of 20.
+/// assert_eq!(line_starts.rank(21) - 1, 2);
+/// assert_eq!(line_starts.predecessor(20), Some(13));
+/// assert_eq!(line_starts.predecessor(40), Some(40));
+/// ```
+#[derive(Clone)]
+pub struct EliasFano {
+    /// The number of encoded values.
+    len: usize,
+    /// The number of low bits stored verbatim per value.
+    low_len: u32,
+    /// The low bits of all values, packed back to back.
+    low_bits: Vec<u64>,
+    /// The high bits of all values in unary encoding.
+    high_bits: BitRank,
+}
+
+impl EliasFano {
+    /// Encodes the strictly increasing `values`, which must all be smaller than `universe`.
+    ///
+    /// This panics if the values are not strictly increasing or not smaller than `universe`.
+    pub fn new(values: &[u64], universe: u64) -> Self {
+        let len = values.len();
+        let low_len = if len == 0 || universe <= len as u64 {
+            0
+        } else {
+            (universe / len as u64).ilog2()
+        };
+        let mut low_bits = vec![0; (len * low_len as usize).div_ceil(64)];
+        let mut high_bits = BitRankBuilder::new();
+        let mut prev = None;
+        for (i, &value) in values.iter().enumerate() {
+            assert!(prev < Some(value), "values must be increasing!");
+            assert!(
+                value < universe,
+                "values must be smaller than the universe!"
+            );
+            prev = Some(value);
+            write_bits(&mut low_bits, i * low_len as usize, low_len, value);
+            high_bits.push((value >> low_len) as usize + i);
+        }
+        Self {
+            len,
+            low_len,
+            low_bits,
+            high_bits: high_bits.finish(),
+        }
+    }
+
+    /// The number of encoded values.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if there are no values.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The value with the given index, or `None` if `idx` is out of bounds.
+    pub fn access(&self, idx: usize) -> Option<u64> {
+        let high_pos = self.high_bits.select(idx)?;
+        Some((((high_pos - idx) as u64) << self.low_len) | self.low(idx))
+    }
+
+    /// The number of values smaller than `value`.
+    pub fn rank(&self, value: u64) -> usize {
+        let high = value >> self.low_len;
+        let low = value & low_mask(self.low_len);
+        // The values with a smaller high part are the set bits before the `high`-th unset bit.
+        let (mut idx, mut high_pos) = match usize::try_from(high) {
+            Ok(0) => (0, 0),
+            Ok(high) => match self.high_bits.select0(high - 1) {
+                Some(pos) => (pos - (high - 1), pos + 1),
+                None => return self.len,
+            },
+            Err(_) => return self.len,
+        };
+        // Then scan the values with the same high part.
+        while idx < self.len && self.high_bits.contains(high_pos) && self.low(idx) < low {
+            idx += 1;
+            high_pos += 1;
+        }
+        idx
+    }
+
+    /// The largest value which is smaller than or equal to `value`, or `None` if all values are
+    /// larger.
+    pub fn predecessor(&self, value: u64) -> Option<u64> {
+        let rank = value
+            .checked_add(1)
+            .map_or(self.len, |next| self.rank(next));
+        self.access(rank.checked_sub(1)?)
+    }
+
+    /// Returns an iterator over the values in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        (0..self.len).map(|idx| self.access(idx).expect("index is in bounds"))
+    }
+
+    /// The low bits of the value with the given index.
+    fn low(&self, idx: usize) -> u64 {
+        read_bits(&self.low_bits, idx * self.low_len as usize, self.low_len)
+    }
+}
+
+impl fmt::Debug for EliasFano {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl PartialEq for EliasFano {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for EliasFano {}
+
+/// Collects strictly increasing values into an `EliasFano` sequence whose universe ends after the
+/// largest value.
+///
+/// This panics if the values are not strictly increasing or contain `u64::MAX`.
+impl FromIterator<u64> for EliasFano {
+    fn from_iter<I: IntoIterator<Item = u64>>(iter: I) -> Self {
+        let values: Vec<u64> = iter.into_iter().collect();
+        let universe = values.last().map_or(0, |&max| max.saturating_add(1));
+        Self::new(&values, universe)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::distr::Uniform;
+    use rand::prelude::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::EliasFano;
+
+    #[test]
+    fn test_elias_fano() {
+        let ef = EliasFano::new(&[], 0);
+        assert_eq!(ef.len(), 0);
+        assert_eq!(ef.rank(100), 0);
+        assert_eq!(ef.access(0), None);
+
+        let values = [0, 3, 4, 17, 1000, 1001, 70_000];
+        let ef = EliasFano::new(&values, 100_000);
+        for (i, &value) in values.iter().enumerate() {
+            assert_eq!(ef.access(i), Some(value));
+            assert_eq!(ef.rank(value), i);
+            assert_eq!(ef.rank(value + 1), i + 1);
+        }
+        assert_eq!(ef.rank(u64::MAX), values.len());
+    }
+
+    #[test]
+    fn test_elias_fano_random() {
+        let mut rng = ChaCha8Rng::seed_from_u64(8);
+        for (len, universe) in [(100, 1_000_000u64), (10_000, 20_000), (1000, u64::MAX)] {
+            let uniform = Uniform::new(0, universe).unwrap();
+            let mut values: Vec<u64> = (0..len).map(|_| uniform.sample(&mut rng)).collect();
+            values.sort_unstable();
+            values.dedup();
+            let ef = EliasFano::new(&values, universe);
+            for (i, &value) in values.iter().enumerate() {
+                assert_eq!(ef.access(i), Some(value));
+                assert_eq!(ef.rank(value), i);
+            }
+            for _ in 0..1000 {
+                let value = uniform.sample(&mut rng);
+                let rank = values.partition_point(|&v| v < value);
+                assert_eq!(ef.rank(value), rank);
+                let predecessor = values[..values.partition_point(|&v| v <= value)].last();
+                assert_eq!(ef.predecessor(value), predecessor.copied());
+            }
+        }
+    }
+
+    #[test]
+    fn test_elias_fano_predecessor() {
+        let ef: EliasFano = [5, 6, 100].into_iter().collect();
+        assert_eq!(ef.predecessor(4), None);
+        assert_eq!(ef.predecessor(5), Some(5));
+        assert_eq!(ef.predecessor(99), Some(6));
+        assert_eq!(ef.predecessor(u64::MAX), Some(100));
+        assert_eq!(ef.iter().collect::<Vec<_>>(), [5, 6, 100]);
+        assert_eq!(format!("{ef:?}"), "[5, 6, 100]");
+
+        let empty: EliasFano = std::iter::empty().collect();
+        assert!(empty.is_empty());
+        assert_eq!(empty.predecessor(u64::MAX), None);
+    }
+}
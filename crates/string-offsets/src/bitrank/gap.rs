@@ -0,0 +1,404 @@
+//! A [`BitRank`] with a gap of unused positions at the last edit, so that edits don't have to
+//! move the elements behind them.
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use super::{BitRank, Block, SubblockBits, BITS_PER_SUB_BLOCK, DEFAULT_SUB_BLOCKS_PER_BLOCK};
+
+/// A set of unsigned integers with the rank and select queries of a [`BitRank`], in which ranges
+/// of positions can be replaced like with [`BitRank::splice`], but without rebuilding the set.
+///
+/// The underlying [`BitRank`] keeps a gap of unused positions behind the last replacement. A
+/// splice moves the gap to the start of the replaced range, which moves the elements between the
+/// old and the new position of the gap, turns the replaced positions into gap and fills the
+/// replacement into it. The elements behind the gap stay where they are, so only the ranks of the
+/// following blocks are shifted. Splicing close to the previous splice therefore takes time
+/// proportional to the size of the replacement plus a tiny amount per block, independent of the
+/// number of elements. If the gap is too small, blocks are inserted behind it.
+///
+/// Queries translate positions behind the gap with one comparison and are otherwise just as fast
+/// as the ones of a [`BitRank`].
+///
+/// The set is serialized and archived as the [`BitRank`] returned by [`GapBitRank::to_bit_rank`],
+/// so that the serialized data doesn't depend on the position of the gap.
+///
+/// # Examples
+///
+/// ```
+/// use string_offsets::bitrank::GapBitRank;
+/// use string_offsets::BitRank;
+///
+/// let set: BitRank = [1, 5, 9].into_iter().collect();
+/// let mut set = GapBitRank::from(set);
+/// set.splice(4..6, [0, 2], 3);
+/// assert_eq!(set.iter_range(0..20).collect::<Vec<_>>(), [1, 4, 6, 10]);
+/// assert_eq!(set.rank(7), 3);
+/// ```
+#[derive(Clone)]
+pub struct GapBitRank<const SUB_BLOCKS_PER_BLOCK: usize = DEFAULT_SUB_BLOCKS_PER_BLOCK> {
+    bit_rank: BitRank<SUB_BLOCKS_PER_BLOCK>,
+    /// The first position of the gap, which is also the first position that is stored shifted.
+    gap_start: usize,
+    /// The stored position of `gap_start`. None of the stored positions in between is set.
+    gap_end: usize,
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> From<BitRank<SUB_BLOCKS_PER_BLOCK>>
+    for GapBitRank<SUB_BLOCKS_PER_BLOCK>
+{
+    fn from(bit_rank: BitRank<SUB_BLOCKS_PER_BLOCK>) -> Self {
+        Self {
+            bit_rank,
+            gap_start: 0,
+            gap_end: 0,
+        }
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> GapBitRank<SUB_BLOCKS_PER_BLOCK> {
+    /// The number of bits per block.
+    const BITS_PER_BLOCK: usize = Block::<SUB_BLOCKS_PER_BLOCK>::BITS;
+
+    fn gap_len(&self) -> usize {
+        self.gap_end - self.gap_start
+    }
+
+    /// The position in the underlying [`BitRank`] at which `position` is stored.
+    fn stored_of(&self, position: usize) -> usize {
+        if position < self.gap_start {
+            position
+        } else {
+            position.saturating_add(self.gap_len())
+        }
+    }
+
+    /// The position which is stored at `stored` in the underlying [`BitRank`], which must not be
+    /// within the gap.
+    fn position_of(&self, stored: usize) -> usize {
+        if stored < self.gap_start {
+            stored
+        } else {
+            stored - self.gap_len()
+        }
+    }
+
+    /// The rank at the specified index (exclusive), see [`BitRank::rank`].
+    pub fn rank(&self, idx: usize) -> usize {
+        self.bit_rank.rank(self.stored_of(idx))
+    }
+
+    /// The same as [`GapBitRank::rank`], but without checking whether `idx` is covered by a
+    /// block, see [`BitRank::rank_unchecked`].
+    ///
+    /// # Safety
+    ///
+    /// `idx` must be smaller than [`GapBitRank::universe_size`], otherwise the behavior is
+    /// undefined.
+    pub unsafe fn rank_unchecked(&self, idx: usize) -> usize {
+        // SAFETY: Positions smaller than the universe size are stored at positions smaller than
+        // the universe size of the underlying `BitRank`.
+        unsafe { self.bit_rank.rank_unchecked(self.stored_of(idx)) }
+    }
+
+    /// Returns true if `idx` is an element of the set.
+    pub fn contains(&self, idx: usize) -> bool {
+        self.bit_rank.contains(self.stored_of(idx))
+    }
+
+    /// The position of the element with the given (zero-based) rank, see [`BitRank::select`].
+    pub fn select(&self, rank: usize) -> Option<usize> {
+        self.bit_rank.select(rank).map(|p| self.position_of(p))
+    }
+
+    /// Returns the number of elements in the set.
+    pub fn max_rank(&self) -> usize {
+        self.bit_rank.max_rank()
+    }
+
+    /// One past the largest position covered by the blocks, see [`BitRank::universe_size`].
+    pub fn universe_size(&self) -> usize {
+        let universe = self.bit_rank.universe_size();
+        if universe <= self.gap_start {
+            universe
+        } else {
+            // Nothing is stored behind a gap that reaches the end of the blocks.
+            universe.saturating_sub(self.gap_len()).max(self.gap_start)
+        }
+    }
+
+    /// Returns an iterator over the elements of the set within the half-open `range` in
+    /// increasing order, see [`BitRank::iter_range`].
+    pub fn iter_range(&self, range: Range<usize>) -> impl Iterator<Item = usize> + '_ {
+        let stored = self.stored_of(range.start)..self.stored_of(range.end);
+        self.bit_rank
+            .iter_range(stored)
+            .map(|p| self.position_of(p))
+    }
+
+    /// Returns the number of bytes allocated on the heap, including the blocks of the gap.
+    pub fn heap_size_bytes(&self) -> usize {
+        self.bit_rank.heap_size_bytes()
+    }
+
+    /// Returns the set as a [`BitRank`] without gap.
+    pub fn to_bit_rank(&self) -> BitRank<SUB_BLOCKS_PER_BLOCK> {
+        let mut set = self.clone();
+        // Behind the last element, the gap doesn't shift any element.
+        set.move_gap(set.universe_size());
+        set.bit_rank.trim();
+        set.bit_rank
+    }
+
+    /// Replaces the positions in `range` with the positions `replacement`, which have to be
+    /// sorted and smaller than `len`, and shifts the elements behind the range by
+    /// `len - range.len()`.
+    ///
+    /// This panics if `range` is reversed or if `replacement` isn't sorted or contains positions
+    /// greater than or equal to `len`.
+    pub fn splice(
+        &mut self,
+        range: Range<usize>,
+        replacement: impl IntoIterator<Item = usize>,
+        len: usize,
+    ) {
+        assert!(range.start <= range.end, "invalid range {range:?}");
+        self.move_gap(range.start);
+        // The replaced positions become part of the gap.
+        let removed = self.gap_end..self.gap_end + range.len();
+        self.bit_rank.clear_bits(removed.clone());
+        self.bit_rank.update_ranks(removed.clone());
+        self.gap_end = removed.end;
+
+        self.reserve_gap(len);
+        let start = self.gap_start;
+        let mut previous = None;
+        for position in replacement {
+            assert!(
+                previous < Some(position) && position < len,
+                "replacement must be sorted and smaller than {len}"
+            );
+            self.bit_rank.set_bit(start + position);
+            previous = Some(position);
+        }
+        self.bit_rank.update_ranks(start..start + len);
+        self.gap_start += len;
+        self.bit_rank.trim();
+    }
+
+    /// Moves the gap to `position` by moving the elements between its current and its new
+    /// position to the other side of the gap.
+    fn move_gap(&mut self, position: usize) {
+        let gap_len = self.gap_len();
+        if gap_len > 0 && position < self.gap_start {
+            let moved = position..self.gap_start;
+            let chunks = self.bit_rank.read_bits(moved.clone());
+            self.bit_rank.clear_bits(moved);
+            self.bit_rank.write_bits(position + gap_len, &chunks);
+            self.bit_rank.update_ranks(position..self.gap_end);
+        } else if gap_len > 0 && position > self.gap_start {
+            let moved = self.gap_end..self.gap_end + (position - self.gap_start);
+            let chunks = self.bit_rank.read_bits(moved.clone());
+            self.bit_rank.clear_bits(moved.clone());
+            self.bit_rank.write_bits(self.gap_start, &chunks);
+            self.bit_rank.update_ranks(self.gap_start..moved.end);
+        }
+        self.gap_start = position;
+        self.gap_end = position + gap_len;
+    }
+
+    /// Makes the gap at least `len` positions long.
+    fn reserve_gap(&mut self, len: usize) {
+        let missing = len.saturating_sub(self.gap_len());
+        if missing == 0 {
+            return;
+        }
+        if self.gap_end >= self.bit_rank.universe_size() {
+            // There are no elements behind the gap which would have to be moved.
+            self.gap_end += missing;
+            return;
+        }
+        // Blocks are inserted behind the block containing the start of the gap. The elements in
+        // that block behind the gap move into the last inserted block.
+        let num_blocks = missing.div_ceil(Self::BITS_PER_BLOCK);
+        let block_num = self.gap_start / Self::BITS_PER_BLOCK + 1;
+        let moved =
+            self.gap_end.min(block_num * Self::BITS_PER_BLOCK)..block_num * Self::BITS_PER_BLOCK;
+        let chunks = self.bit_rank.read_bits(moved.clone());
+        self.bit_rank.clear_bits(moved.clone());
+        let mut empty = Block::ZERO;
+        empty.rank = self.bit_rank.blocks[block_num - 1].rank;
+        self.bit_rank.blocks.splice(
+            block_num..block_num,
+            core::iter::repeat_n(empty, num_blocks),
+        );
+        let shift = num_blocks * Self::BITS_PER_BLOCK;
+        self.bit_rank.write_bits(moved.start + shift, &chunks);
+        self.bit_rank
+            .update_ranks((block_num - 1) * Self::BITS_PER_BLOCK..moved.end + shift);
+        self.gap_end += shift;
+    }
+}
+
+/// In-place modifications of the bits, which leave the ranks to [`BitRank::update_ranks`].
+impl<const SUB_BLOCKS_PER_BLOCK: usize> BitRank<SUB_BLOCKS_PER_BLOCK> {
+    /// The block with the given number, appending empty blocks if necessary.
+    fn block_mut(&mut self, block_num: usize) -> &mut Block<SUB_BLOCKS_PER_BLOCK> {
+        if block_num >= self.blocks.len() {
+            let mut empty = Block::ZERO;
+            empty.rank = self.max_rank() as u64;
+            self.blocks.resize(block_num + 1, empty);
+        }
+        &mut self.blocks[block_num]
+    }
+
+    fn set_bit(&mut self, position: usize) {
+        self.block_mut(position / Self::BITS_PER_BLOCK)
+            .set(position % Self::BITS_PER_BLOCK);
+    }
+
+    /// ORs `bits` into the chunk with the given index.
+    fn or_chunk(&mut self, chunk_idx: usize, bits: SubblockBits) {
+        if bits != 0 {
+            self.block_mut(chunk_idx / SUB_BLOCKS_PER_BLOCK).bits
+                [chunk_idx % SUB_BLOCKS_PER_BLOCK] |= bits;
+        }
+    }
+
+    /// Clears the bits of the positions in `range`.
+    fn clear_bits(&mut self, range: Range<usize>) {
+        let end = range.end.min(self.universe_size());
+        if range.start >= end {
+            return;
+        }
+        for chunk_idx in range.start / BITS_PER_SUB_BLOCK..=(end - 1) / BITS_PER_SUB_BLOCK {
+            let chunk_start = chunk_idx * BITS_PER_SUB_BLOCK;
+            let from = range.start.max(chunk_start) - chunk_start;
+            let to = end.min(chunk_start + BITS_PER_SUB_BLOCK) - chunk_start;
+            // Bits are stored from the most significant bit.
+            let mask = (SubblockBits::MAX >> from)
+                & !SubblockBits::MAX.checked_shr(to as u32).unwrap_or(0);
+            self.blocks[chunk_idx / SUB_BLOCKS_PER_BLOCK].bits[chunk_idx % SUB_BLOCKS_PER_BLOCK] &=
+                !mask;
+        }
+    }
+
+    /// The bits of the positions in `range`, 128 per chunk starting at the most significant bit.
+    fn read_bits(&self, range: Range<usize>) -> Vec<SubblockBits> {
+        let end = range.end.min(self.universe_size());
+        (range.start..end)
+            .step_by(BITS_PER_SUB_BLOCK)
+            .map(|from| {
+                let len = (end - from).min(BITS_PER_SUB_BLOCK);
+                self.bits_from(from) & !SubblockBits::MAX.checked_shr(len as u32).unwrap_or(0)
+            })
+            .collect()
+    }
+
+    /// ORs chunks returned by [`BitRank::read_bits`] into the positions starting at `position`.
+    fn write_bits(&mut self, position: usize, chunks: &[SubblockBits]) {
+        let shift = position % BITS_PER_SUB_BLOCK;
+        for (i, &bits) in chunks.iter().enumerate() {
+            let chunk_idx = position / BITS_PER_SUB_BLOCK + i;
+            self.or_chunk(chunk_idx, bits >> shift);
+            if shift > 0 {
+                self.or_chunk(chunk_idx + 1, bits << (BITS_PER_SUB_BLOCK - shift));
+            }
+        }
+    }
+
+    /// Recomputes the ranks of the blocks overlapping `range` after their bits were modified, and
+    /// shifts the ranks of the following blocks by the number of added or removed elements.
+    fn update_ranks(&mut self, range: Range<usize>) {
+        self.select_samples = None;
+        let first = range.start / Self::BITS_PER_BLOCK;
+        if range.is_empty() || first >= self.blocks.len() {
+            return;
+        }
+        let last = ((range.end - 1) / Self::BITS_PER_BLOCK).min(self.blocks.len() - 1);
+        let mut rank = self.blocks[first].rank;
+        for block in &mut self.blocks[first..=last] {
+            block.rank = rank;
+            rank += block.update_sub_blocks();
+        }
+        if let Some(next) = self.blocks.get(last + 1) {
+            let shift = rank.wrapping_sub(next.rank);
+            if shift != 0 {
+                for block in &mut self.blocks[last + 1..] {
+                    block.rank = block.rank.wrapping_add(shift);
+                }
+            }
+        }
+    }
+
+    /// Drops the trailing blocks without any bits set, like [`BitRank::from_blocks`] does.
+    fn trim(&mut self) {
+        while self.blocks.last().is_some_and(Block::is_empty) {
+            self.blocks.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::GapBitRank;
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::BITS_PER_BLOCK;
+    use crate::BitRank;
+
+    fn check_equal(gap: &GapBitRank, expected: &BitRank, len: usize) {
+        assert_eq!(
+            gap.iter_range(0..len).collect::<Vec<_>>(),
+            expected.iter().collect::<Vec<_>>()
+        );
+        for i in (0..len + 2).step_by(7) {
+            assert_eq!(gap.rank(i), expected.rank(i), "rank({i})");
+            assert_eq!(gap.contains(i), expected.contains(i), "contains({i})");
+        }
+        for rank in (0..expected.max_rank() + 1).step_by(7) {
+            assert_eq!(gap.select(rank), expected.select(rank), "select({rank})");
+        }
+        assert!(gap.universe_size() >= expected.max_position().map_or(0, |p| p + 1));
+    }
+
+    #[test]
+    fn test_gap_splice() {
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let mut len = 3 * BITS_PER_BLOCK;
+        let mut expected = bitrank((0..len).filter(|_| rng.random_bool(0.3)));
+        let mut gap = GapBitRank::from(expected.clone());
+        for _ in 0..200 {
+            let start = if rng.random_bool(0.7) {
+                // Most edits are close to each other, like when typing.
+                gap.gap_start.min(len).saturating_sub(100) + rng.random_range(0..200)
+            } else {
+                rng.random_range(0..=len)
+            }
+            .min(len);
+            let end = (start + rng.random_range(0..300)).min(len);
+            let replacement_len = match rng.random_range(0..10) {
+                0 => rng.random_range(0..2 * BITS_PER_BLOCK),
+                _ => rng.random_range(0..100),
+            };
+            let replacement = bitrank((0..replacement_len).filter(|_| rng.random_bool(0.5)));
+            gap.splice(start..end, replacement.iter(), replacement_len);
+            expected = expected.splice(start..end, &replacement, replacement_len);
+            len = len - (end - start) + replacement_len;
+            check_equal(&gap, &expected, len);
+        }
+        // Removing everything leaves an empty set.
+        gap.splice(0..len, [], 0);
+        assert_eq!(gap.max_rank(), 0);
+        assert_eq!(gap.rank(5), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_gap_splice_unsorted() {
+        let mut gap = GapBitRank::from(bitrank([1, 2]));
+        gap.splice(0..1, [2, 1], 3);
+    }
+}
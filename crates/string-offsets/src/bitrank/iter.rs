@@ -0,0 +1,312 @@
+//! Iteration over the elements of a [`BitRank`].
+
+use alloc::vec;
+use core::iter::FusedIterator;
+use core::ops::Range;
+
+use super::{BitRank, SubblockBits, BITS_PER_SUB_BLOCK, DEFAULT_SUB_BLOCKS_PER_BLOCK};
+
+/// An iterator over the elements of a [`BitRank`] in increasing order.
+///
+/// Created by [`BitRank::iter`] and [`BitRank::iter_range`]. The iterator can also be consumed from the back, in which case
+/// elements are returned in decreasing order.
+#[derive(Clone)]
+pub struct Iter<'a, const SUB_BLOCKS_PER_BLOCK: usize = DEFAULT_SUB_BLOCKS_PER_BLOCK> {
+    bit_rank: &'a BitRank<SUB_BLOCKS_PER_BLOCK>,
+    /// Index of the chunk (counted over all blocks) from which `front_bits` was loaded.
+    front_chunk: usize,
+    /// The bits of `front_chunk` which haven't been returned yet.
+    front_bits: SubblockBits,
+    /// Index of the chunk (counted over all blocks) from which `back_bits` was loaded.
+    back_chunk: usize,
+    /// The bits of `back_chunk` which haven't been returned yet.
+    back_bits: SubblockBits,
+    /// The number of elements which haven't been returned yet.
+    ///
+    /// When the front and the back reach the same chunk, `front_bits` and `back_bits` are two
+    /// copies of that chunk. Counting the remaining elements ensures that no element is returned
+    /// twice in this case, since the front consumes the chunk's bits from the most significant
+    /// end and the back from the least significant end.
+    remaining: usize,
+}
+
+impl<'a, const SUB_BLOCKS_PER_BLOCK: usize> Iter<'a, SUB_BLOCKS_PER_BLOCK> {
+    /// Returns an iterator over the elements of `bit_rank` within `range`.
+    pub(super) fn new(bit_rank: &'a BitRank<SUB_BLOCKS_PER_BLOCK>, range: Range<usize>) -> Self {
+        let universe = bit_rank.blocks.len() * BitRank::<SUB_BLOCKS_PER_BLOCK>::BITS_PER_BLOCK;
+        let end = range.end.min(universe);
+        let start = range.start.min(end);
+        if start == end {
+            return Self {
+                bit_rank,
+                front_chunk: 0,
+                front_bits: 0,
+                back_chunk: 0,
+                back_bits: 0,
+                remaining: 0,
+            };
+        }
+        let front_chunk = start / BITS_PER_SUB_BLOCK;
+        // Bits are stored from the most significant bit, so this keeps the bits `start..`.
+        let front_bits = bit_rank.chunk(front_chunk) & (!0 >> (start % BITS_PER_SUB_BLOCK));
+        let back_chunk = (end - 1) / BITS_PER_SUB_BLOCK;
+        // And this keeps the bits `..end`.
+        let back_bits = bit_rank.chunk(back_chunk)
+            & (!0 << (BITS_PER_SUB_BLOCK - 1 - (end - 1) % BITS_PER_SUB_BLOCK));
+        Self {
+            bit_rank,
+            front_chunk,
+            front_bits,
+            back_chunk,
+            back_bits,
+            remaining: bit_rank.rank_range(start..end),
+        }
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> Iterator for Iter<'_, SUB_BLOCKS_PER_BLOCK> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.remaining == 0 {
+            return None;
+        }
+        while self.front_bits == 0 {
+            self.front_chunk += 1;
+            // Skip over blocks without any set bits in one step.
+            while self.front_chunk.is_multiple_of(SUB_BLOCKS_PER_BLOCK)
+                && self.bit_rank.blocks[self.front_chunk / SUB_BLOCKS_PER_BLOCK].is_empty()
+            {
+                self.front_chunk += SUB_BLOCKS_PER_BLOCK;
+            }
+            self.front_bits = self.bit_rank.chunk(self.front_chunk);
+        }
+        let bit_idx = self.front_bits.leading_zeros() as usize;
+        self.front_bits &= !(1 << (BITS_PER_SUB_BLOCK - 1 - bit_idx));
+        self.remaining -= 1;
+        Some(self.front_chunk * BITS_PER_SUB_BLOCK + bit_idx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> DoubleEndedIterator for Iter<'_, SUB_BLOCKS_PER_BLOCK> {
+    fn next_back(&mut self) -> Option<usize> {
+        if self.remaining == 0 {
+            return None;
+        }
+        while self.back_bits == 0 {
+            self.back_chunk -= 1;
+            // Skip over blocks without any set bits in one step.
+            while (self.back_chunk + 1).is_multiple_of(SUB_BLOCKS_PER_BLOCK)
+                && self.bit_rank.blocks[self.back_chunk / SUB_BLOCKS_PER_BLOCK].is_empty()
+            {
+                self.back_chunk -= SUB_BLOCKS_PER_BLOCK;
+            }
+            self.back_bits = self.bit_rank.chunk(self.back_chunk);
+        }
+        let bit_idx = BITS_PER_SUB_BLOCK - 1 - self.back_bits.trailing_zeros() as usize;
+        // Clear the least significant set bit.
+        self.back_bits &= self.back_bits - 1;
+        self.remaining -= 1;
+        Some(self.back_chunk * BITS_PER_SUB_BLOCK + bit_idx)
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> ExactSizeIterator for Iter<'_, SUB_BLOCKS_PER_BLOCK> {}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> FusedIterator for Iter<'_, SUB_BLOCKS_PER_BLOCK> {}
+
+impl<'a, const SUB_BLOCKS_PER_BLOCK: usize> IntoIterator for &'a BitRank<SUB_BLOCKS_PER_BLOCK> {
+    type Item = usize;
+    type IntoIter = Iter<'a, SUB_BLOCKS_PER_BLOCK>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Returns the elements in increasing order. They are decoded into a `Vec` up front, so borrowing
+/// the set with [`BitRank::iter`] is cheaper if the set is still available.
+impl<const SUB_BLOCKS_PER_BLOCK: usize> IntoIterator for BitRank<SUB_BLOCKS_PER_BLOCK> {
+    type Item = usize;
+    type IntoIter = vec::IntoIter<usize>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.to_sorted_vec().into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::distr::Uniform;
+    use rand::prelude::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::BITS_PER_BLOCK;
+
+    #[test]
+    fn test_iter() {
+        assert_eq!(bitrank([]).iter().next(), None);
+
+        let positions = vec![
+            0,
+            1,
+            127,
+            128,
+            1000,
+            BITS_PER_BLOCK - 1,
+            BITS_PER_BLOCK * 5 + 3,
+        ];
+        let br = bitrank(positions.iter().copied());
+        assert_eq!(br.iter().len(), positions.len());
+        assert_eq!(br.iter().collect::<Vec<_>>(), positions);
+    }
+
+    #[test]
+    fn test_iter_step() {
+        let positions: Vec<usize> = (0..5 * BITS_PER_BLOCK).step_by(7).collect();
+        let br = bitrank(positions.iter().copied());
+        for step in [1, 2, 100, positions.len(), positions.len() + 1] {
+            let expected: Vec<usize> = positions.iter().copied().step_by(step).collect();
+            assert_eq!(
+                br.iter_step(step).collect::<Vec<_>>(),
+                expected,
+                "step {step}"
+            );
+        }
+        assert_eq!(br.iter_step(1000).next_back(), Some(positions[11000]));
+        assert_eq!(bitrank([]).iter_step(3).next(), None);
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let positions = vec![3, 200, BITS_PER_BLOCK + 1];
+        let br = bitrank(positions.iter().copied());
+        let mut borrowed = Vec::new();
+        for position in &br {
+            borrowed.push(position);
+        }
+        assert_eq!(borrowed, positions);
+        assert_eq!(
+            br.into_iter().rev().collect::<Vec<_>>(),
+            [BITS_PER_BLOCK + 1, 200, 3]
+        );
+    }
+
+    #[test]
+    fn test_iter_rev() {
+        assert_eq!(bitrank([]).iter().next_back(), None);
+
+        let positions = vec![
+            0,
+            1,
+            127,
+            128,
+            1000,
+            BITS_PER_BLOCK - 1,
+            BITS_PER_BLOCK * 5 + 3,
+        ];
+        let br = bitrank(positions.iter().copied());
+        let mut reversed = positions.clone();
+        reversed.reverse();
+        assert_eq!(br.iter().rev().collect::<Vec<_>>(), reversed);
+        assert_eq!(br.iter().rev().take(2).collect::<Vec<_>>(), reversed[..2]);
+    }
+
+    #[test]
+    fn test_iter_both_ends() {
+        // Front and back meet within the same chunk.
+        let br = bitrank([3, 5, 7, 9]);
+        let mut iter = br.iter();
+        assert_eq!(iter.next(), Some(3));
+        assert_eq!(iter.next_back(), Some(9));
+        assert_eq!(iter.next_back(), Some(7));
+        assert_eq!(iter.next(), Some(5));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+    }
+
+    #[test]
+    fn test_iter_range() {
+        let positions = [
+            0,
+            1,
+            127,
+            128,
+            1000,
+            BITS_PER_BLOCK - 1,
+            BITS_PER_BLOCK * 5 + 3,
+        ];
+        let br = bitrank(positions.iter().copied());
+        let expected = |range: std::ops::Range<usize>| {
+            positions
+                .iter()
+                .copied()
+                .filter(|p| range.contains(p))
+                .collect::<Vec<_>>()
+        };
+        for range in [
+            0..0,
+            0..1,
+            1..128,
+            1..129,
+            127..1001,
+            129..BITS_PER_BLOCK,
+            1000..BITS_PER_BLOCK * 5 + 3,
+            1000..BITS_PER_BLOCK * 5 + 4,
+            BITS_PER_BLOCK..usize::MAX,
+            usize::MAX..usize::MAX,
+        ] {
+            assert_eq!(
+                br.iter_range(range.clone()).collect::<Vec<_>>(),
+                expected(range.clone())
+            );
+            let mut reversed = expected(range.clone());
+            reversed.reverse();
+            assert_eq!(br.iter_range(range).rev().collect::<Vec<_>>(), reversed);
+        }
+    }
+
+    #[test]
+    fn test_iter_large_random() {
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        let uniform = Uniform::new(0, 1_000_000).unwrap();
+        let mut random_bits: Vec<usize> = (0..10_000).map(|_| uniform.sample(&mut rng)).collect();
+        random_bits.sort_unstable();
+        random_bits.dedup();
+        let br = bitrank(random_bits.iter().copied());
+        assert_eq!(br.iter().collect::<Vec<_>>(), random_bits);
+
+        let mut iter = br.iter();
+        let (mut front, mut back) = (0, random_bits.len());
+        while let Some(pos) = if rng.random() {
+            iter.next()
+        } else {
+            iter.next_back()
+        } {
+            if pos == random_bits[front] {
+                front += 1;
+            } else {
+                back -= 1;
+                assert_eq!(pos, random_bits[back]);
+            }
+        }
+        assert_eq!(front, back);
+
+        for _ in 0..100 {
+            let start = rng.random_range(0..1_000_000);
+            let end = rng.random_range(start..1_000_100);
+            let expected: Vec<_> = random_bits
+                .iter()
+                .copied()
+                .filter(|p| (start..end).contains(p))
+                .collect();
+            assert_eq!(br.iter_range(start..end).collect::<Vec<_>>(), expected);
+        }
+    }
+}
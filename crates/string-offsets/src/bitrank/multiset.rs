@@ -0,0 +1,197 @@
+//! A rank data structure for multisets, i.e. sets whose elements can occur more than once.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::{BitRank, BitRankBuilder};
+
+/// An immutable multiset of unsigned integers with the same `rank` API as [`BitRank`], e.g. to
+/// count the events before a position when a position can hold more than one event.
+///
+/// The distinct elements are stored in a [`BitRank`], together with the number of occurrences in
+/// front of every distinct element. [`MultiBitRank::rank`] therefore takes O(1) time, and the
+/// memory consumption is that of the `BitRank` plus one `usize` per distinct element.
+///
+/// # Examples
+///
+/// ```
+/// use string_offsets::bitrank::MultiBitRank;
+///
+/// let events: MultiBitRank = [3, 3, 3, 10, 20, 20].into_iter().collect();
+/// assert_eq!(events.rank(3), 0);
+/// assert_eq!(events.rank(4), 3);
+/// assert_eq!(events.rank(20), 4);
+/// assert_eq!(events.count(20), 2);
+/// assert_eq!(events.select(3), Some(10));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultiBitRank {
+    positions: BitRank,
+    /// The number of occurrences of the `k` smallest distinct elements at index `k`.
+    counts_before: Vec<usize>,
+}
+
+impl MultiBitRank {
+    /// Creates a multiset from pairs of strictly increasing positions and the number of times they
+    /// occur. Pairs with a count of zero are skipped.
+    ///
+    /// This panics if the positions are not strictly increasing.
+    pub fn from_counts(counts: impl IntoIterator<Item = (usize, usize)>) -> Self {
+        let mut builder = BitRankBuilder::new();
+        let mut counts_before = vec![0];
+        let mut total = 0;
+        let mut last = None;
+        for (position, count) in counts {
+            if count == 0 {
+                continue;
+            }
+            assert!(
+                last.is_none_or(|last| last < position),
+                "positions must be increasing!"
+            );
+            last = Some(position);
+            builder.push(position);
+            total += count;
+            counts_before.push(total);
+        }
+        Self {
+            positions: builder.finish(),
+            counts_before,
+        }
+    }
+
+    /// The rank at the specified index (exclusive), i.e. the number of occurrences of elements
+    /// less than `idx`.
+    pub fn rank(&self, idx: usize) -> usize {
+        self.counts_before[self.positions.rank(idx)]
+    }
+
+    /// Returns the number of occurrences of all elements, counting repeated ones repeatedly.
+    pub fn max_rank(&self) -> usize {
+        self.counts_before.last().copied().unwrap_or_default()
+    }
+
+    /// Returns the number of times `idx` occurs in the multiset.
+    pub fn count(&self, idx: usize) -> usize {
+        let rank = self.positions.rank(idx);
+        if self.positions.contains(idx) {
+            self.counts_before[rank + 1] - self.counts_before[rank]
+        } else {
+            0
+        }
+    }
+
+    /// Returns true if `idx` occurs at least once in the multiset.
+    pub fn contains(&self, idx: usize) -> bool {
+        self.positions.contains(idx)
+    }
+
+    /// The element of the occurrence with the given (zero-based) rank, i.e. the inverse of
+    /// [`MultiBitRank::rank`]: `rank(select(k)) <= k < rank(select(k) + 1)`.
+    ///
+    /// Returns `None` if `rank` is greater than or equal to [`MultiBitRank::max_rank`].
+    pub fn select(&self, rank: usize) -> Option<usize> {
+        if rank >= self.max_rank() {
+            return None;
+        }
+        // The first entry is zero, so the partition point is at least 1.
+        let distinct = self.counts_before.partition_point(|&c| c <= rank) - 1;
+        self.positions.select(distinct)
+    }
+
+    /// The distinct elements of the multiset.
+    pub fn positions(&self) -> &BitRank {
+        &self.positions
+    }
+
+    /// Returns an iterator over the distinct elements in increasing order together with the
+    /// number of times they occur.
+    pub fn iter_counts(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.positions
+            .iter()
+            .zip(self.counts_before.windows(2))
+            .map(|(position, counts)| (position, counts[1] - counts[0]))
+    }
+}
+
+/// Collects non-decreasing positions into a `MultiBitRank`, where repeated positions count as
+/// multiple occurrences.
+///
+/// This panics if the positions are decreasing.
+impl FromIterator<usize> for MultiBitRank {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut counts: Vec<(usize, usize)> = Vec::new();
+        for position in iter {
+            match counts.last_mut() {
+                Some((last, count)) if *last == position => *count += 1,
+                _ => counts.push((position, 1)),
+            }
+        }
+        Self::from_counts(counts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::MultiBitRank;
+    use crate::bitrank::BITS_PER_BLOCK;
+
+    #[test]
+    fn test_multiset_matches_naive() {
+        let mut rng = ChaCha8Rng::seed_from_u64(36);
+        let mut events: Vec<usize> = (0..2000)
+            .map(|_| rng.random_range(0..3 * BITS_PER_BLOCK))
+            .collect();
+        // Repeat some of the positions many times.
+        events.extend([0, 5, BITS_PER_BLOCK].repeat(50));
+        events.sort_unstable();
+        let multiset: MultiBitRank = events.iter().copied().collect();
+        assert_eq!(multiset.max_rank(), events.len());
+        for i in (0..3 * BITS_PER_BLOCK + 10)
+            .step_by(3)
+            .chain(events.iter().copied())
+        {
+            let rank = events.partition_point(|&e| e < i);
+            let count = events[rank..].iter().take_while(|&&e| e == i).count();
+            assert_eq!(multiset.rank(i), rank, "position {i}");
+            assert_eq!(multiset.count(i), count, "position {i}");
+            assert_eq!(multiset.contains(i), count > 0, "position {i}");
+        }
+        for (rank, &event) in events.iter().enumerate() {
+            assert_eq!(multiset.select(rank), Some(event), "rank {rank}");
+        }
+        assert_eq!(multiset.select(events.len()), None);
+        let expanded: Vec<usize> = multiset
+            .iter_counts()
+            .flat_map(|(position, count)| std::iter::repeat_n(position, count))
+            .collect();
+        assert_eq!(expanded, events);
+    }
+
+    #[test]
+    fn test_multiset_from_counts() {
+        let multiset = MultiBitRank::from_counts([(1, 2), (4, 0), (7, 3)]);
+        assert_eq!(multiset.rank(5), 2);
+        assert_eq!(multiset.count(4), 0);
+        assert!(!multiset.contains(4));
+        assert_eq!(multiset.count(7), 3);
+        assert_eq!(multiset.max_rank(), 5);
+        assert_eq!(multiset.positions().max_rank(), 2);
+        assert_eq!(multiset.iter_counts().collect::<Vec<_>>(), [(1, 2), (7, 3)]);
+
+        let empty: MultiBitRank = std::iter::empty().collect();
+        assert_eq!(empty.rank(10), 0);
+        assert_eq!(empty.max_rank(), 0);
+        assert_eq!(empty.select(0), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_multiset_decreasing() {
+        let _: MultiBitRank = [3, 2].into_iter().collect();
+    }
+}
@@ -0,0 +1,204 @@
+//! Set operations on [`BitRank`]s.
+//!
+//! The operations combine the bit-vectors of both sets word by word and then rebuild the rank
+//! index, so their cost is proportional to the size of the bit-vectors and independent of the
+//! number of elements.
+
+use alloc::vec::Vec;
+
+use super::{BitRank, Block, SubblockBits, BITS_PER_SUB_BLOCK};
+
+/// The mask which keeps the bits of the chunk with index `chunk_idx` (counted over all blocks)
+/// that are below `len`.
+pub(super) fn prefix_mask(chunk_idx: usize, len: usize) -> SubblockBits {
+    let start = chunk_idx * BITS_PER_SUB_BLOCK;
+    if start + BITS_PER_SUB_BLOCK <= len {
+        !0
+    } else if start >= len {
+        0
+    } else {
+        // Bits are stored from the most significant bit, so this keeps the bits `..len`. The chunk
+        // is partially covered, so the shift is less than 128.
+        !0 << (start + BITS_PER_SUB_BLOCK - len)
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> BitRank<SUB_BLOCKS_PER_BLOCK> {
+    /// Returns the set of integers which are elements of `self`, `other`, or both.
+    pub fn union(&self, other: &Self) -> Self {
+        let num_blocks = self.blocks.len().max(other.blocks.len());
+        self.combine(other, num_blocks, |a, b| a | b)
+    }
+
+    /// Returns the set of integers which are elements of both `self` and `other`.
+    pub fn intersect(&self, other: &Self) -> Self {
+        let num_blocks = self.blocks.len().min(other.blocks.len());
+        self.combine(other, num_blocks, |a, b| a & b)
+    }
+
+    /// Returns the set of integers which are elements of `self`, but not of `other`.
+    pub fn difference(&self, other: &Self) -> Self {
+        self.combine(other, self.blocks.len(), |a, b| a & !b)
+    }
+
+    /// Returns the set of integers which are elements of either `self` or `other`, but not of
+    /// both.
+    pub fn symmetric_difference(&self, other: &Self) -> Self {
+        let num_blocks = self.blocks.len().max(other.blocks.len());
+        self.combine(other, num_blocks, |a, b| a ^ b)
+    }
+
+    /// Returns the set of integers in `0..universe_len` which are **not** elements of `self`.
+    ///
+    /// The rank of the complement at `i < universe_len` equals [`BitRank::rank0`] of `self`.
+    pub fn complement(&self, universe_len: usize) -> Self {
+        Self::from_chunks(universe_len.div_ceil(Self::BITS_PER_BLOCK), |c| {
+            !self.chunk(c) & prefix_mask(c, universe_len)
+        })
+    }
+
+    /// Combines the first `num_blocks` blocks of both sets chunk by chunk with `op` and rebuilds
+    /// the rank index. Blocks past the end of a set are treated as empty.
+    fn combine(
+        &self,
+        other: &Self,
+        num_blocks: usize,
+        op: impl Fn(SubblockBits, SubblockBits) -> SubblockBits,
+    ) -> Self {
+        Self::from_chunks(num_blocks, |c| op(self.chunk(c), other.chunk(c)))
+    }
+
+    /// Creates a `BitRank` with `num_blocks` blocks whose chunks are produced by `chunk_fn`, called
+    /// with the index of each chunk (counted over all blocks).
+    pub(super) fn from_chunks(
+        num_blocks: usize,
+        mut chunk_fn: impl FnMut(usize) -> SubblockBits,
+    ) -> Self {
+        let mut blocks = Vec::with_capacity(num_blocks);
+        for i in 0..num_blocks {
+            blocks.push(Block::ZERO);
+            let block = blocks.last_mut().expect("just inserted");
+            for (j, chunk) in block.bits.iter_mut().enumerate() {
+                *chunk = chunk_fn(i * SUB_BLOCKS_PER_BLOCK + j);
+            }
+        }
+        Self::from_blocks(blocks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use rand::distr::Uniform;
+    use rand::prelude::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::BITS_PER_BLOCK;
+
+    /// Returns a random set with roughly `len` elements below `max`.
+    fn random_set(rng: &mut impl Rng, len: usize, max: usize) -> BTreeSet<usize> {
+        let uniform = Uniform::new(0, max).unwrap();
+        (0..len).map(|_| uniform.sample(&mut *rng)).collect()
+    }
+
+    /// Checks that all queries on `br` agree with `expected`.
+    fn assert_set_eq(br: &crate::BitRank, expected: &BTreeSet<usize>) {
+        assert_eq!(br.iter().collect::<BTreeSet<_>>(), *expected);
+        assert_eq!(br.max_rank(), expected.len());
+        for (k, &pos) in expected.iter().enumerate() {
+            assert_eq!(br.rank(pos), k);
+            assert_eq!(br.select(k), Some(pos));
+        }
+    }
+
+    #[test]
+    fn test_union() {
+        let a = bitrank([1, 5, BITS_PER_BLOCK + 3]);
+        let b = bitrank([0, 5, 6, BITS_PER_BLOCK * 3]);
+        assert_eq!(
+            a.union(&b).iter().collect::<Vec<_>>(),
+            [0, 1, 5, 6, BITS_PER_BLOCK + 3, BITS_PER_BLOCK * 3]
+        );
+        assert_eq!(
+            a.union(&bitrank([])).iter().collect::<Vec<_>>(),
+            [1, 5, BITS_PER_BLOCK + 3]
+        );
+    }
+
+    #[test]
+    fn test_intersect() {
+        let a = bitrank([1, 5, BITS_PER_BLOCK + 3, BITS_PER_BLOCK * 3]);
+        let b = bitrank([0, 5, 6, BITS_PER_BLOCK * 3]);
+        assert_eq!(
+            a.intersect(&b).iter().collect::<Vec<_>>(),
+            [5, BITS_PER_BLOCK * 3]
+        );
+        assert_eq!(a.intersect(&bitrank([])).max_rank(), 0);
+        // Trailing blocks which become empty are dropped.
+        let c = bitrank([5, BITS_PER_BLOCK * 3 + 1]);
+        assert_eq!(a.intersect(&c).blocks.len(), 1);
+    }
+
+    #[test]
+    fn test_difference() {
+        let a = bitrank([1, 5, BITS_PER_BLOCK + 3, BITS_PER_BLOCK * 3]);
+        let b = bitrank([0, 5, 6, BITS_PER_BLOCK * 3]);
+        assert_eq!(
+            a.difference(&b).iter().collect::<Vec<_>>(),
+            [1, BITS_PER_BLOCK + 3]
+        );
+        assert_eq!(b.difference(&a).iter().collect::<Vec<_>>(), [0, 6]);
+        assert_eq!(
+            a.symmetric_difference(&b).iter().collect::<Vec<_>>(),
+            [0, 1, 6, BITS_PER_BLOCK + 3]
+        );
+        assert_eq!(a.difference(&a).max_rank(), 0);
+        assert!(a.symmetric_difference(&a).blocks.is_empty());
+    }
+
+    #[test]
+    fn test_complement() {
+        let a = bitrank([1, 5, BITS_PER_BLOCK + 3]);
+        let c = a.complement(8);
+        assert_eq!(c.iter().collect::<Vec<_>>(), [0, 2, 3, 4, 6, 7]);
+        assert_eq!(a.complement(0).max_rank(), 0);
+        for len in [
+            127,
+            128,
+            129,
+            BITS_PER_BLOCK,
+            BITS_PER_BLOCK + 3,
+            BITS_PER_BLOCK * 2 + 1,
+        ] {
+            let c = a.complement(len);
+            assert_eq!(c.max_rank(), len - a.rank(len));
+            for i in 0..=len {
+                assert_eq!(c.rank(i), a.rank0(i));
+            }
+            assert_eq!(
+                c.complement(len).iter().collect::<Vec<_>>(),
+                a.iter_range(0..len).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_random_set_operations() {
+        let mut rng = ChaCha8Rng::seed_from_u64(6);
+        for (len_a, len_b) in [(1000, 1000), (10, 10_000), (10_000, 10)] {
+            let a = random_set(&mut rng, len_a, 200_000);
+            let b = random_set(&mut rng, len_b, 100_000);
+            let (br_a, br_b) = (bitrank(a.iter().copied()), bitrank(b.iter().copied()));
+            assert_set_eq(&br_a.union(&br_b), &(&a | &b));
+            assert_set_eq(&br_a.intersect(&br_b), &(&a & &b));
+            assert_set_eq(&br_a.difference(&br_b), &(&a - &b));
+            assert_set_eq(&br_b.difference(&br_a), &(&b - &a));
+            assert_set_eq(&br_a.symmetric_difference(&br_b), &(&a ^ &b));
+            let universe: BTreeSet<usize> = (0..150_000).collect();
+            assert_set_eq(&br_a.complement(150_000), &(&universe - &a));
+        }
+    }
+}
@@ -0,0 +1,36 @@
+//! Helpers for bit fields packed back to back into `u64` words.
+
+/// A mask of the lowest `len` bits.
+pub(super) fn low_mask(len: u32) -> u64 {
+    if len == 0 {
+        0
+    } else {
+        u64::MAX >> (64 - len)
+    }
+}
+
+/// Writes the lowest `len` bits of `value` at bit offset `offset` into `words`.
+pub(super) fn write_bits(words: &mut [u64], offset: usize, len: u32, value: u64) {
+    if len == 0 {
+        return;
+    }
+    let value = value & low_mask(len);
+    let (word, shift) = (offset / 64, (offset % 64) as u32);
+    words[word] |= value << shift;
+    if shift + len > 64 {
+        words[word + 1] |= value >> (64 - shift);
+    }
+}
+
+/// Reads `len` bits at bit offset `offset` from `words`.
+pub(super) fn read_bits(words: &[u64], offset: usize, len: u32) -> u64 {
+    if len == 0 {
+        return 0;
+    }
+    let (word, shift) = (offset / 64, (offset % 64) as u32);
+    let mut value = words[word] >> shift;
+    if shift + len > 64 {
+        value |= words[word + 1] << (64 - shift);
+    }
+    value & low_mask(len)
+}
@@ -0,0 +1,118 @@
+//! Parallel construction of and queries on a [`BitRank`] with [`rayon`](::rayon).
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use ::rayon::prelude::*;
+
+use super::{BitRank, Block, BITS_PER_SUB_BLOCK};
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> BitRank<SUB_BLOCKS_PER_BLOCK> {
+    /// Creates a `BitRank` containing the strictly increasing `positions`, filling the blocks on
+    /// the rayon thread pool.
+    ///
+    /// Every block is filled and indexed independently, only the block ranks are computed by a
+    /// final sequential pass. The result is the same as collecting the positions with a
+    /// [`super::BitRankBuilder`].
+    ///
+    /// This panics if the positions are not strictly increasing.
+    pub fn par_from_sorted(positions: &[usize]) -> Self {
+        assert!(
+            positions.par_windows(2).all(|w| w[0] < w[1]),
+            "positions must be increasing!"
+        );
+        let Some(&max) = positions.last() else {
+            return Self::from_blocks(vec![]);
+        };
+        let mut blocks = vec![Block::ZERO; max / Self::BITS_PER_BLOCK + 1];
+        let counts: Vec<u64> = blocks
+            .par_iter_mut()
+            .enumerate()
+            .map(|(i, block)| {
+                let start = i * Self::BITS_PER_BLOCK;
+                let first = positions.partition_point(|&p| p < start);
+                let last = positions.partition_point(|&p| p < start + Self::BITS_PER_BLOCK);
+                for &position in &positions[first..last] {
+                    let local_idx = position - start;
+                    block.bits[local_idx / BITS_PER_SUB_BLOCK] |=
+                        1 << (BITS_PER_SUB_BLOCK - 1 - local_idx % BITS_PER_SUB_BLOCK);
+                }
+                block.update_sub_blocks()
+            })
+            .collect();
+        let mut rank = 0;
+        for (block, count) in blocks.iter_mut().zip(counts) {
+            block.rank = rank;
+            rank += count;
+        }
+        Self {
+            blocks,
+            select_samples: None,
+        }
+    }
+
+    /// The ranks at the specified indices, i.e. the same as
+    /// `indices.iter().map(|&i| self.rank(i)).collect()`, computed on the rayon thread pool.
+    ///
+    /// The indices are split into batches which are answered with
+    /// [`BitRank::rank_many_unordered`] on the worker threads. This only pays off for many
+    /// queries, e.g. millions of offsets which are converted in an offline job.
+    pub fn par_rank_many(&self, indices: &[usize]) -> Vec<usize> {
+        // Large enough to amortize the scheduling, small enough to balance the load.
+        const BATCH_LEN: usize = 4096;
+        indices
+            .par_chunks(BATCH_LEN)
+            .flat_map_iter(|batch| self.rank_many_unordered(batch))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::{BitRank, BITS_PER_BLOCK};
+
+    #[test]
+    fn test_par_from_sorted() {
+        let mut rng = ChaCha8Rng::seed_from_u64(25);
+        let positions: Vec<usize> = (0..40 * BITS_PER_BLOCK)
+            .filter(|i| !(3 * BITS_PER_BLOCK..=30 * BITS_PER_BLOCK).contains(i))
+            .filter(|_| rng.random_bool(0.05))
+            .collect();
+        let br = BitRank::<128>::par_from_sorted(&positions);
+        let expected = bitrank(positions.iter().copied());
+        assert_eq!(br.max_rank(), expected.max_rank());
+        for i in (0..41 * BITS_PER_BLOCK).step_by(97) {
+            assert_eq!(br.rank(i), expected.rank(i));
+        }
+        for rank in 0..positions.len() {
+            assert_eq!(br.select(rank), expected.select(rank));
+        }
+        assert_eq!(BitRank::<128>::par_from_sorted(&[]).max_rank(), 0);
+    }
+
+    #[test]
+    fn test_par_rank_many() {
+        let mut rng = ChaCha8Rng::seed_from_u64(39);
+        let positions: Vec<usize> = (0..10 * BITS_PER_BLOCK)
+            .filter(|_| rng.random_bool(0.1))
+            .collect();
+        let br = bitrank(positions.iter().copied());
+        let indices: Vec<usize> = (0..20_000)
+            .map(|_| rng.random_range(0..11 * BITS_PER_BLOCK))
+            .collect();
+        let expected: Vec<usize> = indices.iter().map(|&i| br.rank(i)).collect();
+        assert_eq!(br.par_rank_many(&indices), expected);
+        assert!(br.par_rank_many(&[]).is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_par_from_sorted_decreasing() {
+        BitRank::<128>::par_from_sorted(&[3, 2]);
+    }
+}
@@ -0,0 +1,280 @@
+//! Loading a range of blocks of a serialized [`BitRank`].
+//!
+//! All blocks of the serialized format have the same length, so the offset of every block follows
+//! from its index and only the blocks covering the requested positions have to be read.
+
+#[cfg(feature = "std")]
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::io::{self, Read, Seek, SeekFrom};
+
+#[cfg(feature = "std")]
+use super::serialize::HEADER_LEN;
+use super::serialize::{block_len, read_header, FormatError};
+use super::{Block, DEFAULT_SUB_BLOCKS_PER_BLOCK};
+
+/// The blocks of a serialized [`super::BitRank`] which cover a range of positions, e.g. of a
+/// persisted index which is too large to be loaded as a whole.
+///
+/// The blocks keep their ranks, so queries within [`PartialBitRank::positions`] return the same
+/// results as on the complete set. Queries outside of the loaded blocks return `None`.
+///
+/// # Examples
+///
+/// ```
+/// use string_offsets::bitrank::PartialBitRank;
+/// use string_offsets::BitRank;
+///
+/// let set: BitRank = [17, 100_000, 200_000].into_iter().collect();
+/// let bytes = set.to_bytes();
+/// let partial: PartialBitRank = PartialBitRank::from_bytes(&bytes, 99_000..101_000).unwrap();
+/// assert!(partial.positions().contains(&100_000));
+/// assert_eq!(partial.rank(100_001), Some(2));
+/// assert_eq!(partial.select(1), Some(100_000));
+/// assert_eq!(partial.rank(1000), None);
+/// ```
+#[derive(Clone)]
+pub struct PartialBitRank<const SUB_BLOCKS_PER_BLOCK: usize = DEFAULT_SUB_BLOCKS_PER_BLOCK> {
+    /// The index of the first loaded block within the complete set.
+    first_block: usize,
+    blocks: Vec<Block<SUB_BLOCKS_PER_BLOCK>>,
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> PartialBitRank<SUB_BLOCKS_PER_BLOCK> {
+    /// The number of bits per block.
+    const BITS_PER_BLOCK: usize = Block::<SUB_BLOCKS_PER_BLOCK>::BITS;
+
+    /// Loads the blocks covering `positions` from data in the serialized format of
+    /// [`super::BitRank::to_bytes`]. Blocks past the end of the data aren't loaded.
+    ///
+    /// Like [`super::BitRank::from_bytes`], the rank index of the loaded blocks is checked
+    /// against their bits.
+    pub fn from_bytes(bytes: &[u8], positions: Range<usize>) -> Result<Self, FormatError> {
+        let data = read_header(bytes, SUB_BLOCKS_PER_BLOCK)?;
+        let block_len = block_len(SUB_BLOCKS_PER_BLOCK);
+        let blocks = Self::block_range(positions, data.len() / block_len);
+        Self::decode(
+            blocks.start,
+            &data[blocks.start * block_len..blocks.end * block_len],
+        )
+    }
+
+    /// Loads the blocks covering `positions` from a reader which is positioned at the start of
+    /// data in the serialized format. Only the header and the requested blocks are read.
+    ///
+    /// This requires the `std` feature. Data which isn't in the serialized format results in an
+    /// error of kind [`io::ErrorKind::InvalidData`] wrapping a [`FormatError`].
+    #[cfg(feature = "std")]
+    pub fn read_from<R: Read + Seek>(mut reader: R, positions: Range<usize>) -> io::Result<Self> {
+        let invalid_data = |e: FormatError| io::Error::new(io::ErrorKind::InvalidData, e);
+        let start = reader.stream_position()?;
+        let mut header = [0; HEADER_LEN];
+        reader.read_exact(&mut header)?;
+        read_header(&header, SUB_BLOCKS_PER_BLOCK).map_err(invalid_data)?;
+        let len = reader.seek(SeekFrom::End(0))? - start;
+        let block_len = block_len(SUB_BLOCKS_PER_BLOCK);
+        let blocks_len = len - HEADER_LEN as u64;
+        if !blocks_len.is_multiple_of(block_len as u64) {
+            return Err(invalid_data(FormatError::InvalidLength(len as usize)));
+        }
+        let num_blocks = usize::try_from(blocks_len / block_len as u64)
+            .map_err(|_| invalid_data(FormatError::TooLarge))?;
+        let blocks = Self::block_range(positions, num_blocks);
+        reader.seek(SeekFrom::Start(
+            start + (HEADER_LEN + blocks.start * block_len) as u64,
+        ))?;
+        let mut bytes = vec![0; blocks.len() * block_len];
+        reader.read_exact(&mut bytes)?;
+        Self::decode(blocks.start, &bytes).map_err(invalid_data)
+    }
+
+    /// The indices of the blocks covering `positions`, limited to the first `num_blocks`.
+    fn block_range(positions: Range<usize>, num_blocks: usize) -> Range<usize> {
+        let end = positions.end.div_ceil(Self::BITS_PER_BLOCK).min(num_blocks);
+        (positions.start / Self::BITS_PER_BLOCK).min(end)..end
+    }
+
+    /// Decodes consecutive serialized blocks, the first of which has the index `first_block`.
+    fn decode(first_block: usize, bytes: &[u8]) -> Result<Self, FormatError> {
+        let num_blocks = bytes.len() / block_len(SUB_BLOCKS_PER_BLOCK);
+        first_block
+            .checked_add(num_blocks)
+            .and_then(|end| end.checked_mul(Self::BITS_PER_BLOCK))
+            .ok_or(FormatError::TooLarge)?;
+        let mut blocks: Vec<Block<SUB_BLOCKS_PER_BLOCK>> = Vec::with_capacity(num_blocks);
+        for (i, bytes) in bytes
+            .chunks_exact(block_len(SUB_BLOCKS_PER_BLOCK))
+            .enumerate()
+        {
+            let (block, _) =
+                Block::decode(bytes).ok_or(FormatError::InvalidIndex(first_block + i))?;
+            if blocks
+                .last()
+                .is_some_and(|prev| prev.total_rank() as u64 != block.rank)
+            {
+                return Err(FormatError::InvalidIndex(first_block + i));
+            }
+            blocks.push(block);
+        }
+        Ok(Self {
+            first_block,
+            blocks,
+        })
+    }
+
+    /// The positions covered by the loaded blocks.
+    pub fn positions(&self) -> Range<usize> {
+        let start = self.first_block * Self::BITS_PER_BLOCK;
+        start..start + self.blocks.len() * Self::BITS_PER_BLOCK
+    }
+
+    /// The ranks of the elements in the loaded blocks.
+    pub fn ranks(&self) -> Range<usize> {
+        match (self.blocks.first(), self.blocks.last()) {
+            (Some(first), Some(last)) => first.rank as usize..last.total_rank(),
+            _ => 0..0,
+        }
+    }
+
+    /// The rank at the specified index (exclusive) within the complete set, or `None` if `idx`
+    /// isn't in [`PartialBitRank::positions`] or its end.
+    pub fn rank(&self, idx: usize) -> Option<usize> {
+        let positions = self.positions();
+        if idx < positions.start || idx > positions.end || self.blocks.is_empty() {
+            return None;
+        }
+        let local_idx = idx - positions.start;
+        Some(match self.blocks.get(local_idx / Self::BITS_PER_BLOCK) {
+            Some(block) => block.rank_and_prev(local_idx % Self::BITS_PER_BLOCK).rank,
+            None => self.ranks().end,
+        })
+    }
+
+    /// Returns whether `idx` is an element of the set, or `None` if `idx` isn't in
+    /// [`PartialBitRank::positions`].
+    pub fn contains(&self, idx: usize) -> Option<bool> {
+        let local_idx = idx.checked_sub(self.positions().start)?;
+        self.blocks
+            .get(local_idx / Self::BITS_PER_BLOCK)
+            .map(|block| block.get(local_idx % Self::BITS_PER_BLOCK))
+    }
+
+    /// The element with the given (zero-based) rank within the complete set, or `None` if `rank`
+    /// isn't in [`PartialBitRank::ranks`].
+    pub fn select(&self, rank: usize) -> Option<usize> {
+        if !self.ranks().contains(&rank) {
+            return None;
+        }
+        // The first block has a rank `<= rank`, so the partition point is at least 1.
+        let block_num = self.blocks.partition_point(|b| b.rank as usize <= rank) - 1;
+        let block = &self.blocks[block_num];
+        Some(
+            (self.first_block + block_num) * Self::BITS_PER_BLOCK
+                + block.select(rank - block.rank as usize),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::PartialBitRank;
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::{FormatError, BITS_PER_BLOCK, DEFAULT_SUB_BLOCKS_PER_BLOCK};
+
+    #[test]
+    fn test_partial_matches_bitrank() {
+        let mut rng = ChaCha8Rng::seed_from_u64(41);
+        let positions: Vec<usize> = (0..8 * BITS_PER_BLOCK)
+            .filter(|_| rng.random_bool(0.02))
+            .collect();
+        let br = bitrank(positions.iter().copied());
+        let bytes = br.to_bytes();
+        let partial: PartialBitRank =
+            PartialBitRank::from_bytes(&bytes, 3 * BITS_PER_BLOCK + 5..5 * BITS_PER_BLOCK + 1)
+                .unwrap();
+        assert_eq!(partial.positions(), 3 * BITS_PER_BLOCK..6 * BITS_PER_BLOCK);
+        assert_eq!(
+            partial.ranks(),
+            br.rank(3 * BITS_PER_BLOCK)..br.rank(6 * BITS_PER_BLOCK)
+        );
+        for i in (0..9 * BITS_PER_BLOCK).step_by(17) {
+            let inside = partial.positions().contains(&i);
+            let expected_rank = (inside || i == partial.positions().end).then(|| br.rank(i));
+            assert_eq!(partial.rank(i), expected_rank, "rank({i})");
+            assert_eq!(
+                partial.contains(i),
+                inside.then(|| br.contains(i)),
+                "contains({i})"
+            );
+        }
+        for rank in 0..=positions.len() {
+            let expected = br.select(rank).filter(|p| partial.positions().contains(p));
+            assert_eq!(partial.select(rank), expected, "select({rank})");
+        }
+    }
+
+    #[test]
+    fn test_partial_past_end() {
+        let bytes = bitrank([5, BITS_PER_BLOCK + 1]).to_bytes();
+        let partial: PartialBitRank =
+            PartialBitRank::from_bytes(&bytes, BITS_PER_BLOCK..10 * BITS_PER_BLOCK).unwrap();
+        assert_eq!(partial.positions(), BITS_PER_BLOCK..2 * BITS_PER_BLOCK);
+        assert_eq!(partial.rank(2 * BITS_PER_BLOCK), Some(2));
+
+        let empty: PartialBitRank =
+            PartialBitRank::from_bytes(&bytes, 5 * BITS_PER_BLOCK..6 * BITS_PER_BLOCK).unwrap();
+        assert!(empty.positions().is_empty());
+        assert_eq!(empty.rank(5 * BITS_PER_BLOCK), None);
+        assert_eq!(empty.select(0), None);
+    }
+
+    #[test]
+    fn test_partial_invalid_index() {
+        let mut bytes = bitrank([5, BITS_PER_BLOCK + 1, 2 * BITS_PER_BLOCK]).to_bytes();
+        // Corrupt the block rank of the third block.
+        let offset = bytes.len() - super::block_len(DEFAULT_SUB_BLOCKS_PER_BLOCK);
+        bytes[offset] ^= 1;
+        assert!(PartialBitRank::<DEFAULT_SUB_BLOCKS_PER_BLOCK>::from_bytes(
+            &bytes,
+            0..BITS_PER_BLOCK
+        )
+        .is_ok());
+        assert_eq!(
+            PartialBitRank::<DEFAULT_SUB_BLOCKS_PER_BLOCK>::from_bytes(
+                &bytes,
+                0..3 * BITS_PER_BLOCK
+            )
+            .err(),
+            Some(FormatError::InvalidIndex(2))
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_from() {
+        let br = bitrank([5, BITS_PER_BLOCK + 1, 4 * BITS_PER_BLOCK]);
+        let mut data = b"prefix".to_vec();
+        data.extend(br.to_bytes());
+        let mut reader = std::io::Cursor::new(data);
+        reader.set_position(6);
+        let partial: PartialBitRank =
+            PartialBitRank::read_from(reader, BITS_PER_BLOCK..2 * BITS_PER_BLOCK).unwrap();
+        assert_eq!(partial.positions(), BITS_PER_BLOCK..2 * BITS_PER_BLOCK);
+        assert_eq!(partial.select(1), Some(BITS_PER_BLOCK + 1));
+        assert_eq!(partial.rank(2 * BITS_PER_BLOCK), Some(2));
+
+        let err = PartialBitRank::<DEFAULT_SUB_BLOCKS_PER_BLOCK>::read_from(
+            std::io::Cursor::new(b"nope".repeat(4)),
+            0..1,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
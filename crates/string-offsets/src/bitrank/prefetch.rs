@@ -0,0 +1,22 @@
+//! Software prefetching of the memory touched by upcoming queries.
+
+/// Hints the CPU to load the cache line containing `value` into all cache levels.
+///
+/// This is a no-op on targets without a prefetch instruction.
+#[inline(always)]
+pub(super) fn prefetch<T>(value: &T) {
+    let ptr: *const T = value;
+    #[cfg(target_arch = "x86_64")]
+    // SAFETY: Prefetching is only a hint and never faults, SSE is part of the x86_64 baseline.
+    unsafe {
+        use core::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        _mm_prefetch::<_MM_HINT_T0>(ptr.cast());
+    }
+    #[cfg(target_arch = "aarch64")]
+    // SAFETY: Prefetching is only a hint and never faults.
+    unsafe {
+        core::arch::asm!("prfm pldl1keep, [{ptr}]", ptr = in(reg) ptr, options(nostack, readonly, preserves_flags));
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    let _ = ptr;
+}
@@ -0,0 +1,171 @@
+//! A rank data structure for sets consisting of long runs of consecutive integers.
+
+use alloc::vec::Vec;
+
+use super::{BitRank, BitRankBuilder, RankSelect, BITS_PER_SUB_BLOCK};
+
+/// An immutable set of unsigned integers with the same `rank` API as [`BitRank`], which stores
+/// runs of consecutive integers.
+///
+/// Memory consumption and query time only depend on the number of runs, so this is the preferred
+/// representation for bit-vectors with long runs of identical bits. [`RleBitRank::rank`] takes
+/// O(log r) time for `r` runs.
+///
+/// # Examples
+///
+/// ```
+/// use string_offsets::BitRankBuilder;
+///
+/// let mut builder = BitRankBuilder::new();
+/// builder.push_run(0..1_000_000);
+/// builder.push_run(2_000_000..3_000_000);
+/// let set = builder.finish_rle();
+/// assert_eq!(set.rank(2_000_100), 1_000_100);
+/// assert_eq!(set.runs(), 2);
+/// ```
+#[derive(Clone)]
+pub struct RleBitRank {
+    /// The first element of every run, in increasing order.
+    starts: Vec<usize>,
+    /// The number of elements up to and including every run.
+    ends_rank: Vec<usize>,
+}
+
+impl RleBitRank {
+    /// Returns the index of the last run starting before `idx`, if any.
+    fn run_before(&self, idx: usize) -> Option<usize> {
+        self.starts
+            .partition_point(|&start| start < idx)
+            .checked_sub(1)
+    }
+
+    /// The number of elements in the run with the given index.
+    fn run_len(&self, run: usize) -> usize {
+        self.ends_rank[run] - run.checked_sub(1).map_or(0, |r| self.ends_rank[r])
+    }
+
+    /// Returns the number of runs of consecutive integers.
+    pub fn runs(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// The rank at the specified index (exclusive), i.e. the number of elements less than `idx`.
+    pub fn rank(&self, idx: usize) -> usize {
+        match self.run_before(idx) {
+            None => 0,
+            Some(run) => {
+                let covered = (idx - self.starts[run]).min(self.run_len(run));
+                self.ends_rank[run] - self.run_len(run) + covered
+            }
+        }
+    }
+
+    /// Returns the number of elements in the set.
+    pub fn max_rank(&self) -> usize {
+        self.ends_rank.last().copied().unwrap_or_default()
+    }
+
+    /// The rank at the specified index (exclusive) and the largest element smaller than `idx`
+    /// **if** it falls into the same 128-bit chunk as `idx`, otherwise `None`.
+    ///
+    /// This returns exactly the same results as [`BitRank::rank_and_prev`].
+    pub fn rank_and_prev(&self, idx: usize) -> RankSelect {
+        let Some(run) = self.run_before(idx) else {
+            return RankSelect::default();
+        };
+        let covered = (idx - self.starts[run]).min(self.run_len(run));
+        let rank = self.ends_rank[run] - self.run_len(run) + covered;
+        let prev = self.starts[run] + covered - 1;
+        let chunk_start = idx - idx % BITS_PER_SUB_BLOCK;
+        RankSelect {
+            rank,
+            prev_set_in_chunk: Some(prev).filter(|&prev| prev >= chunk_start),
+        }
+    }
+
+    /// The element with the given (zero-based) rank, or `None` if `rank` is greater than or equal
+    /// to the number of elements in the set.
+    pub fn select(&self, rank: usize) -> Option<usize> {
+        let run = self.ends_rank.partition_point(|&r| r <= rank);
+        let start_rank = self.ends_rank.get(run)? - self.run_len(run);
+        Some(self.starts[run] + rank - start_rank)
+    }
+
+    /// Returns true if `idx` is an element of the set.
+    pub fn contains(&self, idx: usize) -> bool {
+        let run = self.starts.partition_point(|&start| start <= idx);
+        run.checked_sub(1)
+            .is_some_and(|run| idx - self.starts[run] < self.run_len(run))
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> From<&BitRank<SUB_BLOCKS_PER_BLOCK>> for RleBitRank {
+    fn from(bit_rank: &BitRank<SUB_BLOCKS_PER_BLOCK>) -> Self {
+        let mut starts = Vec::new();
+        let mut ends_rank = Vec::new();
+        let mut prev = None;
+        for (rank, position) in bit_rank.iter().enumerate() {
+            if prev.is_some_and(|prev| prev + 1 == position) {
+                *ends_rank.last_mut().expect("run was started") = rank + 1;
+            } else {
+                starts.push(position);
+                ends_rank.push(rank + 1);
+            }
+            prev = Some(position);
+        }
+        Self { starts, ends_rank }
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> BitRankBuilder<SUB_BLOCKS_PER_BLOCK> {
+    /// Finishes the set like [`BitRankBuilder::finish`], but returns it as runs of consecutive
+    /// integers.
+    pub fn finish_rle(self) -> RleBitRank {
+        RleBitRank::from(&self.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::RleBitRank;
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::RankSelect;
+
+    #[test]
+    fn test_rle_matches_bitrank() {
+        let mut rng = ChaCha8Rng::seed_from_u64(12);
+        let mut positions = Vec::new();
+        let mut start = 0;
+        for _ in 0..100 {
+            start += rng.random_range(1..1000);
+            let len = rng.random_range(1..1000);
+            positions.extend(start..start + len);
+            start += len;
+        }
+        let br = bitrank(positions.iter().copied());
+        let rle = RleBitRank::from(&br);
+        assert!(rle.runs() <= 100);
+        assert_eq!(rle.max_rank(), br.max_rank());
+        for i in 0..start + 200 {
+            assert_eq!(rle.rank_and_prev(i), br.rank_and_prev(i), "position {i}");
+            assert_eq!(rle.rank(i), br.rank(i), "position {i}");
+            assert_eq!(rle.contains(i), br.contains(i), "position {i}");
+        }
+        for k in 0..=positions.len() {
+            assert_eq!(rle.select(k), br.select(k));
+        }
+    }
+
+    #[test]
+    fn test_rle_empty() {
+        let rle = RleBitRank::from(&bitrank([]));
+        assert_eq!(rle.runs(), 0);
+        assert_eq!(rle.rank_and_prev(100), RankSelect::default());
+        assert_eq!(rle.select(0), None);
+        assert!(!rle.contains(0));
+    }
+}
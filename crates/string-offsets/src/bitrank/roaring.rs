@@ -0,0 +1,54 @@
+//! Conversions between [`BitRank`] and [`RoaringBitmap`].
+
+use ::roaring::RoaringBitmap;
+
+use super::{BitRank, BitRankBuilder};
+
+/// Creates a `BitRank` containing the same integers as the roaring bitmap.
+impl From<&RoaringBitmap> for BitRank {
+    fn from(bitmap: &RoaringBitmap) -> Self {
+        let mut builder = match bitmap.max() {
+            Some(max) => BitRankBuilder::with_capacity(max as usize + 1),
+            None => BitRankBuilder::new(),
+        };
+        builder.push_many(bitmap.iter().map(|value| value as usize));
+        builder.finish()
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> BitRank<SUB_BLOCKS_PER_BLOCK> {
+    /// Returns a roaring bitmap containing the same integers as this set, or `None` if the set
+    /// contains integers which don't fit into a `u32`.
+    pub fn to_roaring(&self) -> Option<RoaringBitmap> {
+        if self
+            .iter()
+            .next_back()
+            .is_some_and(|max| max > u32::MAX as usize)
+        {
+            return None;
+        }
+        let bitmap = RoaringBitmap::from_sorted_iter(self.iter().map(|position| position as u32))
+            .expect("elements are iterated in increasing order");
+        Some(bitmap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::roaring::RoaringBitmap;
+
+    use crate::BitRank;
+
+    #[test]
+    fn test_roaring_round_trip() {
+        let bitmap: RoaringBitmap = [0, 1, 5, 70_000, 100_000].into_iter().collect();
+        let br = BitRank::from(&bitmap);
+        assert_eq!(br.iter().collect::<Vec<_>>(), [0, 1, 5, 70_000, 100_000]);
+        assert_eq!(br.to_roaring(), Some(bitmap));
+
+        assert_eq!(
+            BitRank::from(&RoaringBitmap::new()).to_roaring(),
+            Some(RoaringBitmap::new())
+        );
+    }
+}
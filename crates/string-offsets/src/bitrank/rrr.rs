@@ -0,0 +1,292 @@
+//! A rank data structure with [RRR](https://dl.acm.org/doi/10.1145/1290672.1290680)-compressed
+//! blocks.
+//!
+//! The bit-vector is split into blocks of 63 bits. Each block is represented by its class (the
+//! number of bits set) and its offset (the index of the block among all blocks of the same class
+//! in the [combinatorial number system](https://en.wikipedia.org/wiki/Combinatorial_number_system)).
+//! Blocks which are almost empty or almost full have few possible values and therefore short
+//! offsets, which is how the encoding approaches the zero-order entropy of the bit-vector.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::packed::{read_bits, write_bits};
+use super::{BitRank, BitRankBuilder, RankSelect, BITS_PER_SUB_BLOCK};
+
+/// The number of bits per RRR block.
+const BLOCK_BITS: usize = 63;
+/// The number of RRR blocks between two samples of the rank and offset position.
+const BLOCKS_PER_SAMPLE: usize = 32;
+
+/// `BINOMIAL[n][k]` is `n` choose `k`.
+static BINOMIAL: [[u64; BLOCK_BITS + 1]; BLOCK_BITS + 1] = {
+    let mut table = [[0; BLOCK_BITS + 1]; BLOCK_BITS + 1];
+    let mut n = 0;
+    while n <= BLOCK_BITS {
+        table[n][0] = 1;
+        let mut k = 1;
+        while k <= n {
+            table[n][k] = table[n - 1][k - 1] + if k < n { table[n - 1][k] } else { 0 };
+            k += 1;
+        }
+        n += 1;
+    }
+    table
+};
+
+/// The number of bits needed to store the offset of a block of the given class.
+fn offset_len(class: u8) -> u32 {
+    let combinations = BINOMIAL[BLOCK_BITS][class as usize];
+    u64::BITS - (combinations - 1).leading_zeros()
+}
+
+/// Returns the offset of `block` among all blocks with the same number of bits set.
+fn encode(block: u64) -> u64 {
+    let mut offset = 0;
+    let mut remaining = block.count_ones() as usize;
+    for i in (0..BLOCK_BITS).rev() {
+        if block & (1 << i) != 0 {
+            offset += BINOMIAL[i][remaining];
+            remaining -= 1;
+        }
+    }
+    offset
+}
+
+/// The inverse of [`encode`].
+fn decode(class: u8, mut offset: u64) -> u64 {
+    let mut block = 0;
+    let mut remaining = class as usize;
+    for i in (0..BLOCK_BITS).rev() {
+        if remaining == 0 {
+            break;
+        }
+        if offset >= BINOMIAL[i][remaining] {
+            block |= 1 << i;
+            offset -= BINOMIAL[i][remaining];
+            remaining -= 1;
+        }
+    }
+    block
+}
+
+/// An immutable set of unsigned integers with the same `rank` API as [`BitRank`], whose bit-vector
+/// is compressed with the RRR encoding.
+///
+/// Moderately dense sets need considerably less memory than with a [`BitRank`], at the cost of
+/// slower queries: [`RrrBitRank::rank`] decodes up to 32 block classes and one block per query.
+///
+/// # Examples
+///
+/// ```
+/// use string_offsets::BitRankBuilder;
+///
+/// let mut builder = BitRankBuilder::new();
+/// builder.push(17);
+/// builder.push(23);
+/// builder.push(102);
+/// let set = builder.finish_rrr();
+/// assert_eq!(set.rank(100), 2);
+/// assert_eq!(set.select(2), Some(102));
+/// ```
+#[derive(Clone)]
+pub struct RrrBitRank {
+    /// The number of bits set in each RRR block.
+    classes: Vec<u8>,
+    /// The offsets of all RRR blocks, packed back to back.
+    offsets: Vec<u64>,
+    /// For every `BLOCKS_PER_SAMPLE`-th block, the number of bits set in previous blocks.
+    sampled_ranks: Vec<u64>,
+    /// For every `BLOCKS_PER_SAMPLE`-th block, the position of its offset in `offsets`.
+    sampled_positions: Vec<u64>,
+    /// The total number of bits set.
+    max_rank: usize,
+}
+
+impl RrrBitRank {
+    /// Creates the RRR encoding of the bit-vector whose blocks of 63 bits are given by `blocks`.
+    fn from_blocks(blocks: &[u64]) -> Self {
+        let classes: Vec<u8> = blocks.iter().map(|b| b.count_ones() as u8).collect();
+        let total_len: usize = classes.iter().map(|&c| offset_len(c) as usize).sum();
+        let mut offsets = vec![0; total_len.div_ceil(64)];
+        let mut sampled_ranks = Vec::with_capacity(blocks.len().div_ceil(BLOCKS_PER_SAMPLE));
+        let mut sampled_positions = Vec::with_capacity(sampled_ranks.capacity());
+        let (mut rank, mut position) = (0, 0);
+        for (i, (&block, &class)) in blocks.iter().zip(&classes).enumerate() {
+            if i % BLOCKS_PER_SAMPLE == 0 {
+                sampled_ranks.push(rank as u64);
+                sampled_positions.push(position as u64);
+            }
+            write_bits(&mut offsets, position, offset_len(class), encode(block));
+            rank += class as usize;
+            position += offset_len(class) as usize;
+        }
+        Self {
+            classes,
+            offsets,
+            sampled_ranks,
+            sampled_positions,
+            max_rank: rank,
+        }
+    }
+
+    /// Returns the number of bits set before the given RRR block and the block's decoded bits.
+    fn rank_block(&self, block_idx: usize) -> (usize, u64) {
+        let sample = block_idx / BLOCKS_PER_SAMPLE;
+        let mut rank = self.sampled_ranks[sample] as usize;
+        let mut position = self.sampled_positions[sample] as usize;
+        for &class in &self.classes[sample * BLOCKS_PER_SAMPLE..block_idx] {
+            rank += class as usize;
+            position += offset_len(class) as usize;
+        }
+        let class = self.classes[block_idx];
+        let offset = read_bits(&self.offsets, position, offset_len(class));
+        (rank, decode(class, offset))
+    }
+
+    /// The rank at the specified index (exclusive), i.e. the number of elements less than `idx`.
+    pub fn rank(&self, idx: usize) -> usize {
+        let block_idx = idx / BLOCK_BITS;
+        if block_idx >= self.classes.len() {
+            return self.max_rank;
+        }
+        let (rank, block) = self.rank_block(block_idx);
+        rank + (block & ((1 << (idx % BLOCK_BITS)) - 1)).count_ones() as usize
+    }
+
+    /// Returns the number of elements in the set.
+    pub fn max_rank(&self) -> usize {
+        self.max_rank
+    }
+
+    /// The rank at the specified index (exclusive) and the largest element smaller than `idx`
+    /// **if** it falls into the same 128-bit chunk as `idx`, otherwise `None`.
+    ///
+    /// This returns exactly the same results as [`BitRank::rank_and_prev`].
+    pub fn rank_and_prev(&self, idx: usize) -> RankSelect {
+        let block_idx = idx / BLOCK_BITS;
+        let chunk_start = idx - idx % BITS_PER_SUB_BLOCK;
+        if block_idx >= self.classes.len() {
+            let select = self.max_rank.checked_sub(1).and_then(|r| self.select(r));
+            return RankSelect {
+                rank: self.max_rank,
+                prev_set_in_chunk: select.filter(|&prev| prev >= chunk_start),
+            };
+        }
+        let (block_rank, block) = self.rank_block(block_idx);
+        let below = block & ((1 << (idx % BLOCK_BITS)) - 1);
+        let rank = block_rank + below.count_ones() as usize;
+        let block_start = block_idx * BLOCK_BITS;
+        let select = if below != 0 {
+            Some(block_start + (u64::BITS - 1 - below.leading_zeros()) as usize)
+        } else if block_start > chunk_start && rank > 0 {
+            // The chunk also covers (parts of) previous RRR blocks.
+            self.select(rank - 1)
+        } else {
+            None
+        };
+        RankSelect {
+            rank,
+            prev_set_in_chunk: select.filter(|&prev| prev >= chunk_start),
+        }
+    }
+
+    /// The element with the given (zero-based) rank, or `None` if `rank` is greater than or equal
+    /// to the number of elements in the set.
+    pub fn select(&self, rank: usize) -> Option<usize> {
+        if rank >= self.max_rank {
+            return None;
+        }
+        // The first sample has rank 0, so the partition point is at least 1.
+        let sample = self.sampled_ranks.partition_point(|&r| r as usize <= rank) - 1;
+        let mut block_rank = self.sampled_ranks[sample] as usize;
+        let mut position = self.sampled_positions[sample] as usize;
+        let mut block_idx = sample * BLOCKS_PER_SAMPLE;
+        while block_rank + self.classes[block_idx] as usize <= rank {
+            block_rank += self.classes[block_idx] as usize;
+            position += offset_len(self.classes[block_idx]) as usize;
+            block_idx += 1;
+        }
+        let class = self.classes[block_idx];
+        let mut block = decode(class, read_bits(&self.offsets, position, offset_len(class)));
+        for _ in block_rank..rank {
+            // Clear the least significant set bit.
+            block &= block - 1;
+        }
+        Some(block_idx * BLOCK_BITS + block.trailing_zeros() as usize)
+    }
+
+    /// Returns true if `idx` is an element of the set.
+    pub fn contains(&self, idx: usize) -> bool {
+        let block_idx = idx / BLOCK_BITS;
+        block_idx < self.classes.len()
+            && self.rank_block(block_idx).1 & (1 << (idx % BLOCK_BITS)) != 0
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> From<&BitRank<SUB_BLOCKS_PER_BLOCK>> for RrrBitRank {
+    fn from(bit_rank: &BitRank<SUB_BLOCKS_PER_BLOCK>) -> Self {
+        let len = bit_rank.iter().next_back().map_or(0, |max| max + 1);
+        let mut blocks = vec![0u64; len.div_ceil(BLOCK_BITS)];
+        for position in bit_rank.iter() {
+            blocks[position / BLOCK_BITS] |= 1 << (position % BLOCK_BITS);
+        }
+        Self::from_blocks(&blocks)
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> BitRankBuilder<SUB_BLOCKS_PER_BLOCK> {
+    /// Finishes the set like [`BitRankBuilder::finish`], but returns it with RRR-compressed blocks.
+    pub fn finish_rrr(self) -> RrrBitRank {
+        RrrBitRank::from(&self.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::{decode, encode, RrrBitRank};
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::RankSelect;
+
+    #[test]
+    fn test_encode_decode() {
+        let mut rng = ChaCha8Rng::seed_from_u64(10);
+        for block in [0u64, 1, 1 << 62, (1 << 63) - 1, 0x5555_5555_5555_5555 >> 1] {
+            assert_eq!(decode(block.count_ones() as u8, encode(block)), block);
+        }
+        for _ in 0..10_000 {
+            let block = rng.random::<u64>() >> 1;
+            assert_eq!(decode(block.count_ones() as u8, encode(block)), block);
+        }
+    }
+
+    #[test]
+    fn test_rrr_matches_bitrank() {
+        let mut rng = ChaCha8Rng::seed_from_u64(11);
+        for density in [0.001, 0.1, 0.5, 0.97] {
+            let positions: Vec<usize> = (0..20_000).filter(|_| rng.random_bool(density)).collect();
+            let br = bitrank(positions.iter().copied());
+            let rrr = RrrBitRank::from(&br);
+            assert_eq!(rrr.max_rank(), br.max_rank());
+            for i in 0..20_100 {
+                assert_eq!(rrr.rank_and_prev(i), br.rank_and_prev(i), "position {i}");
+                assert_eq!(rrr.contains(i), br.contains(i), "position {i}");
+            }
+            for k in 0..=positions.len() {
+                assert_eq!(rrr.select(k), br.select(k));
+            }
+        }
+    }
+
+    #[test]
+    fn test_rrr_empty() {
+        let rrr = RrrBitRank::from(&bitrank([]));
+        assert_eq!(rrr.rank_and_prev(100), RankSelect::default());
+        assert_eq!(rrr.select(0), None);
+        assert!(!rrr.contains(0));
+    }
+}
@@ -0,0 +1,270 @@
+//! A set of unsigned integers optimized for select queries.
+
+use alloc::vec::Vec;
+
+use super::adaptive::select_in_words;
+use super::BitRank;
+
+/// The number of elements per group, for which the position of the first element is sampled.
+const GROUP_SIZE: usize = 1024;
+/// The number of elements per sub-sample within dense groups.
+const SUB_SAMPLE_RATE: usize = 32;
+/// Groups spanning at least this many positions store the positions of all their elements, since
+/// scanning the bits between sub-samples would be slow. Shorter groups store their sub-samples as
+/// `u16` offsets to the first element of the group.
+const SPARSE_SPAN: usize = 1 << 16;
+
+/// How the elements of a group are located.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Group {
+    /// The offsets of every `SUB_SAMPLE_RATE`-th element start at this index of `sub_samples`.
+    Dense(usize),
+    /// The positions of all elements start at this index of `positions`.
+    Sparse(usize),
+}
+
+/// An immutable set of unsigned integers which answers select queries in constant time, e.g. to
+/// map the `n`-th line start to its offset when rank queries are rare.
+///
+/// The elements are stored in a plain bit-vector, which is divided into groups of 1024 elements.
+/// The position of the first element of every group is sampled. Dense groups additionally store
+/// the offset of every 32nd element, so that [`BitSelect::select`] only has to scan the bits
+/// between two of them. Sparse groups store the positions of all their elements instead, since
+/// there could be many unset bits between two sub-samples. Compared to [`BitRank::select`], which binary searches the
+/// blocks, no search is needed.
+///
+/// [`BitSelect::rank`] is supported as well, but it has to binary search the samples and count
+/// the bits behind a sub-sample, so a [`BitRank`] is faster for workloads with many rank queries.
+///
+/// # Examples
+///
+/// ```
+/// use string_offsets::bitrank::BitSelect;
+///
+/// let line_starts: BitSelect = [0, 12, 13, 40].into_iter().collect();
+/// assert_eq!(line_starts.select(2), Some(13));
+/// assert_eq!(line_starts.rank(20), 3);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BitSelect {
+    /// The bits of the set, where bit `i` is bit `i % 64` of `words[i / 64]`.
+    words: Vec<u64>,
+    len: usize,
+    /// The position of the first element of every group.
+    samples: Vec<usize>,
+    groups: Vec<Group>,
+    sub_samples: Vec<u16>,
+    positions: Vec<usize>,
+}
+
+impl BitSelect {
+    /// The rank at the specified index (exclusive), i.e. the number of elements less than `idx`.
+    pub fn rank(&self, idx: usize) -> usize {
+        // The number of groups whose first element is smaller than `idx`.
+        let Some(group) = self.samples.partition_point(|&s| s < idx).checked_sub(1) else {
+            return 0;
+        };
+        let start = self.samples[group];
+        let group_rank = group * GROUP_SIZE;
+        let group_len = (self.len - group_rank).min(GROUP_SIZE);
+        match self.groups[group] {
+            Group::Dense(first) => {
+                let sub_samples =
+                    &self.sub_samples[first..first + group_len.div_ceil(SUB_SAMPLE_RATE)];
+                // The first offset is zero and `idx` is larger than `start`, so this is at least 1.
+                let sub_sample = sub_samples.partition_point(|&o| start + (o as usize) < idx) - 1;
+                let from = start + sub_samples[sub_sample] as usize;
+                group_rank + sub_sample * SUB_SAMPLE_RATE + self.count_ones(from, idx)
+            }
+            Group::Sparse(first) => {
+                group_rank + self.positions[first..first + group_len].partition_point(|&p| p < idx)
+            }
+        }
+    }
+
+    /// Returns the number of elements in the set.
+    pub fn max_rank(&self) -> usize {
+        self.len
+    }
+
+    /// The element with the given (zero-based) rank, or `None` if `rank` is greater than or equal
+    /// to the number of elements in the set.
+    pub fn select(&self, rank: usize) -> Option<usize> {
+        if rank >= self.len {
+            return None;
+        }
+        let group = rank / GROUP_SIZE;
+        let local_rank = rank % GROUP_SIZE;
+        match self.groups[group] {
+            Group::Dense(first) => {
+                let from = self.samples[group]
+                    + self.sub_samples[first + local_rank / SUB_SAMPLE_RATE] as usize;
+                // Scan the bits from the sub-sample, ignoring the ones in front of it.
+                let mut skip = local_rank % SUB_SAMPLE_RATE;
+                let mut word = from / 64;
+                let mut bits = self.words[word] & (u64::MAX << (from % 64));
+                loop {
+                    let count = bits.count_ones() as usize;
+                    if skip < count {
+                        return Some(word * 64 + select_in_words(&[bits], skip, true));
+                    }
+                    skip -= count;
+                    word += 1;
+                    bits = self.words[word];
+                }
+            }
+            Group::Sparse(first) => Some(self.positions[first + local_rank]),
+        }
+    }
+
+    /// Returns true if `idx` is an element of the set.
+    pub fn contains(&self, idx: usize) -> bool {
+        self.words
+            .get(idx / 64)
+            .is_some_and(|word| word >> (idx % 64) & 1 == 1)
+    }
+
+    /// Returns an iterator over the elements of the set in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len).map(|rank| self.select(rank).expect("rank is in bounds"))
+    }
+
+    /// The number of set bits in `from..to`.
+    fn count_ones(&self, from: usize, to: usize) -> usize {
+        let to = to.min(self.words.len() * 64);
+        if from >= to {
+            return 0;
+        }
+        let (first, last) = (from / 64, (to - 1) / 64);
+        let mut count = self.words[first..=last]
+            .iter()
+            .map(|w| w.count_ones() as usize)
+            .sum::<usize>();
+        count -= (self.words[first] & !(u64::MAX << (from % 64))).count_ones() as usize;
+        if !to.is_multiple_of(64) {
+            count -= (self.words[last] & (u64::MAX << (to % 64))).count_ones() as usize;
+        }
+        count
+    }
+
+    /// Adds the sample and the index for a full or final group of positions.
+    fn push_group(&mut self, group: &[usize]) {
+        let start = group[0];
+        self.samples.push(start);
+        if group[group.len() - 1] - start < SPARSE_SPAN {
+            self.groups.push(Group::Dense(self.sub_samples.len()));
+            self.sub_samples.extend(
+                group
+                    .iter()
+                    .step_by(SUB_SAMPLE_RATE)
+                    .map(|&p| (p - start) as u16),
+            );
+        } else {
+            self.groups.push(Group::Sparse(self.positions.len()));
+            self.positions.extend_from_slice(group);
+        }
+    }
+}
+
+/// Collects strictly increasing positions into a `BitSelect`.
+///
+/// This panics if the positions are not strictly increasing.
+impl FromIterator<usize> for BitSelect {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut set = Self {
+            words: Vec::new(),
+            len: 0,
+            samples: Vec::new(),
+            groups: Vec::new(),
+            sub_samples: Vec::new(),
+            positions: Vec::new(),
+        };
+        let mut group = Vec::with_capacity(GROUP_SIZE);
+        let mut last = None;
+        for position in iter {
+            assert!(last < Some(position), "positions must be increasing!");
+            last = Some(position);
+            if set.words.len() <= position / 64 {
+                set.words.resize(position / 64 + 1, 0);
+            }
+            set.words[position / 64] |= 1 << (position % 64);
+            set.len += 1;
+            group.push(position);
+            if group.len() == GROUP_SIZE {
+                set.push_group(&group);
+                group.clear();
+            }
+        }
+        if !group.is_empty() {
+            set.push_group(&group);
+        }
+        set
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> From<&BitRank<SUB_BLOCKS_PER_BLOCK>> for BitSelect {
+    fn from(bit_rank: &BitRank<SUB_BLOCKS_PER_BLOCK>) -> Self {
+        bit_rank.iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::{BitSelect, GROUP_SIZE, SPARSE_SPAN};
+    use crate::bitrank::tests::bitrank;
+
+    #[test]
+    fn test_bit_select_matches_bitrank() {
+        let mut rng = ChaCha8Rng::seed_from_u64(38);
+        // Alternate between dense and sparse regions, so that both kinds of groups are used.
+        let positions: Vec<usize> = (0..20 * SPARSE_SPAN)
+            .filter(|&i| {
+                rng.random_bool(if (i / SPARSE_SPAN).is_multiple_of(2) {
+                    0.3
+                } else {
+                    0.002
+                })
+            })
+            .collect();
+        let br = bitrank(positions.iter().copied());
+        let select = BitSelect::from(&br);
+        assert_eq!(select.max_rank(), positions.len());
+        for (rank, &position) in positions.iter().enumerate() {
+            assert_eq!(select.select(rank), Some(position), "select({rank})");
+        }
+        assert_eq!(select.select(positions.len()), None);
+        for i in (0..20 * SPARSE_SPAN + 100)
+            .step_by(13)
+            .chain(positions.iter().copied())
+        {
+            assert_eq!(select.rank(i), br.rank(i), "rank({i})");
+            assert_eq!(select.contains(i), br.contains(i), "contains({i})");
+        }
+    }
+
+    #[test]
+    fn test_bit_select_edge_cases() {
+        let empty: BitSelect = std::iter::empty().collect();
+        assert_eq!(empty.rank(100), 0);
+        assert_eq!(empty.select(0), None);
+        assert!(!empty.contains(0));
+
+        // A full group followed by a single far away element.
+        let positions: Vec<usize> = (0..GROUP_SIZE).chain([1 << 20]).collect();
+        let select: BitSelect = positions.iter().copied().collect();
+        assert_eq!(select.select(GROUP_SIZE), Some(1 << 20));
+        assert_eq!(select.rank(1 << 20), GROUP_SIZE);
+        assert_eq!(select.rank(usize::MAX), GROUP_SIZE + 1);
+        assert_eq!(select.iter().collect::<Vec<_>>(), positions);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_bit_select_decreasing() {
+        let _: BitSelect = [5, 3].into_iter().collect();
+    }
+}
@@ -0,0 +1,125 @@
+//! A sampled select index, which narrows down the blocks to search in [`BitRank::select`].
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use super::{BitRank, BitRankBuilder};
+
+/// The block containing every `rate`-th element of a [`BitRank`].
+#[derive(Clone)]
+pub(super) struct SelectSamples {
+    rate: usize,
+    /// `blocks[i]` is the index of the block containing the element with rank `i * rate`.
+    blocks: Vec<usize>,
+}
+
+impl SelectSamples {
+    fn new<const SUB_BLOCKS_PER_BLOCK: usize>(
+        bit_rank: &BitRank<SUB_BLOCKS_PER_BLOCK>,
+        rate: usize,
+    ) -> Self {
+        assert!(rate > 0, "the sampling rate must be positive");
+        let mut blocks = Vec::with_capacity(bit_rank.max_rank().div_ceil(rate));
+        let mut next_sample = 0;
+        for (i, block) in bit_rank.blocks.iter().enumerate() {
+            let end_rank = block.total_rank();
+            while next_sample < end_rank {
+                blocks.push(i);
+                next_sample += rate;
+            }
+        }
+        Self { rate, blocks }
+    }
+
+    /// The number of bytes allocated on the heap.
+    pub(super) fn heap_size_bytes(&self) -> usize {
+        self.blocks.capacity() * core::mem::size_of::<usize>()
+    }
+
+    /// The range of blocks which contains the element with the given rank.
+    ///
+    /// `rank` must be smaller than the number of elements in the set.
+    pub(super) fn block_range(&self, rank: usize) -> Range<usize> {
+        let sample = rank / self.rate;
+        let end = self
+            .blocks
+            .get(sample + 1)
+            .map_or(usize::MAX, |&block| block + 1);
+        self.blocks[sample]..end
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> BitRank<SUB_BLOCKS_PER_BLOCK> {
+    /// Adds a select index which stores the block of every `rate`-th element.
+    ///
+    /// With the index, [`BitRank::select`] only searches the blocks between two samples instead
+    /// of all blocks. For sets whose elements aren't extremely unevenly distributed, this makes
+    /// `select` run in constant time. The index takes one `usize` per `rate` elements.
+    ///
+    /// The index is dropped by operations that modify the set, like [`BitRank::append`].
+    pub fn with_select_samples(mut self, rate: usize) -> Self {
+        self.select_samples = Some(SelectSamples::new(&self, rate));
+        self
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> BitRankBuilder<SUB_BLOCKS_PER_BLOCK> {
+    /// Finishes the `BitRank` together with a select index sampling every `rate`-th element. See
+    /// [`BitRank::with_select_samples`].
+    pub fn finish_with_select_samples(self, rate: usize) -> BitRank<SUB_BLOCKS_PER_BLOCK> {
+        self.finish().with_select_samples(rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::{BitRankBuilder, BITS_PER_BLOCK};
+
+    #[test]
+    fn test_select_samples() {
+        let mut rng = ChaCha8Rng::seed_from_u64(24);
+        let positions: Vec<usize> = (0..20 * BITS_PER_BLOCK)
+            .filter(|&i| {
+                rng.random_bool(if (i / BITS_PER_BLOCK).is_multiple_of(3) {
+                    0.01
+                } else {
+                    0.0001
+                })
+            })
+            .collect();
+        let br = bitrank(positions.iter().copied());
+        for rate in [1, 7, 100, 1_000_000] {
+            let sampled = br.clone().with_select_samples(rate);
+            for rank in 0..positions.len() + 2 {
+                assert_eq!(sampled.select(rank), br.select(rank), "select({rank})");
+            }
+        }
+    }
+
+    #[test]
+    fn test_finish_with_select_samples() {
+        let mut builder = BitRankBuilder::new();
+        builder.push(3);
+        builder.push(5 * BITS_PER_BLOCK);
+        builder.push(5 * BITS_PER_BLOCK + 1);
+        let br = builder.finish_with_select_samples(2);
+        assert_eq!(br.select(0), Some(3));
+        assert_eq!(br.select(1), Some(5 * BITS_PER_BLOCK));
+        assert_eq!(br.select(2), Some(5 * BITS_PER_BLOCK + 1));
+        assert_eq!(br.select(3), None);
+        assert_eq!(bitrank([]).with_select_samples(4).select(0), None);
+    }
+
+    #[test]
+    fn test_append_drops_select_samples() {
+        let mut br = bitrank([1, 2, 3]).with_select_samples(1);
+        br.append(&bitrank([0, 1]), BITS_PER_BLOCK);
+        assert_eq!(br.select(3), Some(BITS_PER_BLOCK));
+        assert_eq!(br.select(4), Some(BITS_PER_BLOCK + 1));
+    }
+}
@@ -0,0 +1,104 @@
+//! [`serde`](::serde) support for [`BitRank`] and [`GapBitRank`].
+//!
+//! A `BitRank` is serialized as a byte string in the format of [`BitRank::to_bytes`]. A
+//! `GapBitRank` is serialized like the `BitRank` returned by [`GapBitRank::to_bit_rank`].
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+
+use ::serde::de::{self, SeqAccess, Visitor};
+use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{BitRank, GapBitRank};
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> Serialize for BitRank<SUB_BLOCKS_PER_BLOCK> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.to_bytes())
+    }
+}
+
+impl<'de, const SUB_BLOCKS_PER_BLOCK: usize> Deserialize<'de> for BitRank<SUB_BLOCKS_PER_BLOCK> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_bytes(BytesVisitor(PhantomData))
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> Serialize for GapBitRank<SUB_BLOCKS_PER_BLOCK> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_bit_rank().serialize(serializer)
+    }
+}
+
+impl<'de, const SUB_BLOCKS_PER_BLOCK: usize> Deserialize<'de> for GapBitRank<SUB_BLOCKS_PER_BLOCK> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        BitRank::deserialize(deserializer).map(Self::from)
+    }
+}
+
+/// Deserializes a `BitRank` from a byte string, or from a sequence of bytes for formats without
+/// native byte strings.
+struct BytesVisitor<const SUB_BLOCKS_PER_BLOCK: usize>(PhantomData<BitRank<SUB_BLOCKS_PER_BLOCK>>);
+
+impl<'de, const SUB_BLOCKS_PER_BLOCK: usize> Visitor<'de> for BytesVisitor<SUB_BLOCKS_PER_BLOCK> {
+    type Value = BitRank<SUB_BLOCKS_PER_BLOCK>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a serialized BitRank")
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+        BitRank::from_bytes(bytes).map_err(E::custom)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        // Like serde, don't trust the size hint of the input with more than a small allocation.
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or_default().min(4096));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        self.visit_bytes(&bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::{BitRank, GapBitRank, BITS_PER_BLOCK};
+
+    #[test]
+    fn test_serde_round_trip() {
+        let br = bitrank([1, 5, 300, BITS_PER_BLOCK + 7]);
+        let json = serde_json::to_string(&br).unwrap();
+        let decoded: BitRank = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            decoded.iter().collect::<Vec<_>>(),
+            br.iter().collect::<Vec<_>>()
+        );
+        assert!(serde_json::from_str::<BitRank>("[1, 2, 3]").is_err());
+    }
+
+    #[test]
+    fn test_serde_gap_bit_rank() {
+        let mut gap = GapBitRank::from(bitrank([1, 5, 300, BITS_PER_BLOCK + 7]));
+        gap.splice(2..4, [0, 1], 2 * BITS_PER_BLOCK);
+        let expected = bitrank([
+            1,
+            2,
+            3,
+            2 * BITS_PER_BLOCK + 3,
+            2 * BITS_PER_BLOCK + 298,
+            3 * BITS_PER_BLOCK + 5,
+        ]);
+        // The gap isn't serialized.
+        let json = serde_json::to_string(&gap).unwrap();
+        assert_eq!(json, serde_json::to_string(&expected).unwrap());
+        let decoded: GapBitRank = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            decoded
+                .iter_range(0..4 * BITS_PER_BLOCK)
+                .collect::<Vec<_>>(),
+            expected.iter().collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,458 @@
+//! The serialized format of a [`BitRank`] and a builder which writes it incrementally.
+//!
+//! A `BitRank` is serialized with [`BitRank::to_bytes`] and deserialized with
+//! [`BitRank::from_bytes`]. The serialized data can also be queried directly with a
+//! [`super::BitRankRef`].
+//!
+//! All integers are stored in little-endian byte order independent of the platform, so that data
+//! written on one machine can be read on any other. The format starts with a header of
+//! [`HEADER_LEN`] bytes:
+//!
+//! | offset | size | content                                  |
+//! |--------|------|------------------------------------------|
+//! | 0      | 7    | the magic bytes `BITRANK`                |
+//! | 7      | 1    | the format version, currently 1          |
+//! | 8      | 8    | the number of sub-blocks per block (`S`) |
+//!
+//! The header is followed by the blocks, each taking `8 + 18 * S` bytes:
+//!
+//! | offset | size     | content                                                     |
+//! |--------|----------|-------------------------------------------------------------|
+//! | 0      | 8        | the rank of the first bit of the block as `u64`             |
+//! | 8      | `2 * S`  | the rank of every sub-block relative to the block as `u16`  |
+//! | 8+2*S  | `16 * S` | the bits of every sub-block as `u128`, position 0 in the msb |
+//!
+//! The number of blocks follows from the length of the data, so that the blocks can be written
+//! without knowing their number in advance.
+//!
+//! Data with a different format version is rejected with [`FormatError::UnsupportedVersion`]
+//! instead of being misinterpreted.
+
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io::{self, Write};
+
+#[cfg(feature = "std")]
+use super::DEFAULT_SUB_BLOCKS_PER_BLOCK;
+use super::{BitRank, Block, SubblockBits};
+
+/// The magic bytes at the start of the serialized format.
+const MAGIC: &[u8; 7] = b"BITRANK";
+/// The version of the serialized format, which follows the magic bytes. It has to be increased
+/// whenever the layout changes.
+const FORMAT_VERSION: u8 = 1;
+/// The number of bytes in front of the first block.
+pub(super) const HEADER_LEN: usize = 16;
+
+/// An error which is returned when data is not in the serialized format.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FormatError {
+    /// The data doesn't start with the magic bytes.
+    InvalidMagic,
+    /// The data was serialized with a format version which isn't supported.
+    UnsupportedVersion(u8),
+    /// The data was serialized with a different number of sub-blocks per block.
+    BlockSizeMismatch {
+        /// The number of sub-blocks per block of the type the data is read as.
+        expected: usize,
+        /// The number of sub-blocks per block in the header.
+        found: usize,
+    },
+    /// The length of the data doesn't correspond to a whole number of blocks.
+    InvalidLength(usize),
+    /// The rank index stored for the block with the given index doesn't match its bits.
+    InvalidIndex(usize),
+    /// The block with the given index is the last one, but has no bits set. Archived sets must
+    /// not end with empty blocks, since a [`BitRank`] never does.
+    EmptyLastBlock(usize),
+    /// The positions don't fit into `usize` on this platform. Such data can still be queried with
+    /// a [`super::BitRankRef`], which uses `u64` positions.
+    TooLarge,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidMagic => write!(f, "missing BitRank magic bytes"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported BitRank format version {version}")
+            }
+            Self::BlockSizeMismatch { expected, found } => write!(
+                f,
+                "expected {expected} sub-blocks per block, but found {found}"
+            ),
+            Self::InvalidLength(len) => write!(f, "invalid length of serialized BitRank: {len}"),
+            Self::InvalidIndex(block) => write!(f, "invalid rank index in block {block}"),
+            Self::EmptyLastBlock(block) => write!(f, "empty last block {block}"),
+            Self::TooLarge => write!(f, "BitRank positions exceed the pointer width"),
+        }
+    }
+}
+
+impl core::error::Error for FormatError {}
+
+/// Checks the header of the serialized data and returns the bytes of the blocks.
+pub(super) fn read_header(bytes: &[u8], sub_blocks_per_block: usize) -> Result<&[u8], FormatError> {
+    if bytes.len() < HEADER_LEN || &bytes[..7] != MAGIC {
+        return Err(FormatError::InvalidMagic);
+    }
+    if bytes[7] != FORMAT_VERSION {
+        return Err(FormatError::UnsupportedVersion(bytes[7]));
+    }
+    let found = u64::from_le_bytes(bytes[8..HEADER_LEN].try_into().expect("8 bytes"));
+    if found != sub_blocks_per_block as u64 {
+        return Err(FormatError::BlockSizeMismatch {
+            expected: sub_blocks_per_block,
+            found: found as usize,
+        });
+    }
+    let blocks = &bytes[HEADER_LEN..];
+    if !blocks.len().is_multiple_of(block_len(sub_blocks_per_block)) {
+        return Err(FormatError::InvalidLength(bytes.len()));
+    }
+    Ok(blocks)
+}
+
+/// The number of bytes of a serialized block with `sub_blocks_per_block` sub-blocks.
+pub(super) const fn block_len(sub_blocks_per_block: usize) -> usize {
+    8 + 18 * sub_blocks_per_block
+}
+
+/// The header of the serialized format.
+pub(super) fn header(sub_blocks_per_block: usize) -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    header[..7].copy_from_slice(MAGIC);
+    header[7] = FORMAT_VERSION;
+    header[8..].copy_from_slice(&(sub_blocks_per_block as u64).to_le_bytes());
+    header
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> Block<SUB_BLOCKS_PER_BLOCK> {
+    /// Appends the block, whose sub-block ranks must be up to date, in the serialized format.
+    pub(super) fn encode_into(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.rank.to_le_bytes());
+        for sub_block in 0..SUB_BLOCKS_PER_BLOCK {
+            bytes.extend_from_slice(&(self.sub_block_rank(sub_block) as u16).to_le_bytes());
+        }
+        for chunk in &self.bits {
+            bytes.extend_from_slice(&chunk.to_le_bytes());
+        }
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> Block<SUB_BLOCKS_PER_BLOCK> {
+    /// Decodes a block in the serialized format and returns it together with the number of its
+    /// elements, or `None` if the stored sub-block ranks don't match the bits.
+    pub(super) fn decode(bytes: &[u8]) -> Option<(Self, u64)> {
+        let bits_offset = 8 + 2 * SUB_BLOCKS_PER_BLOCK;
+        let mut block = Block::ZERO;
+        block.rank = u64::from_le_bytes(bytes[..8].try_into().expect("8 bytes"));
+        for (chunk, chunk_bytes) in block
+            .bits
+            .iter_mut()
+            .zip(bytes[bits_offset..].as_chunks::<16>().0)
+        {
+            *chunk = SubblockBits::from_le_bytes(*chunk_bytes);
+        }
+        let count = block.update_sub_blocks();
+        bytes[8..bits_offset]
+            .as_chunks::<2>()
+            .0
+            .iter()
+            .enumerate()
+            .all(|(j, &r)| u16::from_le_bytes(r) as usize == block.sub_block_rank(j))
+            .then_some((block, count))
+    }
+}
+
+/// Decodes the blocks returned by [`read_header`] and checks their rank index against the bits.
+pub(super) fn decode_blocks<const SUB_BLOCKS_PER_BLOCK: usize>(
+    data: &[u8],
+) -> impl Iterator<Item = Result<Block<SUB_BLOCKS_PER_BLOCK>, FormatError>> + '_ {
+    let mut rank = 0;
+    data.chunks_exact(block_len(SUB_BLOCKS_PER_BLOCK))
+        .enumerate()
+        .map(move |(i, bytes)| match Block::decode(bytes) {
+            Some((block, count)) if block.rank == rank => {
+                rank += count;
+                Ok(block)
+            }
+            _ => Err(FormatError::InvalidIndex(i)),
+        })
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> BitRank<SUB_BLOCKS_PER_BLOCK> {
+    /// Serializes the set into the format described in the [module documentation](self).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes =
+            Vec::with_capacity(HEADER_LEN + self.blocks.len() * block_len(SUB_BLOCKS_PER_BLOCK));
+        bytes.extend_from_slice(&header(SUB_BLOCKS_PER_BLOCK));
+        for block in &self.blocks {
+            block.encode_into(&mut bytes);
+        }
+        bytes
+    }
+
+    /// Deserializes a set which was serialized with [`BitRank::to_bytes`] or a
+    /// [`BitRankWriter`].
+    ///
+    /// Besides the header and the length, the stored rank index is checked against the bits, so
+    /// that a corrupted index results in an error rather than in inconsistent query results.
+    ///
+    /// On 32-bit targets, data with positions beyond `usize::MAX` is rejected with
+    /// [`FormatError::TooLarge`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FormatError> {
+        let data = read_header(bytes, SUB_BLOCKS_PER_BLOCK)?;
+        let mut blocks = Vec::with_capacity(data.len() / block_len(SUB_BLOCKS_PER_BLOCK));
+        for block in decode_blocks(data) {
+            blocks.push(block?);
+        }
+        while blocks.last().is_some_and(Block::is_empty) {
+            blocks.pop();
+        }
+        if blocks.len() as u128 * Block::<SUB_BLOCKS_PER_BLOCK>::BITS as u128
+            > usize::MAX as u128 + 1
+        {
+            return Err(FormatError::TooLarge);
+        }
+        Ok(Self {
+            blocks,
+            select_samples: None,
+        })
+    }
+}
+
+/// A builder which writes a [`BitRank`] in the serialized format to a [`Write`] sink while the
+/// positions are added.
+///
+/// This requires the `std` feature.
+///
+/// Only the current block is kept in memory, so this can create sets which are much larger than
+/// the available memory. The output is the same as the serialization of a [`BitRank`] built by a
+/// [`super::BitRankBuilder`] from the same positions.
+///
+/// # Examples
+///
+/// ```
+/// use string_offsets::bitrank::BitRankWriter;
+///
+/// let mut writer = BitRankWriter::new(Vec::new()).unwrap();
+/// writer.push(17).unwrap();
+/// writer.push(100_000).unwrap();
+/// let bytes = writer.finish().unwrap();
+/// assert!(bytes.starts_with(b"BITRANK"));
+/// ```
+///
+/// Writers for other block sizes are created with [`BitRankWriter::with_sink`], e.g.
+/// `BitRankWriter::<_, 32>::with_sink(file)`.
+#[cfg(feature = "std")]
+pub struct BitRankWriter<W: Write, const SUB_BLOCKS_PER_BLOCK: usize = DEFAULT_SUB_BLOCKS_PER_BLOCK>
+{
+    sink: W,
+    /// The block which positions are currently added to. It has not been written yet.
+    block: Block<SUB_BLOCKS_PER_BLOCK>,
+    /// The index of `block`, or `None` if no position has been added yet.
+    block_num: Option<u64>,
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> BitRankWriter<W> {
+    /// Returns a writer which writes to `sink`, after writing the header.
+    pub fn new(sink: W) -> io::Result<Self> {
+        Self::with_sink(sink)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: Write, const SUB_BLOCKS_PER_BLOCK: usize> BitRankWriter<W, SUB_BLOCKS_PER_BLOCK> {
+    /// Returns a writer which writes to `sink`, after writing the header.
+    pub fn with_sink(mut sink: W) -> io::Result<Self> {
+        sink.write_all(&header(SUB_BLOCKS_PER_BLOCK))?;
+        Ok(Self {
+            sink,
+            block: Block::ZERO,
+            block_num: None,
+        })
+    }
+
+    /// Adds a bit. Bits must be added in order of increasing `position`.
+    ///
+    /// Positions are `u64` independent of the pointer width, see [`super::BitRankRef`].
+    ///
+    /// When `position` falls into a later block than the previous one, the finished blocks are
+    /// written to the sink.
+    pub fn push(&mut self, position: u64) -> io::Result<()> {
+        let bits = Block::<SUB_BLOCKS_PER_BLOCK>::BITS as u64;
+        let block_num = position / bits;
+        match self.block_num {
+            Some(current) if current == block_num => {}
+            Some(current) => {
+                assert!(current < block_num, "positions must be increasing!");
+                let rank = self.write_block()?;
+                // Blocks without any bits set in between.
+                for _ in current + 1..block_num {
+                    self.block = Block::ZERO;
+                    self.block.rank = rank;
+                    self.write_block()?;
+                }
+                self.block = Block::ZERO;
+                self.block.rank = rank;
+            }
+            None => {
+                // Blocks without any bits set before the first position.
+                for _ in 0..block_num {
+                    self.write_block()?;
+                }
+            }
+        }
+        self.block_num = Some(block_num);
+        self.block.set((position % bits) as usize);
+        Ok(())
+    }
+
+    /// Writes the current block and returns the rank at its end.
+    fn write_block(&mut self) -> io::Result<u64> {
+        let rank = self.block.rank + self.block.update_sub_blocks();
+        let mut bytes = Vec::with_capacity(block_len(SUB_BLOCKS_PER_BLOCK));
+        self.block.encode_into(&mut bytes);
+        self.sink.write_all(&bytes)?;
+        Ok(rank)
+    }
+
+    /// Writes the last block and returns the sink.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.block_num.is_some() {
+            self.write_block()?;
+        }
+        self.sink.flush()?;
+        Ok(self.sink)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[cfg(feature = "std")]
+    use super::BitRankWriter;
+    use super::{block_len, FormatError, HEADER_LEN};
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::{BitRank, BITS_PER_BLOCK, DEFAULT_SUB_BLOCKS_PER_BLOCK};
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = ChaCha8Rng::seed_from_u64(28);
+        let positions: Vec<usize> = (0..5 * BITS_PER_BLOCK)
+            .filter(|_| rng.random_bool(0.03))
+            .collect();
+        let br = bitrank(positions.iter().copied());
+        let decoded: BitRank = BitRank::from_bytes(&br.to_bytes()).unwrap();
+        assert_eq!(decoded.iter().collect::<Vec<_>>(), positions);
+        assert_eq!(decoded.to_bytes(), br.to_bytes());
+
+        let small: BitRank<4> = positions.iter().copied().collect();
+        let decoded = BitRank::<4>::from_bytes(&small.to_bytes()).unwrap();
+        assert_eq!(decoded.iter().collect::<Vec<_>>(), positions);
+
+        let empty: BitRank = BitRank::from_bytes(&bitrank([]).to_bytes()).unwrap();
+        assert_eq!(empty.max_rank(), 0);
+    }
+
+    /// The layout must not change without increasing the format version.
+    #[test]
+    fn test_format_stable() {
+        let mut expected = b"BITRANK\x01".to_vec();
+        expected.extend([1, 0, 0, 0, 0, 0, 0, 0]);
+        // The block rank and the single sub-block rank.
+        expected.extend([0; 10]);
+        // Bits 0 and 127 are the most and least significant bit of the first chunk.
+        expected.push(1);
+        expected.extend([0; 14]);
+        expected.push(0x80);
+        let br: BitRank<1> = [0, 127].into_iter().collect();
+        assert_eq!(br.to_bytes(), expected);
+        let decoded = BitRank::<1>::from_bytes(&expected).unwrap();
+        assert_eq!(decoded.iter().collect::<Vec<_>>(), [0, 127]);
+    }
+
+    #[test]
+    fn test_from_bytes_unsupported_version() {
+        let mut bytes = bitrank([1, 2, 3]).to_bytes();
+        bytes[7] = 0;
+        assert_eq!(
+            BitRank::<128>::from_bytes(&bytes).err(),
+            Some(FormatError::UnsupportedVersion(0))
+        );
+    }
+
+    #[test]
+    fn test_from_bytes_corrupted() {
+        let bytes = bitrank([1, 2, 3, BITS_PER_BLOCK + 5]).to_bytes();
+        assert_eq!(
+            BitRank::<128>::from_bytes(&bytes[..10]).err(),
+            Some(FormatError::InvalidMagic)
+        );
+        assert_eq!(
+            BitRank::<128>::from_bytes(&bytes[..bytes.len() - 16]).err(),
+            Some(FormatError::InvalidLength(bytes.len() - 16))
+        );
+        assert!(matches!(
+            BitRank::<64>::from_bytes(&bytes),
+            Err(FormatError::BlockSizeMismatch { .. })
+        ));
+        // Flip a bit in the first block, which invalidates the rank of the second block.
+        let mut corrupted = bytes.clone();
+        corrupted[HEADER_LEN + block_len(DEFAULT_SUB_BLOCKS_PER_BLOCK) - 1] ^= 1;
+        assert_eq!(
+            BitRank::<128>::from_bytes(&corrupted).err(),
+            Some(FormatError::InvalidIndex(1))
+        );
+        // Change the rank of the first sub-block of the second block.
+        let mut corrupted = bytes;
+        corrupted[HEADER_LEN + block_len(DEFAULT_SUB_BLOCKS_PER_BLOCK) + 8] = 1;
+        assert_eq!(
+            BitRank::<128>::from_bytes(&corrupted).err(),
+            Some(FormatError::InvalidIndex(1))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_writer_matches_blocks() {
+        let mut rng = ChaCha8Rng::seed_from_u64(26);
+        let positions: Vec<usize> = (2 * BITS_PER_BLOCK..10 * BITS_PER_BLOCK)
+            .filter(|i| !(4 * BITS_PER_BLOCK..=7 * BITS_PER_BLOCK).contains(i))
+            .filter(|_| rng.random_bool(0.01))
+            .collect();
+        let mut writer = BitRankWriter::new(Vec::new()).unwrap();
+        for &position in &positions {
+            writer.push(position as u64).unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+        let expected = bitrank(positions.iter().copied());
+        assert_eq!(
+            bytes.len(),
+            HEADER_LEN + expected.blocks.len() * block_len(DEFAULT_SUB_BLOCKS_PER_BLOCK)
+        );
+        assert_eq!(bytes, expected.to_bytes());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_writer_empty() {
+        let bytes = BitRankWriter::new(Vec::new()).unwrap().finish().unwrap();
+        assert_eq!(bytes, bitrank([]).to_bytes());
+    }
+
+    #[test]
+    #[should_panic]
+    #[cfg(feature = "std")]
+    fn test_writer_decreasing() {
+        let mut writer = BitRankWriter::new(Vec::new()).unwrap();
+        writer.push(BITS_PER_BLOCK as u64).unwrap();
+        writer.push(0).unwrap();
+    }
+}
@@ -0,0 +1,183 @@
+//! SIMD implementations of the in-block scans, selected at runtime depending on the features of
+//! the CPU. On targets without a SIMD implementation (or CPUs lacking the required features),
+//! the scalar implementations are used.
+
+use super::SubblockBits;
+
+/// The number of bits set in `chunks`.
+pub(super) fn count_ones(chunks: &[SubblockBits]) -> usize {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // SAFETY: AVX2 is supported by the CPU.
+        return unsafe { x86::count_ones(chunks) };
+    }
+    #[cfg(target_arch = "aarch64")]
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        // SAFETY: NEON is supported by the CPU.
+        return unsafe { neon::count_ones(chunks) };
+    }
+    scalar::count_ones(chunks)
+}
+
+/// The number of entries of the increasing `ranks` which are smaller than or equal to `value`,
+/// i.e. the same as `ranks.partition_point(|&r| r <= value)`.
+#[cfg(not(feature = "compact"))]
+pub(super) fn count_le(ranks: &[u16], value: u16) -> usize {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // SAFETY: AVX2 is supported by the CPU.
+        return unsafe { x86::count_le(ranks, value) };
+    }
+    #[cfg(target_arch = "aarch64")]
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        // SAFETY: NEON is supported by the CPU.
+        return unsafe { neon::count_le(ranks, value) };
+    }
+    scalar::count_le(ranks, value)
+}
+
+mod scalar {
+    use super::SubblockBits;
+
+    pub(super) fn count_ones(chunks: &[SubblockBits]) -> usize {
+        chunks.iter().map(|c| c.count_ones() as usize).sum()
+    }
+
+    #[cfg(not(feature = "compact"))]
+    pub(super) fn count_le(ranks: &[u16], value: u16) -> usize {
+        ranks.partition_point(|&r| r <= value)
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    use super::SubblockBits;
+
+    /// Counts the bits of two chunks per 256-bit vector by looking up the bit counts of all
+    /// nibbles in parallel and summing the bytes with `vpsadbw`.
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn count_ones(chunks: &[SubblockBits]) -> usize {
+        let lookup = _mm256_setr_epi8(
+            0, 1, 1, 2, 1, 2, 2, 3, 1, 2, 2, 3, 2, 3, 3, 4, //
+            0, 1, 1, 2, 1, 2, 2, 3, 1, 2, 2, 3, 2, 3, 3, 4,
+        );
+        let low_nibbles = _mm256_set1_epi8(0x0f);
+        let mut sums = _mm256_setzero_si256();
+        let (pairs, rest) = chunks.as_chunks::<2>();
+        for pair in pairs {
+            let v = _mm256_loadu_si256(pair.as_ptr().cast());
+            let lo = _mm256_shuffle_epi8(lookup, _mm256_and_si256(v, low_nibbles));
+            let hi = _mm256_shuffle_epi8(
+                lookup,
+                _mm256_and_si256(_mm256_srli_epi16::<4>(v), low_nibbles),
+            );
+            let bytes = _mm256_add_epi8(lo, hi);
+            sums = _mm256_add_epi64(sums, _mm256_sad_epu8(bytes, _mm256_setzero_si256()));
+        }
+        let mut lanes = [0u64; 4];
+        _mm256_storeu_si256(lanes.as_mut_ptr().cast(), sums);
+        lanes.iter().sum::<u64>() as usize + super::scalar::count_ones(rest)
+    }
+
+    /// Compares 16 ranks per 256-bit vector and stops at the first vector containing a rank
+    /// larger than `value`.
+    #[cfg(not(feature = "compact"))]
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn count_le(ranks: &[u16], value: u16) -> usize {
+        // There is no unsigned 16-bit comparison, so flip the sign bits and compare signed.
+        let flip = _mm256_set1_epi16(i16::MIN);
+        let value_v = _mm256_xor_si256(_mm256_set1_epi16(value as i16), flip);
+        let (vectors, rest) = ranks.as_chunks::<16>();
+        let mut count = 0;
+        for vector in vectors {
+            let v = _mm256_xor_si256(_mm256_loadu_si256(vector.as_ptr().cast()), flip);
+            // Two mask bits per 16-bit lane which is larger than `value`.
+            let greater = _mm256_movemask_epi8(_mm256_cmpgt_epi16(v, value_v)) as u32;
+            if greater != 0 {
+                return count + 16 - greater.count_ones() as usize / 2;
+            }
+            count += 16;
+        }
+        count + super::scalar::count_le(rest, value)
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+
+    use super::SubblockBits;
+
+    /// Counts the bits of every byte with `cnt` and sums them up per chunk.
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn count_ones(chunks: &[SubblockBits]) -> usize {
+        chunks
+            .iter()
+            .map(|chunk| {
+                let v = vld1q_u8((chunk as *const SubblockBits).cast());
+                vaddlvq_u8(vcntq_u8(v)) as usize
+            })
+            .sum()
+    }
+
+    /// Compares 8 ranks per 128-bit vector and stops at the first vector containing a rank
+    /// larger than `value`.
+    #[cfg(not(feature = "compact"))]
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn count_le(ranks: &[u16], value: u16) -> usize {
+        let value_v = vdupq_n_u16(value);
+        let (vectors, rest) = ranks.as_chunks::<8>();
+        let mut count = 0;
+        for vector in vectors {
+            let le = vcleq_u16(vld1q_u16(vector.as_ptr()), value_v);
+            // Every matching lane is all ones, so shifting leaves a 1 per matching lane.
+            let matches = vaddvq_u16(vshrq_n_u16::<15>(le)) as usize;
+            count += matches;
+            if matches < 8 {
+                return count;
+            }
+        }
+        count + super::scalar::count_le(rest, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::{count_ones, scalar};
+
+    #[test]
+    fn test_count_ones_matches_scalar() {
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        for len in 0..40 {
+            let chunks: Vec<u128> = (0..len).map(|_| rng.random()).collect();
+            assert_eq!(count_ones(&chunks), scalar::count_ones(&chunks));
+        }
+        assert_eq!(count_ones(&[u128::MAX; 7]), 7 * 128);
+    }
+
+    #[test]
+    #[cfg(not(feature = "compact"))]
+    fn test_count_le_matches_scalar() {
+        use super::count_le;
+
+        let mut rng = ChaCha8Rng::seed_from_u64(6);
+        for len in 0..70 {
+            let mut ranks: Vec<u16> = (0..len).map(|_| rng.random()).collect();
+            ranks.sort_unstable();
+            for value in [0, 1, 32767, 32768, u16::MAX] {
+                assert_eq!(count_le(&ranks, value), scalar::count_le(&ranks, value));
+            }
+            for &rank in &ranks {
+                for value in [rank.saturating_sub(1), rank, rank.saturating_add(1)] {
+                    assert_eq!(count_le(&ranks, value), scalar::count_le(&ranks, value));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! Queries on the finished part of a [`BitRankBuilder`] while it is still being built.
+
+use super::{BitRankBuilder, Block, DEFAULT_SUB_BLOCKS_PER_BLOCK};
+
+/// A read-only view of the blocks of a [`BitRankBuilder`] which are already finished, created by
+/// [`BitRankBuilder::snapshot`].
+///
+/// The last block of a builder can still change, so the snapshot covers the positions in front of
+/// it, i.e. `0..universe_size()`. Queries within that range return the same results as on the
+/// finished [`super::BitRank`], which allows serving queries for the already ingested prefix of a
+/// stream. Taking a snapshot doesn't copy anything.
+///
+/// # Examples
+///
+/// ```
+/// use string_offsets::BitRankBuilder;
+///
+/// let mut builder = BitRankBuilder::new();
+/// builder.push(17);
+/// builder.push(100_000);
+/// let snapshot = builder.snapshot();
+/// assert_eq!(snapshot.rank(1000), Some(1));
+/// // The block containing 100_000 isn't finished yet.
+/// assert_eq!(snapshot.rank(100_001), None);
+/// ```
+#[derive(Clone, Copy)]
+pub struct BitRankSnapshot<'a, const SUB_BLOCKS_PER_BLOCK: usize = DEFAULT_SUB_BLOCKS_PER_BLOCK> {
+    blocks: &'a [Block<SUB_BLOCKS_PER_BLOCK>],
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> BitRankSnapshot<'_, SUB_BLOCKS_PER_BLOCK> {
+    /// The number of bits per block.
+    const BITS_PER_BLOCK: usize = Block::<SUB_BLOCKS_PER_BLOCK>::BITS;
+
+    /// The number of positions covered by the finished blocks.
+    pub fn universe_size(&self) -> usize {
+        self.blocks.len() * Self::BITS_PER_BLOCK
+    }
+
+    /// The number of elements in the finished blocks.
+    pub fn max_rank(&self) -> usize {
+        self.blocks.last().map_or(0, Block::total_rank)
+    }
+
+    /// The rank at the specified index (exclusive), or `None` if `idx` is past
+    /// [`BitRankSnapshot::universe_size`], where later positions could still be added.
+    pub fn rank(&self, idx: usize) -> Option<usize> {
+        match self.blocks.get(idx / Self::BITS_PER_BLOCK) {
+            Some(block) => Some(block.rank_and_prev(idx % Self::BITS_PER_BLOCK).rank),
+            None => (idx == self.universe_size()).then(|| self.max_rank()),
+        }
+    }
+
+    /// Returns true if `idx` is an element of the finished blocks.
+    pub fn contains(&self, idx: usize) -> bool {
+        self.blocks
+            .get(idx / Self::BITS_PER_BLOCK)
+            .is_some_and(|block| block.get(idx % Self::BITS_PER_BLOCK))
+    }
+
+    /// The element with the given (zero-based) rank, or `None` if it isn't in the finished
+    /// blocks.
+    pub fn select(&self, rank: usize) -> Option<usize> {
+        if rank >= self.max_rank() {
+            return None;
+        }
+        // The first block has rank 0, so the partition point is at least 1.
+        let block_num = self.blocks.partition_point(|b| b.rank as usize <= rank) - 1;
+        let block = &self.blocks[block_num];
+        Some(block_num * Self::BITS_PER_BLOCK + block.select(rank - block.rank as usize))
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> BitRankBuilder<SUB_BLOCKS_PER_BLOCK> {
+    /// Returns a view which answers queries for the blocks that are already finished, i.e. all
+    /// blocks in front of the one containing the largest position added so far.
+    pub fn snapshot(&self) -> BitRankSnapshot<'_, SUB_BLOCKS_PER_BLOCK> {
+        BitRankSnapshot {
+            blocks: &self.blocks[..self.blocks.len().saturating_sub(1)],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::BITS_PER_BLOCK;
+    use crate::BitRankBuilder;
+
+    #[test]
+    fn test_snapshot_matches_bitrank() {
+        let mut rng = ChaCha8Rng::seed_from_u64(40);
+        let positions: Vec<usize> = (0..6 * BITS_PER_BLOCK)
+            .filter(|i| !(2 * BITS_PER_BLOCK..3 * BITS_PER_BLOCK).contains(i))
+            .filter(|_| rng.random_bool(0.01))
+            .collect();
+        let br = bitrank(positions.iter().copied());
+        let mut builder = BitRankBuilder::new();
+        for (added, &position) in positions.iter().enumerate() {
+            builder.push(position);
+            let snapshot = builder.snapshot();
+            let universe = position / BITS_PER_BLOCK * BITS_PER_BLOCK;
+            assert_eq!(snapshot.universe_size(), universe);
+            assert_eq!(snapshot.max_rank(), br.rank(universe));
+            if added % 50 != 0 {
+                continue;
+            }
+            for i in (0..universe + 1).step_by(31).chain([universe]) {
+                assert_eq!(snapshot.rank(i), Some(br.rank(i)), "rank({i})");
+                assert_eq!(snapshot.contains(i), br.contains(i), "contains({i})");
+            }
+            assert_eq!(snapshot.rank(universe + 1), None);
+            for rank in 0..=snapshot.max_rank() {
+                let expected = br.select(rank).filter(|&p| p < universe);
+                assert_eq!(snapshot.select(rank), expected, "select({rank})");
+            }
+        }
+    }
+
+    #[test]
+    fn test_snapshot_empty() {
+        let builder = BitRankBuilder::new();
+        let snapshot = builder.snapshot();
+        assert_eq!(snapshot.universe_size(), 0);
+        assert_eq!(snapshot.rank(0), Some(0));
+        assert_eq!(snapshot.rank(1), None);
+        assert_eq!(snapshot.select(0), None);
+    }
+}
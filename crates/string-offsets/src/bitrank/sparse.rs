@@ -0,0 +1,164 @@
+//! A rank data structure for sparse sets.
+
+use alloc::vec::Vec;
+
+use super::elias_fano::EliasFano;
+use super::{BitRank, BitRankBuilder, RankSelect, BITS_PER_SUB_BLOCK};
+
+/// An immutable set of unsigned integers with the same `rank` API as [`BitRank`], optimized for
+/// sparse sets.
+///
+/// A [`BitRank`] requires memory proportional to its largest element. A `SparseBitRank` stores its
+/// elements with the Elias–Fano encoding instead, which requires about `2 + log2(max / n)` bits per
+/// element. In exchange, [`SparseBitRank::rank`] takes O(log n) instead of O(1) time.
+///
+/// # Examples
+///
+/// ```
+/// use string_offsets::bitrank::SparseBitRank;
+///
+/// let set: SparseBitRank = [17, 23, 1_000_000].into_iter().collect();
+/// assert_eq!(set.rank(100), 2);
+/// assert_eq!(set.select(2), Some(1_000_000));
+/// ```
+#[derive(Clone)]
+pub struct SparseBitRank {
+    values: EliasFano,
+}
+
+impl SparseBitRank {
+    /// The rank at the specified index (exclusive), i.e. the number of elements less than `idx`.
+    pub fn rank(&self, idx: usize) -> usize {
+        self.values.rank(idx as u64)
+    }
+
+    /// Returns the number of elements in the set.
+    pub fn max_rank(&self) -> usize {
+        self.values.len()
+    }
+
+    /// The rank at the specified index (exclusive) and the largest element smaller than `idx`
+    /// **if** it falls into the same 128-bit chunk as `idx`, otherwise `None`.
+    ///
+    /// This returns exactly the same results as [`BitRank::rank_and_prev`].
+    pub fn rank_and_prev(&self, idx: usize) -> RankSelect {
+        let rank = self.rank(idx);
+        let chunk_start = idx - idx % BITS_PER_SUB_BLOCK;
+        let select = rank
+            .checked_sub(1)
+            .and_then(|r| self.select(r))
+            .filter(|&prev| prev >= chunk_start);
+        RankSelect {
+            rank,
+            prev_set_in_chunk: select,
+        }
+    }
+
+    /// The element with the given (zero-based) rank, or `None` if `rank` is greater than or equal
+    /// to the number of elements in the set.
+    pub fn select(&self, rank: usize) -> Option<usize> {
+        self.values.access(rank).map(|value| value as usize)
+    }
+
+    /// Returns true if `idx` is an element of the set.
+    pub fn contains(&self, idx: usize) -> bool {
+        self.select(self.rank(idx)) == Some(idx)
+    }
+}
+
+/// Collects strictly increasing positions into a `SparseBitRank`.
+///
+/// This panics if the positions are not strictly increasing.
+impl FromIterator<usize> for SparseBitRank {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let values: Vec<u64> = iter.into_iter().map(|p| p as u64).collect();
+        let universe = values.last().map_or(0, |&max| max + 1);
+        Self {
+            values: EliasFano::new(&values, universe),
+        }
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> From<&BitRank<SUB_BLOCKS_PER_BLOCK>> for SparseBitRank {
+    fn from(bit_rank: &BitRank<SUB_BLOCKS_PER_BLOCK>) -> Self {
+        bit_rank.iter().collect()
+    }
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> BitRankBuilder<SUB_BLOCKS_PER_BLOCK> {
+    /// Finishes the set like [`BitRankBuilder::finish`], but returns it in the Elias–Fano encoding
+    /// of a [`SparseBitRank`].
+    pub fn finish_sparse(mut self) -> SparseBitRank {
+        self.finish_last_block();
+        // The blocks are dropped afterwards, so they aren't shrunk like in `finish`.
+        let bit_rank = BitRank {
+            blocks: self.blocks,
+            select_samples: None,
+        };
+        let mut values = Vec::with_capacity(self.expected_ones);
+        values.extend(bit_rank.iter().map(|p| p as u64));
+        let universe = values.last().map_or(0, |&max| max + 1);
+        SparseBitRank {
+            values: EliasFano::new(&values, universe),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::distr::Uniform;
+    use rand::prelude::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::SparseBitRank;
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::{RankSelect, BITS_PER_BLOCK};
+    use crate::BitRankBuilder;
+
+    #[test]
+    fn test_sparse_matches_bitrank() {
+        let mut rng = ChaCha8Rng::seed_from_u64(9);
+        let uniform = Uniform::new(0, 1_000_000).unwrap();
+        let mut positions: Vec<usize> = (0..1000).map(|_| uniform.sample(&mut rng)).collect();
+        positions.sort_unstable();
+        positions.dedup();
+        positions.extend([BITS_PER_BLOCK * 100, BITS_PER_BLOCK * 100 + 1]);
+        let br = bitrank(positions.iter().copied());
+        let sparse = SparseBitRank::from(&br);
+        assert_eq!(sparse.max_rank(), br.max_rank());
+        for i in (0..BITS_PER_BLOCK * 101)
+            .step_by(7)
+            .chain(positions.iter().copied())
+        {
+            assert_eq!(sparse.rank_and_prev(i), br.rank_and_prev(i), "position {i}");
+            assert_eq!(sparse.contains(i), br.contains(i), "position {i}");
+        }
+        for k in 0..=positions.len() {
+            assert_eq!(sparse.select(k), br.select(k));
+        }
+    }
+
+    #[test]
+    fn test_finish_sparse() {
+        let positions = [3, 17, BITS_PER_BLOCK * 3 + 1];
+        let mut builder = BitRankBuilder::with_capacity_and_density(BITS_PER_BLOCK * 4, 3);
+        for position in positions {
+            builder.push(position);
+        }
+        let sparse = builder.finish_sparse();
+        assert_eq!(sparse.max_rank(), 3);
+        for (rank, position) in positions.into_iter().enumerate() {
+            assert_eq!(sparse.select(rank), Some(position));
+            assert_eq!(sparse.rank(position), rank);
+        }
+        assert_eq!(BitRankBuilder::new().finish_sparse().rank(10), 0);
+    }
+
+    #[test]
+    fn test_sparse_empty() {
+        let sparse: SparseBitRank = std::iter::empty().collect();
+        assert_eq!(sparse.rank_and_prev(100), RankSelect::default());
+        assert_eq!(sparse.select(0), None);
+    }
+}
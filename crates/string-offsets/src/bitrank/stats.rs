@@ -0,0 +1,128 @@
+//! Statistics about the distribution of the elements of a [`BitRank`].
+
+use alloc::vec::Vec;
+
+use super::select_samples::SelectSamples;
+use super::{BitRank, Block, SubblockBits};
+
+/// Statistics about a [`BitRank`] which help to decide whether a different representation, like
+/// [`super::SparseBitRank`] or [`super::RleBitRank`], would be more compact.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BitRankStats {
+    /// The number of bits which are stored, see [`BitRank::universe_size`].
+    pub universe_size: usize,
+    /// The number of bits which are set, see [`BitRank::len`].
+    pub len: usize,
+    /// The fraction of set bits for every block.
+    pub block_densities: Vec<f64>,
+    /// A histogram of the lengths of the runs of consecutive set bits. Entry `k` counts the runs
+    /// whose length is in `2^k..2^(k+1)`.
+    pub one_runs: Vec<usize>,
+    /// A histogram of the lengths of the runs of unset bits in front of the elements, bucketed like
+    /// [`BitRankStats::one_runs`]. The unset bits after the largest element are not counted.
+    pub zero_runs: Vec<usize>,
+    /// The number of bytes taken by the bits of the blocks.
+    pub bits_bytes: usize,
+    /// The number of bytes taken by the rank index, i.e. the block and sub-block ranks and the
+    /// select samples.
+    pub index_bytes: usize,
+}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> BitRank<SUB_BLOCKS_PER_BLOCK> {
+    /// Computes statistics about the distribution of the elements and the size of the index.
+    ///
+    /// This iterates over all elements, so it takes O(n) time in the number of elements.
+    pub fn stats(&self) -> BitRankStats {
+        let block_densities = self
+            .blocks
+            .iter()
+            .map(|b| (b.total_rank() - b.rank as usize) as f64 / Self::BITS_PER_BLOCK as f64)
+            .collect();
+        let mut one_runs = Vec::new();
+        let mut zero_runs = Vec::new();
+        // The run of consecutive elements which is currently extended.
+        let mut run = 0..0;
+        for position in self.iter() {
+            if position == run.end {
+                run.end += 1;
+                continue;
+            }
+            if !run.is_empty() {
+                add_run(&mut one_runs, run.len());
+            }
+            add_run(&mut zero_runs, position - run.end);
+            run = position..position + 1;
+        }
+        if !run.is_empty() {
+            add_run(&mut one_runs, run.len());
+        }
+        let bits_bytes =
+            self.blocks.len() * core::mem::size_of::<[SubblockBits; SUB_BLOCKS_PER_BLOCK]>();
+        let index_bytes = self.blocks.len() * core::mem::size_of::<Block<SUB_BLOCKS_PER_BLOCK>>()
+            - bits_bytes
+            + self
+                .select_samples
+                .as_ref()
+                .map_or(0, SelectSamples::heap_size_bytes);
+        BitRankStats {
+            universe_size: self.universe_size(),
+            len: self.len(),
+            block_densities,
+            one_runs,
+            zero_runs,
+            bits_bytes,
+            index_bytes,
+        }
+    }
+}
+
+/// Counts a run of the given non-zero length in the histogram with power of two buckets.
+fn add_run(histogram: &mut Vec<usize>, len: usize) {
+    let bucket = len.ilog2() as usize;
+    if histogram.len() <= bucket {
+        histogram.resize(bucket + 1, 0);
+    }
+    histogram[bucket] += 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::{Block, BITS_PER_BLOCK};
+
+    #[test]
+    fn test_stats() {
+        // Runs of length 1, 3 and 8, preceded by gaps of length 2, 1 and BITS_PER_BLOCK.
+        let positions = [2]
+            .into_iter()
+            .chain(4..7)
+            .chain(BITS_PER_BLOCK + 7..BITS_PER_BLOCK + 15);
+        let stats = bitrank(positions).stats();
+        assert_eq!(stats.universe_size, 2 * BITS_PER_BLOCK);
+        assert_eq!(stats.len, 12);
+        assert_eq!(
+            stats.block_densities,
+            [4.0 / BITS_PER_BLOCK as f64, 8.0 / BITS_PER_BLOCK as f64]
+        );
+        assert_eq!(stats.one_runs, [1, 1, 0, 1]);
+        assert_eq!(stats.zero_runs[..2], [1, 1]);
+        assert_eq!(stats.zero_runs.iter().sum::<usize>(), 3);
+        assert_eq!(stats.zero_runs.len(), 15);
+        assert_eq!(stats.bits_bytes, 2 * BITS_PER_BLOCK / 8);
+        assert_eq!(
+            stats.bits_bytes + stats.index_bytes,
+            2 * core::mem::size_of::<Block>()
+        );
+    }
+
+    #[test]
+    fn test_stats_empty() {
+        let stats = bitrank([]).stats();
+        assert_eq!(stats.universe_size, 0);
+        assert_eq!(stats.len, 0);
+        assert!(stats.block_densities.is_empty());
+        assert!(stats.one_runs.is_empty());
+        assert!(stats.zero_runs.is_empty());
+        assert_eq!(stats.bits_bytes + stats.index_bytes, 0);
+    }
+}
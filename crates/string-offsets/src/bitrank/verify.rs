@@ -0,0 +1,167 @@
+//! Consistency check of the rank index of a [`BitRank`] against its bits.
+
+use core::fmt;
+
+use super::BitRank;
+
+/// An error which is returned by [`BitRank::verify`] when the rank index doesn't match the bits.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The rank stored for a block differs from the number of bits set in the blocks before it.
+    BlockRank {
+        /// The index of the block.
+        block: usize,
+        /// The number of bits set in the blocks before it.
+        expected: u64,
+        /// The stored rank.
+        found: u64,
+    },
+    /// The rank stored for a sub-block differs from the number of bits set in the sub-blocks
+    /// before it within the same block.
+    SubBlockRank {
+        /// The index of the block.
+        block: usize,
+        /// The index of the sub-block within the block.
+        sub_block: usize,
+        /// The number of bits set in the sub-blocks before it.
+        expected: usize,
+        /// The stored rank.
+        found: usize,
+    },
+    /// The last block has no bits set. Trailing empty blocks are dropped, and queries like
+    /// [`BitRank::is_empty`] and [`BitRank::max_position`] rely on that.
+    TrailingEmptyBlock(usize),
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BlockRank {
+                block,
+                expected,
+                found,
+            } => write!(
+                f,
+                "block {block} stores rank {found}, but {expected} bits are set before it"
+            ),
+            Self::SubBlockRank {
+                block,
+                sub_block,
+                expected,
+                found,
+            } => write!(
+                f,
+                "sub-block {sub_block} of block {block} stores rank {found}, but {expected} bits \
+                 are set before it in the block"
+            ),
+            Self::TrailingEmptyBlock(block) => {
+                write!(f, "the last block {block} has no bits set")
+            }
+        }
+    }
+}
+
+impl core::error::Error for VerifyError {}
+
+impl<const SUB_BLOCKS_PER_BLOCK: usize> BitRank<SUB_BLOCKS_PER_BLOCK> {
+    /// Re-derives the block and sub-block ranks from the bits and checks that they match the
+    /// stored ones, so that queries return consistent results.
+    ///
+    /// [`BitRank::from_bytes`] already checks the serialized index. This is meant for instances
+    /// which were obtained in other ways, e.g. by deserializing untrusted data with `rkyv`. It
+    /// takes O(n) time in the size of the universe.
+    pub fn verify(&self) -> Result<(), VerifyError> {
+        let mut rank = 0;
+        for (i, block) in self.blocks.iter().enumerate() {
+            if block.rank != rank {
+                return Err(VerifyError::BlockRank {
+                    block: i,
+                    expected: rank,
+                    found: block.rank,
+                });
+            }
+            // The compact representation doesn't store sub-block ranks.
+            #[cfg(not(feature = "compact"))]
+            {
+                let mut local_rank = 0;
+                for (j, chunk) in block.bits.iter().enumerate() {
+                    if block.sub_blocks[j] as usize != local_rank {
+                        return Err(VerifyError::SubBlockRank {
+                            block: i,
+                            sub_block: j,
+                            expected: local_rank,
+                            found: block.sub_blocks[j] as usize,
+                        });
+                    }
+                    local_rank += chunk.count_ones() as usize;
+                }
+            }
+            rank += block
+                .bits
+                .iter()
+                .map(|chunk| chunk.count_ones() as u64)
+                .sum::<u64>();
+        }
+        if let Some(last) = self.blocks.last() {
+            if last.bits.iter().all(|&chunk| chunk == 0) {
+                return Err(VerifyError::TrailingEmptyBlock(self.blocks.len() - 1));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VerifyError;
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::{BitRank, Block, BITS_PER_BLOCK};
+
+    #[test]
+    fn test_verify_valid() {
+        assert_eq!(bitrank([]).verify(), Ok(()));
+        let br = bitrank([0, 1, 300, BITS_PER_BLOCK * 3 + 7, BITS_PER_BLOCK * 4 - 1]);
+        assert_eq!(br.verify(), Ok(()));
+        let decoded: BitRank = BitRank::from_bytes(&br.to_bytes()).unwrap();
+        assert_eq!(decoded.verify(), Ok(()));
+    }
+
+    #[test]
+    fn test_verify_block_rank() {
+        let mut br = bitrank([0, 1, 300, BITS_PER_BLOCK * 3 + 7]);
+        br.blocks[2].rank += 1;
+        assert_eq!(
+            br.verify(),
+            Err(VerifyError::BlockRank {
+                block: 2,
+                expected: 3,
+                found: 4
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "compact"))]
+    fn test_verify_sub_block_rank() {
+        let mut br = bitrank([0, 1, 300, BITS_PER_BLOCK + 7]);
+        br.blocks[0].sub_blocks[5] = 1;
+        assert_eq!(
+            br.verify(),
+            Err(VerifyError::SubBlockRank {
+                block: 0,
+                sub_block: 5,
+                expected: 3,
+                found: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_verify_trailing_empty_block() {
+        let mut br = bitrank([0, 1, 300]);
+        let mut block = Block::ZERO;
+        block.rank = 3;
+        br.blocks.push(block);
+        assert_eq!(br.verify(), Err(VerifyError::TrailingEmptyBlock(1)));
+    }
+}
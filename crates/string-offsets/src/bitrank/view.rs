@@ -0,0 +1,237 @@
+//! Queries directly on serialized [`BitRank`]s.
+
+use super::serialize::{block_len, decode_blocks, read_header, FormatError};
+use super::{
+    partition_point, select_in_chunk, SubblockBits, BITS_PER_SUB_BLOCK,
+    DEFAULT_SUB_BLOCKS_PER_BLOCK,
+};
+
+/// A borrowed view of a [`super::BitRank`] in the serialized format, which answers queries
+/// without deserializing or allocating.
+///
+/// The data can be produced by a [`super::BitRankWriter`] and can for example come from a
+/// memory-mapped file. The values are decoded from little-endian bytes on every access, so the
+/// data doesn't need any particular alignment and can be read on any platform.
+///
+/// Positions and ranks are `u64` independent of the pointer width, so that a set with positions
+/// beyond 4Gi which was built on a 64-bit machine can also be queried on 32-bit targets like
+/// wasm32.
+///
+/// Creating a view only checks the header and the length of the data. The queries assume that
+/// the stored rank index matches the bits, as it does for data written by
+/// [`super::BitRank::to_bytes`] or a [`super::BitRankWriter`]. On corrupted data they return
+/// wrong results or panic, so data which isn't trusted should be checked with
+/// [`BitRankRef::validate`] first.
+///
+/// # Examples
+///
+/// ```
+/// use string_offsets::bitrank::BitRankRef;
+/// use string_offsets::BitRank;
+///
+/// let bytes = [17, 100_000].into_iter().collect::<BitRank>().to_bytes();
+///
+/// let set = BitRankRef::new(&bytes).unwrap();
+/// assert_eq!(set.rank(1000), 1);
+/// assert_eq!(set.select(1), Some(100_000));
+/// ```
+#[derive(Clone, Copy)]
+pub struct BitRankRef<'a, const SUB_BLOCKS_PER_BLOCK: usize = DEFAULT_SUB_BLOCKS_PER_BLOCK> {
+    /// The serialized blocks, without the header.
+    blocks: &'a [u8],
+}
+
+impl<'a> BitRankRef<'a> {
+    /// Returns a view of the serialized `bytes`, after checking the header and the length.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, FormatError> {
+        Self::with_bytes(bytes)
+    }
+}
+
+impl<'a, const SUB_BLOCKS_PER_BLOCK: usize> BitRankRef<'a, SUB_BLOCKS_PER_BLOCK> {
+    /// The number of bits per block.
+    const BITS_PER_BLOCK: u64 = (SUB_BLOCKS_PER_BLOCK * BITS_PER_SUB_BLOCK) as u64;
+    /// The number of bytes per block.
+    const BLOCK_LEN: usize = block_len(SUB_BLOCKS_PER_BLOCK);
+
+    /// Returns a view of the serialized `bytes` for any block size, after checking the header and
+    /// the length.
+    pub fn with_bytes(bytes: &'a [u8]) -> Result<Self, FormatError> {
+        Ok(Self {
+            blocks: read_header(bytes, SUB_BLOCKS_PER_BLOCK)?,
+        })
+    }
+
+    /// Checks that the stored rank index matches the bits, like [`super::BitRank::from_bytes`]
+    /// does. This takes time proportional to the size of the data.
+    pub fn validate(&self) -> Result<(), FormatError> {
+        decode_blocks::<SUB_BLOCKS_PER_BLOCK>(self.blocks).try_for_each(|block| block.map(drop))
+    }
+
+    /// The number of blocks.
+    fn num_blocks(&self) -> usize {
+        self.blocks.len() / Self::BLOCK_LEN
+    }
+
+    /// The `N` bytes at `offset` within the block with the given index.
+    fn read<const N: usize>(&self, block: usize, offset: usize) -> [u8; N] {
+        let start = block * Self::BLOCK_LEN + offset;
+        self.blocks[start..start + N]
+            .try_into()
+            .expect("slice has length N")
+    }
+
+    /// The rank of the first bit of the block.
+    fn block_rank(&self, block: usize) -> u64 {
+        u64::from_le_bytes(self.read(block, 0))
+    }
+
+    /// The rank of the first bit of the sub-block relative to the start of the block.
+    fn sub_block_rank(&self, block: usize, sub_block: usize) -> u64 {
+        u16::from_le_bytes(self.read(block, 8 + 2 * sub_block)) as u64
+    }
+
+    /// The index of the block containing `idx` and the position within the block, or `None` if
+    /// `idx` lies beyond the last block.
+    fn locate(&self, idx: u64) -> Option<(usize, usize)> {
+        let block = idx / Self::BITS_PER_BLOCK;
+        // The number of blocks fits into `usize`, since the blocks are in memory.
+        if block >= self.num_blocks() as u64 {
+            return None;
+        }
+        Some((block as usize, (idx % Self::BITS_PER_BLOCK) as usize))
+    }
+
+    /// The bits of the sub-block.
+    fn chunk(&self, block: usize, sub_block: usize) -> SubblockBits {
+        SubblockBits::from_le_bytes(self.read(block, 8 + 2 * SUB_BLOCKS_PER_BLOCK + 16 * sub_block))
+    }
+
+    /// The rank at the specified index (exclusive), see [`super::BitRank::rank`].
+    pub fn rank(&self, idx: u64) -> u64 {
+        let Some((block, local_idx)) = self.locate(idx) else {
+            return self.max_rank();
+        };
+        let sub_block = local_idx / BITS_PER_SUB_BLOCK;
+        let remainder = local_idx % BITS_PER_SUB_BLOCK;
+        let masked = if remainder == 0 {
+            0
+        } else {
+            self.chunk(block, sub_block) >> (BITS_PER_SUB_BLOCK - remainder)
+        };
+        self.block_rank(block) + self.sub_block_rank(block, sub_block) + masked.count_ones() as u64
+    }
+
+    /// Returns true if `idx` is an element of the set.
+    pub fn contains(&self, idx: u64) -> bool {
+        let Some((block, local_idx)) = self.locate(idx) else {
+            return false;
+        };
+        let chunk = self.chunk(block, local_idx / BITS_PER_SUB_BLOCK);
+        chunk & (1 << (BITS_PER_SUB_BLOCK - 1 - local_idx % BITS_PER_SUB_BLOCK)) != 0
+    }
+
+    /// Returns the number of elements in the set.
+    pub fn max_rank(&self) -> u64 {
+        match self.num_blocks() {
+            0 => 0,
+            n => {
+                let last = SUB_BLOCKS_PER_BLOCK - 1;
+                self.block_rank(n - 1)
+                    + self.sub_block_rank(n - 1, last)
+                    + self.chunk(n - 1, last).count_ones() as u64
+            }
+        }
+    }
+
+    /// The position of the set bit with the given (zero-based) rank, see
+    /// [`super::BitRank::select`].
+    pub fn select(&self, rank: u64) -> Option<u64> {
+        if rank >= self.max_rank() {
+            return None;
+        }
+        // The first block has rank 0, so the partition point is at least 1.
+        let block = partition_point(self.num_blocks(), |i| self.block_rank(i) <= rank) - 1;
+        let local_rank = rank - self.block_rank(block);
+        // The same holds for the first sub-block.
+        let sub_block = partition_point(SUB_BLOCKS_PER_BLOCK, |i| {
+            self.sub_block_rank(block, i) <= local_rank
+        }) - 1;
+        let remainder = local_rank - self.sub_block_rank(block, sub_block);
+        let local_idx = sub_block * BITS_PER_SUB_BLOCK
+            + select_in_chunk(self.chunk(block, sub_block), remainder as usize);
+        Some(block as u64 * Self::BITS_PER_BLOCK + local_idx as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::BitRankRef;
+    use crate::bitrank::serialize::{FormatError, HEADER_LEN};
+    use crate::bitrank::tests::bitrank;
+    use crate::bitrank::BITS_PER_BLOCK;
+
+    fn serialize(positions: &[usize]) -> Vec<u8> {
+        bitrank(positions.iter().copied()).to_bytes()
+    }
+
+    #[test]
+    fn test_ref_matches_bitrank() {
+        let mut rng = ChaCha8Rng::seed_from_u64(27);
+        let positions: Vec<usize> = (0..5 * BITS_PER_BLOCK)
+            .filter(|_| rng.random_bool(0.02))
+            .collect();
+        let expected = bitrank(positions.iter().copied());
+        // Shift the data by one byte to make sure that unaligned data is supported.
+        let mut bytes = vec![0];
+        bytes.extend(serialize(&positions));
+        let view = BitRankRef::new(&bytes[1..]).unwrap();
+        assert_eq!(view.max_rank(), expected.max_rank() as u64);
+        for i in (0..6 * BITS_PER_BLOCK).step_by(13) {
+            assert_eq!(view.rank(i as u64), expected.rank(i) as u64);
+            assert_eq!(view.contains(i as u64), expected.contains(i));
+        }
+        for rank in 0..positions.len() + 1 {
+            assert_eq!(
+                view.select(rank as u64),
+                expected.select(rank).map(|i| i as u64)
+            );
+        }
+    }
+
+    #[test]
+    fn test_ref_invalid() {
+        let bytes = serialize(&[1, 2, 3]);
+        assert_eq!(
+            BitRankRef::new(&bytes[1..]).err(),
+            Some(FormatError::InvalidMagic)
+        );
+        assert_eq!(
+            BitRankRef::new(&bytes[..bytes.len() - 1]).err(),
+            Some(FormatError::InvalidLength(bytes.len() - 1))
+        );
+        assert_eq!(
+            BitRankRef::<32>::with_bytes(&bytes).err(),
+            Some(FormatError::BlockSizeMismatch {
+                expected: 32,
+                found: 128
+            })
+        );
+        // A corrupted rank index is only detected by `validate`.
+        let mut corrupted = bytes.clone();
+        corrupted[HEADER_LEN] = 1;
+        let view = BitRankRef::new(&corrupted).unwrap();
+        assert_eq!(view.validate(), Err(FormatError::InvalidIndex(0)));
+        assert_eq!(BitRankRef::new(&bytes).unwrap().validate(), Ok(()));
+
+        let empty = serialize(&[]);
+        let view = BitRankRef::new(&empty).unwrap();
+        assert_eq!(view.validate(), Ok(()));
+        assert_eq!(view.rank(100), 0);
+        assert_eq!(view.select(0), None);
+    }
+}
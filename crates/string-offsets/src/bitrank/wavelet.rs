@@ -0,0 +1,211 @@
+//! A wavelet tree for rank and select queries of symbols in a sequence.
+
+use alloc::vec::Vec;
+
+use super::{BitRank, BitRankBuilder};
+
+/// An immutable sequence of symbols from a small alphabet which answers how often a symbol occurs
+/// in front of a position and where its `k`-th occurrence is.
+///
+/// The sequence is stored as a wavelet matrix, the level-wise layout of a balanced wavelet tree:
+/// there is one [`BitRank`] per bit of the largest symbol, which stores that bit of every symbol
+/// after the symbols were stably sorted by their higher bits. All queries take O(log σ) rank or
+/// select queries on these sets, where σ is the size of the alphabet, and the memory consumption
+/// is about `n log σ` bits for `n` symbols.
+///
+/// # Examples
+///
+/// ```
+/// use string_offsets::bitrank::WaveletTree;
+///
+/// let sequence = WaveletTree::from_bytes(b"abracadabra");
+/// assert_eq!(sequence.get(4), Some(u32::from(b'c')));
+/// assert_eq!(sequence.rank(u32::from(b'a'), 5), 2);
+/// assert_eq!(sequence.select(u32::from(b'a'), 2), Some(5));
+/// assert_eq!(sequence.count(u32::from(b'r')), 2);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WaveletTree {
+    len: usize,
+    /// The levels from the most to the least significant bit, each with the number of symbols
+    /// which have that bit unset.
+    levels: Vec<(BitRank, usize)>,
+}
+
+impl WaveletTree {
+    /// Creates a wavelet tree over the given sequence of symbols. The number of levels is the
+    /// number of bits of the largest symbol.
+    pub fn new(symbols: &[u32]) -> Self {
+        let bits = symbols
+            .iter()
+            .max()
+            .map_or(0, |&max| u32::BITS - max.leading_zeros());
+        let mut levels = Vec::with_capacity(bits as usize);
+        let mut current = symbols.to_vec();
+        for level in (0..bits).rev() {
+            let mut builder = BitRankBuilder::with_capacity(current.len());
+            let (mut zeros, mut ones) = (Vec::new(), Vec::new());
+            for (i, &symbol) in current.iter().enumerate() {
+                if symbol >> level & 1 == 1 {
+                    builder.push(i);
+                    ones.push(symbol);
+                } else {
+                    zeros.push(symbol);
+                }
+            }
+            levels.push((builder.finish(), zeros.len()));
+            zeros.extend(ones);
+            current = zeros;
+        }
+        Self {
+            len: symbols.len(),
+            levels,
+        }
+    }
+
+    /// Creates a wavelet tree over a sequence of bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Self::new(&bytes.iter().map(|&b| u32::from(b)).collect::<Vec<_>>())
+    }
+
+    /// Returns the number of symbols in the sequence.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the sequence has no symbols.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the symbol at index `idx`, or `None` if `idx` is out of bounds.
+    pub fn get(&self, idx: usize) -> Option<u32> {
+        if idx >= self.len {
+            return None;
+        }
+        let mut idx = idx;
+        let mut symbol = 0;
+        for (bits, zeros) in &self.levels {
+            let bit = bits.contains(idx);
+            symbol = symbol << 1 | u32::from(bit);
+            idx = if bit {
+                zeros + bits.rank(idx)
+            } else {
+                bits.rank0(idx)
+            };
+        }
+        Some(symbol)
+    }
+
+    /// Returns the number of occurrences of `symbol` in front of index `idx`. Indices past the end
+    /// are interpreted as the end of the sequence.
+    pub fn rank(&self, symbol: u32, idx: usize) -> usize {
+        match self.range(symbol, idx.min(self.len)) {
+            Some((start, end)) => end - start,
+            None => 0,
+        }
+    }
+
+    /// Returns the number of occurrences of `symbol` in the sequence.
+    pub fn count(&self, symbol: u32) -> usize {
+        self.rank(symbol, self.len)
+    }
+
+    /// Returns the index of the occurrence of `symbol` with the given (zero-based) rank, i.e. the
+    /// inverse of [`WaveletTree::rank`], or `None` if `symbol` occurs at most `rank` times.
+    pub fn select(&self, symbol: u32, rank: usize) -> Option<usize> {
+        let (start, end) = self.range(symbol, self.len)?;
+        if rank >= end - start {
+            return None;
+        }
+        let mut idx = start + rank;
+        for (level, (bits, zeros)) in self.levels.iter().enumerate().rev() {
+            idx = if self.bit(symbol, level) {
+                bits.select(idx - zeros)?
+            } else {
+                bits.select0(idx)?
+            };
+        }
+        Some(idx)
+    }
+
+    /// Returns whether `symbol` has the bit of the given level set.
+    fn bit(&self, symbol: u32, level: usize) -> bool {
+        symbol >> (self.levels.len() - 1 - level) & 1 == 1
+    }
+
+    /// Maps the occurrences of `symbol` in front of `end` to their range in the last level, or
+    /// returns `None` if `symbol` has more bits than there are levels.
+    fn range(&self, symbol: u32, end: usize) -> Option<(usize, usize)> {
+        if (symbol as u64) >> self.levels.len() != 0 {
+            return None;
+        }
+        let (mut start, mut end) = (0, end);
+        for (level, (bits, zeros)) in self.levels.iter().enumerate() {
+            (start, end) = if self.bit(symbol, level) {
+                (zeros + bits.rank(start), zeros + bits.rank(end))
+            } else {
+                (bits.rank0(start), bits.rank0(end))
+            };
+        }
+        Some((start, end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::WaveletTree;
+
+    #[test]
+    fn test_wavelet_tree_matches_naive() {
+        let mut rng = ChaCha8Rng::seed_from_u64(37);
+        for alphabet in [1, 2, 5, 64, 256] {
+            let symbols: Vec<u32> = (0..3000).map(|_| rng.random_range(0..alphabet)).collect();
+            let tree = WaveletTree::new(&symbols);
+            assert_eq!(tree.len(), symbols.len());
+            for (i, &symbol) in symbols.iter().enumerate() {
+                assert_eq!(tree.get(i), Some(symbol), "index {i}");
+            }
+            assert_eq!(tree.get(symbols.len()), None);
+            for symbol in 0..alphabet + 1 {
+                let occurrences: Vec<usize> = (0..symbols.len())
+                    .filter(|&i| symbols[i] == symbol)
+                    .collect();
+                for i in (0..=symbols.len() + 1).step_by(7) {
+                    let expected = occurrences.partition_point(|&o| o < i);
+                    assert_eq!(tree.rank(symbol, i), expected, "rank({symbol}, {i})");
+                }
+                for (rank, &occurrence) in occurrences.iter().enumerate() {
+                    assert_eq!(tree.select(symbol, rank), Some(occurrence));
+                }
+                assert_eq!(tree.select(symbol, occurrences.len()), None);
+                assert_eq!(tree.count(symbol), occurrences.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_wavelet_tree_edge_cases() {
+        let empty = WaveletTree::new(&[]);
+        assert!(empty.is_empty());
+        assert_eq!(empty.get(0), None);
+        assert_eq!(empty.rank(0, 10), 0);
+        assert_eq!(empty.select(0, 0), None);
+
+        // A sequence of zeros doesn't need any levels.
+        let zeros = WaveletTree::new(&[0, 0, 0]);
+        assert_eq!(zeros.get(2), Some(0));
+        assert_eq!(zeros.rank(0, 2), 2);
+        assert_eq!(zeros.select(0, 2), Some(2));
+        assert_eq!(zeros.rank(1, 3), 0);
+
+        let tree = WaveletTree::new(&[u32::MAX, 7, u32::MAX]);
+        assert_eq!(tree.get(0), Some(u32::MAX));
+        assert_eq!(tree.select(u32::MAX, 1), Some(2));
+        assert_eq!(tree.rank(7, 3), 1);
+    }
+}
@@ -0,0 +1,292 @@
+//! Conversions which return an error for invalid input instead of saturating.
+
+use core::fmt;
+use core::ops::Range;
+
+use crate::{Pos, PositionEncoding, StringOffsets};
+
+/// An error which is returned by the `try_` conversions of [`StringOffsets`] for input which the
+/// other conversions would clamp.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConversionError {
+    /// The offset is past the end of the string.
+    OutOfBounds {
+        /// The offset.
+        offset: usize,
+        /// The length of the string in the unit of the offset.
+        len: usize,
+    },
+    /// The UTF-8 offset is within a character.
+    NotCharBoundary(usize),
+    /// The UTF-16 offset is between the two code units of a surrogate pair.
+    SplitsSurrogatePair(usize),
+    /// The line number is past the last line.
+    LineOutOfBounds {
+        /// The line number.
+        line: usize,
+        /// The number of lines.
+        lines: usize,
+    },
+    /// The column is past the end of its line.
+    ColumnOutOfBounds {
+        /// The position.
+        pos: Pos,
+        /// The length of the line including the newline, in the unit of the column.
+        len: usize,
+    },
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfBounds { offset, len } => {
+                write!(
+                    f,
+                    "offset {offset} is past the end of the string of length {len}"
+                )
+            }
+            Self::NotCharBoundary(offset) => {
+                write!(f, "offset {offset} is not a character boundary")
+            }
+            Self::SplitsSurrogatePair(offset) => {
+                write!(f, "UTF-16 offset {offset} splits a surrogate pair")
+            }
+            Self::LineOutOfBounds { line, lines } => {
+                write!(f, "line {line} is past the last line of {lines} lines")
+            }
+            Self::ColumnOutOfBounds { pos, len } => write!(
+                f,
+                "column {} is past the end of line {} of length {len}",
+                pos.col, pos.line
+            ),
+        }
+    }
+}
+
+impl core::error::Error for ConversionError {}
+
+impl StringOffsets {
+    /// The length of the string in UTF-8 bytes.
+    fn utf8_len(&self) -> usize {
+        self.line_to_utf8_begin(self.lines())
+    }
+
+    /// Checks that `byte_number` is a character boundary within the string.
+    fn check_utf8(&self, byte_number: usize) -> Result<(), ConversionError> {
+        let len = self.utf8_len();
+        if byte_number > len {
+            return Err(ConversionError::OutOfBounds {
+                offset: byte_number,
+                len,
+            });
+        }
+        // Every character is marked by its last byte.
+        if byte_number > 0 && byte_number < len && !self.utf8_to_char.contains(byte_number - 1) {
+            return Err(ConversionError::NotCharBoundary(byte_number));
+        }
+        Ok(())
+    }
+
+    /// Like [`StringOffsets::utf8_to_char`], but fails for offsets past the end of the string or
+    /// within a character.
+    pub fn try_utf8_to_char(&self, byte_number: usize) -> Result<usize, ConversionError> {
+        self.check_utf8(byte_number)?;
+        Ok(self.utf8_to_char(byte_number))
+    }
+
+    /// Like [`StringOffsets::utf8_to_utf16`], but fails for offsets past the end of the string or
+    /// within a character.
+    pub fn try_utf8_to_utf16(&self, byte_number: usize) -> Result<usize, ConversionError> {
+        self.check_utf8(byte_number)?;
+        Ok(self.utf8_to_utf16(byte_number))
+    }
+
+    /// Like [`StringOffsets::utf8_to_line`], but fails for offsets past the end of the string or
+    /// within a character.
+    pub fn try_utf8_to_line(&self, byte_number: usize) -> Result<usize, ConversionError> {
+        self.check_utf8(byte_number)?;
+        Ok(self.utf8_to_line(byte_number))
+    }
+
+    /// Like [`StringOffsets::char_to_utf8`], but fails for offsets past the end of the string.
+    pub fn try_char_to_utf8(&self, char_number: usize) -> Result<usize, ConversionError> {
+        let len = self.utf8_to_char(self.utf8_len());
+        if char_number > len {
+            return Err(ConversionError::OutOfBounds {
+                offset: char_number,
+                len,
+            });
+        }
+        Ok(self.char_to_utf8(char_number))
+    }
+
+    /// Like [`StringOffsets::utf16_to_utf8`], but fails for offsets past the end of the string or
+    /// between the two code units of a surrogate pair.
+    pub fn try_utf16_to_utf8(&self, utf16_number: usize) -> Result<usize, ConversionError> {
+        let len = self.utf8_to_utf16(self.utf8_len());
+        if utf16_number > len {
+            return Err(ConversionError::OutOfBounds {
+                offset: utf16_number,
+                len,
+            });
+        }
+        let byte_number = self.utf16_to_utf8(utf16_number);
+        if self.utf8_to_utf16(byte_number) != utf16_number {
+            return Err(ConversionError::SplitsSurrogatePair(utf16_number));
+        }
+        Ok(byte_number)
+    }
+
+    /// Like [`StringOffsets::line_to_utf8s`], but fails for lines past the last line.
+    pub fn try_line_to_utf8s(&self, line_number: usize) -> Result<Range<usize>, ConversionError> {
+        if line_number >= self.lines() {
+            return Err(ConversionError::LineOutOfBounds {
+                line: line_number,
+                lines: self.lines(),
+            });
+        }
+        Ok(self.line_to_utf8s(line_number))
+    }
+
+    /// Like [`StringOffsets::offset_to_position`], but fails for offsets past the end of the
+    /// string or within a character.
+    pub fn try_offset_to_position(
+        &self,
+        byte_number: usize,
+        encoding: PositionEncoding,
+    ) -> Result<Pos, ConversionError> {
+        self.check_utf8(byte_number)?;
+        Ok(self.offset_to_position(byte_number, encoding))
+    }
+
+    /// Like [`StringOffsets::position_to_offset`], but fails for positions which don't refer to a
+    /// character boundary within the string, e.g. a malformed position sent by an LSP client.
+    ///
+    /// The end of the string is the position with column 0 on the line after the last line, as
+    /// returned by [`StringOffsets::offset_to_position`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_offsets::{ConversionError, Pos, PositionEncoding, StringOffsets};
+    ///
+    /// let offsets = StringOffsets::new("👋\n");
+    /// let pos = |line, col| Pos { line, col };
+    /// assert_eq!(offsets.try_position_to_offset(pos(0, 2), PositionEncoding::Utf16), Ok(4));
+    /// assert_eq!(
+    ///     offsets.try_position_to_offset(pos(0, 1), PositionEncoding::Utf16),
+    ///     Err(ConversionError::SplitsSurrogatePair(1)),
+    /// );
+    /// assert_eq!(
+    ///     offsets.try_position_to_offset(pos(0, 4), PositionEncoding::Utf16),
+    ///     Err(ConversionError::ColumnOutOfBounds { pos: pos(0, 4), len: 3 }),
+    /// );
+    /// ```
+    pub fn try_position_to_offset(
+        &self,
+        pos: Pos,
+        encoding: PositionEncoding,
+    ) -> Result<usize, ConversionError> {
+        if pos.line > self.lines() {
+            return Err(ConversionError::LineOutOfBounds {
+                line: pos.line,
+                lines: self.lines(),
+            });
+        }
+        let line = match encoding {
+            PositionEncoding::Utf8 => self.line_to_utf8s(pos.line),
+            PositionEncoding::Utf16 => {
+                self.line_to_utf16_begin(pos.line)..self.line_to_utf16_end(pos.line)
+            }
+            PositionEncoding::Utf32 => self.line_to_chars(pos.line),
+        };
+        if pos.col > line.len() {
+            return Err(ConversionError::ColumnOutOfBounds {
+                pos,
+                len: line.len(),
+            });
+        }
+        let offset = line.start + pos.col;
+        match encoding {
+            PositionEncoding::Utf8 => self.check_utf8(offset).map(|()| offset),
+            PositionEncoding::Utf16 => self.try_utf16_to_utf8(offset),
+            PositionEncoding::Utf32 => self.try_char_to_utf8(offset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ConversionError, Pos, PositionEncoding, StringOffsets};
+
+    #[test]
+    fn test_checked_conversions() {
+        // The wave is 4 UTF-8 bytes and 2 UTF-16 code units.
+        let content = "a👋\nb";
+        let offsets = StringOffsets::new(content);
+        for (byte_number, _) in content.char_indices().chain([(content.len(), ' ')]) {
+            let char_number = offsets.utf8_to_char(byte_number);
+            let utf16_number = offsets.utf8_to_utf16(byte_number);
+            assert_eq!(offsets.try_utf8_to_char(byte_number), Ok(char_number));
+            assert_eq!(offsets.try_utf8_to_utf16(byte_number), Ok(utf16_number));
+            assert_eq!(offsets.try_char_to_utf8(char_number), Ok(byte_number));
+            assert_eq!(offsets.try_utf16_to_utf8(utf16_number), Ok(byte_number));
+            for encoding in [
+                PositionEncoding::Utf8,
+                PositionEncoding::Utf16,
+                PositionEncoding::Utf32,
+            ] {
+                let pos = offsets
+                    .try_offset_to_position(byte_number, encoding)
+                    .unwrap();
+                assert_eq!(
+                    offsets.try_position_to_offset(pos, encoding),
+                    Ok(byte_number)
+                );
+            }
+        }
+
+        assert_eq!(
+            offsets.try_utf8_to_char(8),
+            Err(ConversionError::OutOfBounds { offset: 8, len: 7 })
+        );
+        assert_eq!(
+            offsets.try_utf8_to_line(2),
+            Err(ConversionError::NotCharBoundary(2))
+        );
+        assert_eq!(
+            offsets.try_char_to_utf8(5),
+            Err(ConversionError::OutOfBounds { offset: 5, len: 4 })
+        );
+        assert_eq!(
+            offsets.try_utf16_to_utf8(2),
+            Err(ConversionError::SplitsSurrogatePair(2))
+        );
+        assert_eq!(offsets.try_line_to_utf8s(1), Ok(6..7));
+        assert_eq!(
+            offsets.try_line_to_utf8s(2),
+            Err(ConversionError::LineOutOfBounds { line: 2, lines: 2 })
+        );
+
+        let pos = |line, col| Pos { line, col };
+        assert_eq!(
+            offsets.try_position_to_offset(pos(0, 3), PositionEncoding::Utf8),
+            Err(ConversionError::NotCharBoundary(3))
+        );
+        assert_eq!(
+            offsets.try_position_to_offset(pos(1, 2), PositionEncoding::Utf32),
+            Err(ConversionError::ColumnOutOfBounds {
+                pos: pos(1, 2),
+                len: 1
+            })
+        );
+        assert_eq!(
+            offsets.try_position_to_offset(pos(2, 0), PositionEncoding::Utf16),
+            Ok(7)
+        );
+        assert_eq!(
+            offsets.try_position_to_offset(pos(3, 0), PositionEncoding::Utf16),
+            Err(ConversionError::LineOutOfBounds { line: 3, lines: 2 })
+        );
+    }
+}
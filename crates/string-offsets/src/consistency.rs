@@ -0,0 +1,220 @@
+//! Checks that the parts of a deserialized or archived [`StringOffsets`] describe the same
+//! string.
+//!
+//! Every part is validated on its own when it is deserialized, but the conversions also rely on
+//! the bitranks agreeing with the line table, e.g. that the line bitrank has a bit for the end of
+//! every line and that no bitrank has a bit past the end of the string.
+
+use core::fmt;
+
+#[cfg(feature = "rkyv")]
+use crate::bitrank::ArchivedBitRank;
+use crate::bitrank::GapBitRank;
+#[cfg(feature = "serde")]
+use crate::line_table::LineTable;
+#[cfg(feature = "rkyv")]
+use crate::ArchivedStringOffsets;
+use crate::StringOffsets;
+
+/// The queries on a bitrank which are needed to check it.
+trait Positions {
+    fn max_rank(&self) -> usize;
+    fn select(&self, rank: usize) -> Option<usize>;
+}
+
+impl Positions for GapBitRank {
+    fn max_rank(&self) -> usize {
+        GapBitRank::max_rank(self)
+    }
+
+    fn select(&self, rank: usize) -> Option<usize> {
+        GapBitRank::select(self, rank)
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl Positions for ArchivedBitRank {
+    fn max_rank(&self) -> usize {
+        ArchivedBitRank::max_rank(self)
+    }
+
+    fn select(&self, rank: usize) -> Option<usize> {
+        ArchivedBitRank::select(self, rank)
+    }
+}
+
+/// An error which is returned when the parts of deserialized offsets are inconsistent.
+#[derive(Debug)]
+pub(crate) struct InconsistentOffsets;
+
+impl fmt::Display for InconsistentOffsets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("inconsistent string offsets")
+    }
+}
+
+impl core::error::Error for InconsistentOffsets {}
+
+/// Returns the largest element of the set.
+fn last(set: &dyn Positions) -> Option<usize> {
+    set.max_rank()
+        .checked_sub(1)
+        .and_then(|rank| set.select(rank))
+}
+
+/// Checks the bitranks of a string of `len` bytes with `lines` lines starting at `begin`.
+///
+/// The bitranks in `ends` have a bit at the last byte of the string like the line bitrank, the
+/// ones in `others` only must not have bits past the end of the string.
+fn check_offsets(
+    len: usize,
+    lines: usize,
+    begin: impl Fn(usize) -> usize,
+    utf8_to_line: &dyn Positions,
+    ends: &[&dyn Positions],
+    others: &[&dyn Positions],
+) -> Result<(), InconsistentOffsets> {
+    let last_byte = len.checked_sub(1);
+    if last(utf8_to_line) != last_byte
+        || ends.iter().any(|set| last(*set) != last_byte)
+        || others
+            .iter()
+            .any(|set| last(*set).is_some_and(|p| p >= len))
+    {
+        return Err(InconsistentOffsets);
+    }
+    // Every line but the first starts behind the bit which ends the previous one.
+    if utf8_to_line.max_rank() != lines
+        || (1..lines).any(|line| utf8_to_line.select(line - 1).map(|p| p + 1) != Some(begin(line)))
+    {
+        return Err(InconsistentOffsets);
+    }
+    Ok(())
+}
+
+impl StringOffsets {
+    /// Checks that the bitranks agree with the line table, see [`check_offsets`].
+    #[cfg(any(feature = "serde", test))]
+    fn check(&self) -> Result<(), InconsistentOffsets> {
+        check_offsets(
+            self.line_table.len() as usize,
+            self.line_table.count(),
+            |line| self.line_table.begin(line) as usize,
+            &self.utf8_to_line,
+            &[
+                &self.utf8_to_char,
+                &self.utf8_to_utf16,
+                #[cfg(feature = "graphemes")]
+                &self.utf8_to_grapheme,
+            ],
+            &[
+                #[cfg(feature = "display-width")]
+                &self.utf8_to_display,
+                #[cfg(feature = "visual-columns")]
+                &self.tabs,
+            ],
+        )
+    }
+}
+
+// SAFETY: `verify` only rejects archives, it doesn't rely on anything besides the checked bytes.
+#[cfg(feature = "rkyv")]
+unsafe impl<C: rkyv::rancor::Fallible + ?Sized> rkyv::bytecheck::Verify<C> for ArchivedStringOffsets
+where
+    C::Error: rkyv::rancor::Source,
+{
+    /// Checks that the bitranks agree with the line table like deserialization does. The fields
+    /// have been checked on their own at this point.
+    fn verify(&self, _context: &mut C) -> Result<(), C::Error> {
+        check_offsets(
+            self.line_table.len() as usize,
+            self.line_table.count(),
+            |line| self.line_table.begin(line) as usize,
+            &self.utf8_to_line,
+            &[
+                &self.utf8_to_char,
+                &self.utf8_to_utf16,
+                #[cfg(feature = "graphemes")]
+                &self.utf8_to_grapheme,
+            ],
+            &[
+                #[cfg(feature = "display-width")]
+                &self.utf8_to_display,
+                #[cfg(feature = "visual-columns")]
+                &self.tabs,
+            ],
+        )
+        .map_err(<C::Error as rkyv::rancor::Source>::new)
+    }
+}
+
+/// The parts of a [`StringOffsets`] before they were checked, which is the form in which it is
+/// deserialized with serde.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+pub(crate) struct UncheckedOffsets {
+    line_table: LineTable,
+    utf8_to_line: GapBitRank,
+    utf8_to_char: GapBitRank,
+    utf8_to_utf16: GapBitRank,
+    #[cfg(feature = "graphemes")]
+    utf8_to_grapheme: GapBitRank,
+    #[cfg(feature = "display-width")]
+    utf8_to_display: GapBitRank,
+    #[cfg(feature = "visual-columns")]
+    tabs: GapBitRank,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<UncheckedOffsets> for StringOffsets {
+    type Error = InconsistentOffsets;
+
+    fn try_from(parts: UncheckedOffsets) -> Result<Self, Self::Error> {
+        let offsets = Self {
+            line_table: parts.line_table,
+            utf8_to_line: parts.utf8_to_line,
+            utf8_to_char: parts.utf8_to_char,
+            utf8_to_utf16: parts.utf8_to_utf16,
+            #[cfg(feature = "graphemes")]
+            utf8_to_grapheme: parts.utf8_to_grapheme,
+            #[cfg(feature = "display-width")]
+            utf8_to_display: parts.utf8_to_display,
+            #[cfg(feature = "visual-columns")]
+            tabs: parts.tabs,
+            anchors: Default::default(),
+        };
+        offsets.check()?;
+        Ok(offsets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::StringOffsets;
+
+    #[test]
+    fn test_inconsistent_offsets() {
+        let content = "a\tb\n\u{1F5FA}\u{FE0F}\u{4E16}\n";
+        let longer = StringOffsets::new("a\tb\n\u{1F5FA}\u{FE0F}\u{4E16}\n\t\u{4E16}\n");
+        assert!(StringOffsets::new(content).check().is_ok());
+        assert!(StringOffsets::new("").check().is_ok());
+        let check = |swap: fn(&mut StringOffsets, &StringOffsets)| {
+            let mut offsets = StringOffsets::new(content);
+            swap(&mut offsets, &longer);
+            offsets.check().is_err()
+        };
+        assert!(check(|o, l| o.utf8_to_line = l.utf8_to_line.clone()));
+        assert!(check(|o, l| o.utf8_to_char = l.utf8_to_char.clone()));
+        assert!(check(|o, l| o.utf8_to_utf16 = l.utf8_to_utf16.clone()));
+        #[cfg(feature = "graphemes")]
+        assert!(check(|o, l| o.utf8_to_grapheme = l.utf8_to_grapheme.clone()));
+        #[cfg(feature = "display-width")]
+        assert!(check(|o, l| o.utf8_to_display = l.utf8_to_display.clone()));
+        #[cfg(feature = "visual-columns")]
+        assert!(check(|o, l| o.tabs = l.tabs.clone()));
+        // A line which doesn't start behind the end of the previous one.
+        assert!(check(|o, _| o.line_table =
+            StringOffsets::new("a\tb\u{1F5FA}\n\u{FE0F}\u{4E16}\n")
+                .line_table));
+    }
+}
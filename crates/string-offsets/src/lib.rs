@@ -22,12 +22,72 @@
 //! ```
 //!
 //! See [`StringOffsets`] for details.
+//!
+//! The succinct rank data structure backing the conversions is available as [`BitRank`].
+//!
+//! # Features
+//!
+//! The crate supports `no_std` environments with an allocator, e.g. WASM runtimes without WASI,
+//! when the default `std` feature is disabled. The `wasm`, `bitvec`, `roaring`, `simd`, `rayon`,
+//! `arbitrary`, `testing`, `lsp-types` and `miette` features as well as
+//! [`bitrank::BitRankWriter`] require `std`.
+//!
+//! The `arbitrary` feature implements [`arbitrary::Arbitrary`](https://docs.rs/arbitrary) for
+//! [`BitRank`], so that fuzz targets can generate valid instances directly. The `testing` feature
+//! exposes `proptest` strategies in the `testing` module for property tests against the
+//! conversion APIs. The `graphemes` feature adds conversions to offsets counted in extended
+//! grapheme clusters, as determined by
+//! [`unicode-segmentation`](https://docs.rs/unicode-segmentation). The `display-width` feature
+//! adds conversions to the columns of a terminal, as determined by
+//! [`unicode-width`](https://docs.rs/unicode-width). The `visual-columns` feature adds
+//! conversions to columns in which tabs extend to the next tab stop. The `lsp-types` feature adds
+//! conversions from and to the positions and ranges of [`lsp-types`](https://docs.rs/lsp-types).
+//! The `tree-sitter` feature adds conversions from and to the points of
+//! [`tree-sitter`](https://docs.rs/tree-sitter) and describes edits as its `InputEdit`s. The
+//! `codespan-reporting` feature adds [`SourceFile`], which implements the `Files` trait of
+//! [`codespan-reporting`](https://docs.rs/codespan-reporting) on top of a [`StringOffsets`], and
+//! the `miette` feature implements the `SourceCode` trait of [`miette`](https://docs.rs/miette)
+//! for it. The `text-size` feature makes [`text-size`](https://docs.rs/text-size)'s `TextSize` an
+//! [`Offset`] and adds conversions from and to its `TextRange`.
+
 #![deny(missing_docs)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
-use std::ops::Range;
+extern crate alloc;
 
-mod bitrank;
-use bitrank::{BitRank, BitRankBuilder};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+mod anchor;
+pub mod bitrank;
+mod checked;
+#[cfg(any(feature = "serde", feature = "rkyv"))]
+mod consistency;
+mod line_table;
+#[cfg(feature = "lsp-types")]
+mod lsp;
+#[cfg(any(feature = "codespan-reporting", feature = "miette"))]
+mod source_file;
+#[cfg(feature = "text-size")]
+mod text_size;
+#[cfg(feature = "tree-sitter")]
+mod tree_sitter;
+mod typed;
+mod unchecked;
+pub use anchor::{Anchor, Bias};
+use bitrank::GapBitRank;
+pub use bitrank::{BitRank, BitRankBuilder};
+pub use checked::ConversionError;
+use line_table::LineTable;
+#[cfg(any(feature = "codespan-reporting", feature = "miette"))]
+pub use source_file::SourceFile;
+pub use typed::{
+    CharOffset, CharRange, LineIdx, Offset, Utf16Offset, Utf16Range, Utf8Offset, Utf8Range,
+};
+#[cfg(feature = "testing")]
+pub mod testing;
 
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
@@ -87,28 +147,60 @@ use wasm_bindgen::prelude::*;
 /// data structure is typically less than the memory occupied by the actual content. In the best
 /// case, it requires ~45% of the content space.
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "consistency::UncheckedOffsets")
+)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    rkyv(bytecheck(verify))
+)]
 pub struct StringOffsets {
-    /// Vector storing, for every line, the byte position at which the line starts.
-    line_begins: Vec<u32>,
+    /// The byte position at which every line starts and whether it consists only of whitespace
+    /// characters.
+    line_table: LineTable,
 
     /// Encoded bitrank where the rank of a byte position corresponds to the line number to which
     /// the byte belongs.
-    utf8_to_line: BitRank,
+    utf8_to_line: GapBitRank,
 
     /// Encoded bitrank where the rank of a byte position corresponds to the char position to which
     /// the byte belongs.
-    utf8_to_char: BitRank,
+    utf8_to_char: GapBitRank,
 
     /// Encoded bitrank where the rank of a byte position corresponds to the UTF-16 encoded word
     /// position to which the byte belongs.
-    utf8_to_utf16: BitRank,
+    utf8_to_utf16: GapBitRank,
+
+    /// Encoded bitrank where the rank of a byte position corresponds to the grapheme cluster
+    /// position to which the byte belongs.
+    #[cfg(feature = "graphemes")]
+    utf8_to_grapheme: GapBitRank,
+
+    /// Encoded bitrank where the rank of a byte position corresponds to the display column at
+    /// which the byte is shown.
+    #[cfg(feature = "display-width")]
+    utf8_to_display: GapBitRank,
 
-    /// Marks, for every line, whether it consists only of whitespace characters.
-    whitespace_only: Vec<bool>,
+    /// The byte positions of all tab characters.
+    #[cfg(feature = "visual-columns")]
+    tabs: GapBitRank,
+
+    /// Byte positions which are moved by edits, see [`StringOffsets::anchor`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(feature = "rkyv", rkyv(with = rkyv::with::Skip))]
+    anchors: anchor::Anchors,
 }
 
 /// A position in a string, specified by line and column number.
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Pos {
     /// Zero-indexed line number.
@@ -118,6 +210,24 @@ pub struct Pos {
     pub col: usize,
 }
 
+/// The unit in which the columns of a [`Pos`] are counted, i.e. the position encodings which
+/// LSP 3.17 clients and servers negotiate.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PositionEncoding {
+    /// UTF-8 bytes.
+    #[cfg_attr(feature = "serde", serde(rename = "utf-8"))]
+    Utf8,
+    /// UTF-16 code units, which every LSP client supports.
+    #[default]
+    #[cfg_attr(feature = "serde", serde(rename = "utf-16"))]
+    Utf16,
+    /// Unicode code points.
+    #[cfg_attr(feature = "serde", serde(rename = "utf-32"))]
+    Utf32,
+}
+
 // The actual conversion implementation between utf8, utf16, chars, and line numbers.
 // New methods must follow the existing conventions:
 //
@@ -164,16 +274,13 @@ impl StringOffsets {
     /// Returns the number of lines in the string.
     #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = lines))]
     pub fn lines(&self) -> usize {
-        self.line_begins.len() - 1
+        self.line_table.count()
     }
 
     /// Returns true if the specified line is empty except for whitespace.
     #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = onlyWhitespaces))]
     pub fn only_whitespaces(&self, line_number: usize) -> bool {
-        self.whitespace_only
-            .get(line_number)
-            .copied()
-            .unwrap_or(true)
+        self.line_table.whitespace_only(line_number).unwrap_or(true)
     }
 
     /// Return the byte offset of the first character on the specified (zero-based) line.
@@ -182,7 +289,7 @@ impl StringOffsets {
     /// the length of the string.
     #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = lineToUtf8Begin))]
     pub fn line_to_utf8_begin(&self, line_number: usize) -> usize {
-        self.line_begins[line_number.min(self.lines())] as usize
+        self.line_table.begin(line_number) as usize
     }
 
     /// UTF-16 offset of the first character of a line.
@@ -266,6 +373,98 @@ impl StringOffsets {
         self.utf8_to_utf16.rank(byte_number)
     }
 
+    /// Converts a UTF-16 offset to a UTF-8 offset.
+    ///
+    /// An offset between the two code units of a surrogate pair maps to the start of the
+    /// character, and offsets past the end of the string map to the length of the string.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = utf16ToUtf8))]
+    pub fn utf16_to_utf8(&self, utf16_number: usize) -> usize {
+        let Some(last) = utf16_number.checked_sub(1) else {
+            return 0;
+        };
+        // Every code unit is marked by the last byte of its character, except for the first unit
+        // of a surrogate pair, which is marked by the first byte.
+        match self.utf8_to_utf16.select(last) {
+            Some(byte_number) if self.utf8_to_char.contains(byte_number) => byte_number + 1,
+            Some(byte_number) => byte_number,
+            None => self.line_to_utf8_begin(self.lines()),
+        }
+    }
+
+    /// Converts a zero-based line number and UTF-16 offset within the line, e.g. an LSP
+    /// `Position`, to a UTF-8 offset. This is the inverse of [`StringOffsets::utf8_to_utf16_pos`].
+    ///
+    /// Columns past the end of the line map to the end of the line, including the newline.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_offsets::StringOffsets;
+    ///
+    /// let offsets = StringOffsets::new("a\n🗺️ map\n");
+    /// // The map emoji takes 2 UTF-16 code units plus 1 for the variation selector.
+    /// assert_eq!(offsets.utf16_pos_to_utf8(1, 3), 9);
+    /// assert_eq!(offsets.utf16_pos_to_utf8(1, 100), 14);
+    /// ```
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = utf16PosToUtf8))]
+    pub fn utf16_pos_to_utf8(&self, line_number: usize, col: usize) -> usize {
+        let begin = self.line_to_utf16_begin(line_number);
+        self.utf16_to_utf8((begin + col).min(self.line_to_utf16_end(line_number)))
+    }
+
+    /// Converts a UTF-8 offset to a zero-based line number and column within the line, counted in
+    /// the units of `encoding`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_offsets::{Pos, PositionEncoding, StringOffsets};
+    ///
+    /// let offsets = StringOffsets::new("a\n👋b");
+    /// let pos = |col| Pos { line: 1, col };
+    /// assert_eq!(offsets.offset_to_position(6, PositionEncoding::Utf8), pos(4));
+    /// assert_eq!(offsets.offset_to_position(6, PositionEncoding::Utf16), pos(2));
+    /// assert_eq!(offsets.offset_to_position(6, PositionEncoding::Utf32), pos(1));
+    /// assert_eq!(offsets.position_to_offset(pos(2), PositionEncoding::Utf16), 6);
+    /// ```
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = offsetToPosition))]
+    pub fn offset_to_position(&self, byte_number: usize, encoding: PositionEncoding) -> Pos {
+        match encoding {
+            PositionEncoding::Utf8 => {
+                let line = self.utf8_to_line(byte_number);
+                let byte_number = byte_number.min(self.line_to_utf8_begin(self.lines()));
+                Pos {
+                    line,
+                    col: byte_number - self.line_to_utf8_begin(line),
+                }
+            }
+            PositionEncoding::Utf16 => self.utf8_to_utf16_pos(byte_number),
+            PositionEncoding::Utf32 => self.utf8_to_char_pos(byte_number),
+        }
+    }
+
+    /// Converts a zero-based line number and column within the line, counted in the units of
+    /// `encoding`, to a UTF-8 offset. This is the inverse of
+    /// [`StringOffsets::offset_to_position`].
+    ///
+    /// Columns within a character map to the start of the character, and columns past the end of
+    /// the line map to the end of the line, including the newline.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = positionToOffset))]
+    pub fn position_to_offset(&self, pos: Pos, encoding: PositionEncoding) -> usize {
+        match encoding {
+            PositionEncoding::Utf8 => {
+                let bytes = self.line_to_utf8s(pos.line);
+                let byte_number = (bytes.start + pos.col).min(bytes.end);
+                self.floor_char_boundary(byte_number)
+            }
+            PositionEncoding::Utf16 => self.utf16_pos_to_utf8(pos.line, pos.col),
+            PositionEncoding::Utf32 => {
+                let chars = self.line_to_chars(pos.line);
+                self.char_to_utf8((chars.start + pos.col).min(chars.end))
+            }
+        }
+    }
+
     /// Converts a UTF-32 offset to a UTF-8 offset.
     #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = charToUtf8))]
     pub fn char_to_utf8(&self, char_number: usize) -> usize {
@@ -281,14 +480,9 @@ impl StringOffsets {
         // This does not usually happen. For consistency with the rest of the code, we simply return
         // the max utf8 position in this case.
         if char_number > self.utf8_to_char.max_rank() {
-            return self
-                .line_begins
-                .last()
-                .copied()
-                .expect("last entry represents the length of the file!")
-                as usize;
-        }
-        let limit = *self.line_begins.last().expect("no line begins") as usize;
+            return self.line_table.len() as usize;
+        }
+        let limit = self.line_table.len() as usize;
         // Otherwise, we keep searching, but are a bit more careful and add a check that we don't run into an infinite loop.
         loop {
             let char_number2 = self.utf8_to_char(byte_number);
@@ -299,6 +493,281 @@ impl StringOffsets {
             assert!(byte_number < limit);
         }
     }
+
+    /// The largest character boundary not greater than `byte_number`, like
+    /// `str::floor_char_boundary`, e.g. to snap the offsets of a byte-oriented regex match.
+    /// Offsets past the end of the string map to the length of the string.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = floorCharBoundary))]
+    pub fn floor_char_boundary(&self, byte_number: usize) -> usize {
+        let len = self.line_to_utf8_begin(self.lines());
+        if byte_number >= len {
+            return len;
+        }
+        // Every character is marked by its last byte, so the character containing the offset
+        // starts after the last byte of the one before it.
+        match self.utf8_to_char.rank(byte_number).checked_sub(1) {
+            Some(prev) => {
+                self.utf8_to_char
+                    .select(prev)
+                    .expect("character before offset")
+                    + 1
+            }
+            None => 0,
+        }
+    }
+
+    /// The smallest character boundary not less than `byte_number`, like
+    /// `str::ceil_char_boundary`. Offsets past the end of the string map to the length of the
+    /// string.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = ceilCharBoundary))]
+    pub fn ceil_char_boundary(&self, byte_number: usize) -> usize {
+        let len = self.line_to_utf8_begin(self.lines());
+        if byte_number == 0 || byte_number >= len {
+            return byte_number.min(len);
+        }
+        // The end of the character containing the byte in front of the offset.
+        let prev = self.utf8_to_char.rank(byte_number - 1);
+        self.utf8_to_char.select(prev).expect("character at offset") + 1
+    }
+
+    /// Converts a UTF-8 offset to the number of grapheme clusters in front of it. An offset
+    /// within a cluster, e.g. within a ZWJ emoji sequence, maps to the cluster containing it.
+    ///
+    /// This requires the `graphemes` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_offsets::StringOffsets;
+    ///
+    /// // The family emoji consists of 5 code points joined by ZWJs.
+    /// let offsets = StringOffsets::new("a👨‍👩‍👧b");
+    /// assert_eq!(offsets.utf8_to_grapheme(1), 1);
+    /// assert_eq!(offsets.utf8_to_grapheme(5), 1);
+    /// assert_eq!(offsets.utf8_to_grapheme(19), 2);
+    /// assert_eq!(offsets.grapheme_to_utf8(2), 19);
+    /// ```
+    #[cfg(feature = "graphemes")]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = utf8ToGrapheme))]
+    pub fn utf8_to_grapheme(&self, byte_number: usize) -> usize {
+        self.utf8_to_grapheme.rank(byte_number)
+    }
+
+    /// Converts a grapheme cluster offset to the UTF-8 offset at which the cluster starts.
+    ///
+    /// The result is always at a cluster boundary, so moving a cursor by clusters never splits
+    /// one. Offsets past the end of the string map to the length of the string.
+    ///
+    /// This requires the `graphemes` feature.
+    #[cfg(feature = "graphemes")]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = graphemeToUtf8))]
+    pub fn grapheme_to_utf8(&self, grapheme_number: usize) -> usize {
+        // Every cluster is marked by its last byte.
+        match grapheme_number.checked_sub(1) {
+            None => 0,
+            Some(last) => self
+                .utf8_to_grapheme
+                .select(last)
+                .map_or(self.line_to_utf8_begin(self.lines()), |end| end + 1),
+        }
+    }
+
+    /// Grapheme cluster offset of the first cluster of a line.
+    ///
+    /// This requires the `graphemes` feature.
+    #[cfg(feature = "graphemes")]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = lineToGraphemeBegin))]
+    pub fn line_to_grapheme_begin(&self, line_number: usize) -> usize {
+        self.utf8_to_grapheme(self.line_to_utf8_begin(line_number))
+    }
+
+    /// Converts a UTF-8 offset to a zero-based line number and grapheme cluster offset within the
+    /// line, i.e. the column at which a cursor at this offset is displayed.
+    ///
+    /// This requires the `graphemes` feature.
+    #[cfg(feature = "graphemes")]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = utf8ToGraphemePos))]
+    pub fn utf8_to_grapheme_pos(&self, byte_number: usize) -> Pos {
+        let line = self.utf8_to_line(byte_number);
+        Pos {
+            line,
+            col: self.utf8_to_grapheme(byte_number) - self.line_to_grapheme_begin(line),
+        }
+    }
+
+    /// Converts a zero-based line number and grapheme cluster offset within the line to a UTF-8
+    /// offset. Columns past the end of the line map to the end of the line, including the newline.
+    ///
+    /// This requires the `graphemes` feature.
+    #[cfg(feature = "graphemes")]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = graphemePosToUtf8))]
+    pub fn grapheme_pos_to_utf8(&self, line_number: usize, col: usize) -> usize {
+        let begin = self.line_to_grapheme_begin(line_number);
+        let end = self.utf8_to_grapheme(self.line_to_utf8_end(line_number));
+        self.grapheme_to_utf8((begin + col).min(end))
+    }
+
+    /// Converts a UTF-8 offset to a zero-based line number and the display column within the
+    /// line, e.g. to align a caret under a diagnostic in terminal output.
+    ///
+    /// Every character occupies as many columns as given by
+    /// [`unicode-width`](https://docs.rs/unicode-width), e.g. two for CJK characters. Control
+    /// characters, including tabs, don't occupy a column. An offset within a character maps to
+    /// the first column of the character.
+    ///
+    /// This requires the `display-width` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_offsets::{Pos, StringOffsets};
+    ///
+    /// let offsets = StringOffsets::new("let 名前 = 1;\n");
+    /// // `=` is the 7th character, but it is shown in column 9.
+    /// assert_eq!(offsets.utf8_to_display_pos(11), Pos { line: 0, col: 9 });
+    /// assert_eq!(offsets.display_pos_to_utf8(0, 9), 11);
+    /// ```
+    #[cfg(feature = "display-width")]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = utf8ToDisplayPos))]
+    pub fn utf8_to_display_pos(&self, byte_number: usize) -> Pos {
+        let line = self.utf8_to_line(byte_number);
+        let line_start = self.utf8_to_display.rank(self.line_to_utf8_begin(line));
+        Pos {
+            line,
+            col: self
+                .utf8_to_display
+                .rank(self.floor_char_boundary(byte_number))
+                - line_start,
+        }
+    }
+
+    /// Converts a zero-based line number and display column to the UTF-8 offset of the character
+    /// shown in that column. A column in the middle of a wide character maps to the start of the
+    /// character, and columns past the end of the line map to the end of the line, including the
+    /// newline.
+    ///
+    /// This requires the `display-width` feature.
+    #[cfg(feature = "display-width")]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = displayPosToUtf8))]
+    pub fn display_pos_to_utf8(&self, line_number: usize, col: usize) -> usize {
+        let begin = self.line_to_utf8_begin(line_number);
+        let end = self.line_to_utf8_end(line_number);
+        // The bits of a character are set at its last bytes, one per column.
+        match self
+            .utf8_to_display
+            .select(self.utf8_to_display.rank(begin) + col)
+        {
+            Some(byte_number) if byte_number < end => self.floor_char_boundary(byte_number),
+            _ => end,
+        }
+    }
+
+    /// Returns the number of display columns of the specified line, see
+    /// [`StringOffsets::utf8_to_display_pos`].
+    ///
+    /// This requires the `display-width` feature.
+    #[cfg(feature = "display-width")]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = lineDisplayWidth))]
+    pub fn line_display_width(&self, line_number: usize) -> usize {
+        let bytes = self.line_to_utf8s(line_number);
+        self.utf8_to_display.rank(bytes.end) - self.utf8_to_display.rank(bytes.start)
+    }
+
+    /// Converts a UTF-8 offset to a zero-based line number and visual column within the line,
+    /// where tabs extend to the next multiple of `tab_width`, e.g. 8 like compilers and terminals
+    /// or 4 like many editors. All other characters occupy one column.
+    ///
+    /// This panics if `tab_width` is zero. It requires the `visual-columns` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_offsets::{Pos, StringOffsets};
+    ///
+    /// let offsets = StringOffsets::new("a\tb\n\t\tc");
+    /// assert_eq!(offsets.utf8_to_visual_pos(2, 8), Pos { line: 0, col: 8 });
+    /// assert_eq!(offsets.utf8_to_visual_pos(6, 4), Pos { line: 1, col: 8 });
+    /// assert_eq!(offsets.visual_pos_to_utf8(1, 5, 4), 5);
+    /// ```
+    #[cfg(feature = "visual-columns")]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = utf8ToVisualPos))]
+    pub fn utf8_to_visual_pos(&self, byte_number: usize, tab_width: usize) -> Pos {
+        assert!(tab_width > 0, "tab width must be positive");
+        let line = self.utf8_to_line(byte_number);
+        let mut col = 0;
+        let mut from = self.line_to_utf8_begin(line);
+        for tab in self.tabs.iter_range(from..byte_number) {
+            col += self.utf8_to_char(tab) - self.utf8_to_char(from);
+            col = (col / tab_width + 1) * tab_width;
+            from = tab + 1;
+        }
+        col += self.utf8_to_char(byte_number) - self.utf8_to_char(from);
+        Pos { line, col }
+    }
+
+    /// Converts a zero-based line number and visual column, see
+    /// [`StringOffsets::utf8_to_visual_pos`], to the UTF-8 offset of the character shown in that
+    /// column. A column covered by a tab maps to the tab, and columns past the end of the line
+    /// map to the end of the line, including the newline.
+    ///
+    /// This panics if `tab_width` is zero. It requires the `visual-columns` feature.
+    #[cfg(feature = "visual-columns")]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = visualPosToUtf8))]
+    pub fn visual_pos_to_utf8(&self, line_number: usize, col: usize, tab_width: usize) -> usize {
+        assert!(tab_width > 0, "tab width must be positive");
+        let bytes = self.line_to_utf8s(line_number);
+        // The column and the offset behind the last tab in front of `col`.
+        let mut tab_end = 0;
+        let mut from = bytes.start;
+        for tab in self.tabs.iter_range(bytes.clone()) {
+            let tab_start = tab_end + self.utf8_to_char(tab) - self.utf8_to_char(from);
+            if col < tab_start {
+                break;
+            }
+            tab_end = (tab_start / tab_width + 1) * tab_width;
+            if col < tab_end {
+                return tab;
+            }
+            from = tab + 1;
+        }
+        let char_number = self.utf8_to_char(from) + col - tab_end;
+        if char_number < self.utf8_to_char(bytes.end) {
+            self.char_to_utf8(char_number)
+        } else {
+            bytes.end
+        }
+    }
+
+    /// Returns the number of bytes allocated on the heap by this converter, including unused
+    /// capacity. The string itself is not included, since it isn't owned by the converter.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = heapSizeBytes))]
+    pub fn heap_size_bytes(&self) -> usize {
+        self.line_table.heap_size_bytes()
+            + self.utf8_to_line.heap_size_bytes()
+            + self.utf8_to_char.heap_size_bytes()
+            + self.utf8_to_utf16.heap_size_bytes()
+            + self.anchors.heap_size_bytes()
+            + self.feature_heap_size_bytes()
+    }
+
+    /// The number of bytes allocated on the heap by the bitranks of optional features.
+    #[allow(unused_mut)]
+    fn feature_heap_size_bytes(&self) -> usize {
+        let mut size = 0;
+        #[cfg(feature = "graphemes")]
+        {
+            size += self.utf8_to_grapheme.heap_size_bytes();
+        }
+        #[cfg(feature = "display-width")]
+        {
+            size += self.utf8_to_display.heap_size_bytes();
+        }
+        #[cfg(feature = "visual-columns")]
+        {
+            size += self.tabs.heap_size_bytes();
+        }
+        size
+    }
 }
 
 impl StringOffsets {
@@ -344,14 +813,210 @@ impl StringOffsets {
         self.utf8s_to_lines(self.chars_to_utf8s(chars))
     }
 
+    /// UTF-8 offsets of the whole lines containing `bytes`, extended by up to `lines_before` lines
+    /// in front and `lines_after` lines behind. This is the snippet a diagnostic renderer like
+    /// `miette::SourceCode::read_span` shows around a span.
+    pub fn utf8s_with_context(
+        &self,
+        bytes: Range<usize>,
+        lines_before: usize,
+        lines_after: usize,
+    ) -> Range<usize> {
+        let lines = self.utf8s_to_lines(bytes);
+        // An empty span at the end of the string still shows the last line.
+        let start = lines.start.min(self.lines().saturating_sub(1));
+        let first = start.saturating_sub(lines_before);
+        let end = self.lines().min(lines.end.max(start + 1) + lines_after);
+        self.lines_to_utf8s(first..end)
+    }
+
     /// Converts a UTF-8 offset range to a UTF-32 offset range.
     pub fn utf8s_to_chars(&self, bytes: Range<usize>) -> Range<usize> {
-        self.utf8_to_char(bytes.start)..self.utf8_to_char(bytes.end)
+        checked_range(self.utf8_to_char(bytes.start)..self.utf8_to_char(bytes.end))
     }
 
     /// Converts a UTF-32 offset range to a UTF-8 offset range.
     pub fn chars_to_utf8s(&self, chars: Range<usize>) -> Range<usize> {
-        self.char_to_utf8(chars.start)..self.char_to_utf8(chars.end)
+        checked_range(self.char_to_utf8(chars.start)..self.char_to_utf8(chars.end))
+    }
+
+    /// Converts a UTF-8 offset range to a UTF-16 offset range.
+    pub fn utf8s_to_utf16s(&self, bytes: Range<usize>) -> Range<usize> {
+        checked_range(self.utf8_to_utf16(bytes.start)..self.utf8_to_utf16(bytes.end))
+    }
+
+    /// Converts a UTF-16 offset range to a UTF-8 offset range.
+    pub fn utf16s_to_utf8s(&self, utf16s: Range<usize>) -> Range<usize> {
+        checked_range(self.utf16_to_utf8(utf16s.start)..self.utf16_to_utf8(utf16s.end))
+    }
+
+    /// Updates the converter for replacing `byte_range` of `content` with `replacement`, without
+    /// rebuilding it from the whole edited string.
+    ///
+    /// `content` must be the string this converter currently describes, i.e. before the edit. Only
+    /// the lines touched by the edit are scanned again, since whether they consist of whitespace
+    /// depends on the text around the edit. The data of the following lines isn't moved: like in
+    /// a gap buffer, the converter keeps unused space at the last edit, which is moved to the next
+    /// edit. Editing close to the previous edit therefore takes time proportional to the size of
+    /// the edited lines, independent of the size of the string.
+    ///
+    /// This panics if `content` doesn't have the length of the described string or if the range
+    /// is out of bounds or doesn't start and end at character boundaries.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_offsets::StringOffsets;
+    ///
+    /// let mut offsets = StringOffsets::new("hello\nworld\n");
+    /// offsets.apply_edit(2..9, "🗺️\n  ", "hello\nworld\n");
+    /// // The content is now "he🗺️\n  ld\n".
+    /// assert_eq!(offsets.lines(), 2);
+    /// assert_eq!(offsets.line_to_utf8s(1), 10..15);
+    /// assert_eq!(offsets.utf8_to_utf16(10), 6);
+    /// ```
+    pub fn apply_edit(&mut self, byte_range: Range<usize>, replacement: &str, content: &str) {
+        self.apply_edits(&[(byte_range, replacement)], content);
+    }
+
+    /// Updates the converter for several edits of `content` at once, e.g. the content changes of
+    /// an LSP `didChange` notification, see [`StringOffsets::apply_edit`].
+    ///
+    /// The byte ranges refer to `content` before any edit and have to be sorted and
+    /// non-overlapping, otherwise this panics. Edits on the same or adjacent lines are converted
+    /// together. The [anchors](StringOffsets::anchor) are moved along with the text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_offsets::StringOffsets;
+    ///
+    /// let content = "one\ntwo\nthree\n";
+    /// let mut offsets = StringOffsets::new(content);
+    /// offsets.apply_edits(&[(0..3, "1"), (8..13, "3\n4")], content);
+    /// // The content is now "1\ntwo\n3\n4\n".
+    /// assert_eq!(offsets.lines(), 4);
+    /// assert_eq!(offsets.line_to_utf8s(2), 6..8);
+    /// ```
+    pub fn apply_edits(&mut self, edits: &[(Range<usize>, &str)], content: &str) {
+        let len = self.line_to_utf8_begin(self.lines());
+        assert_eq!(content.len(), len, "content doesn't match the converter");
+        // Rebuild the lines containing the edits, including the lines right before and after an
+        // edit when it touches a line boundary.
+        let lines = |bytes: &Range<usize>| match len.checked_sub(1) {
+            Some(last) => {
+                self.utf8_to_line(bytes.start.min(last))..self.utf8_to_line(bytes.end.min(last)) + 1
+            }
+            None => 0..0,
+        };
+        // The edited lines and the converter for their new content.
+        let mut regions: Vec<(Range<usize>, StringOffsets)> = vec![];
+        let mut previous_end = 0;
+        let mut i = 0;
+        while i < edits.len() {
+            let mut region = lines(&edits[i].0);
+            let first = i;
+            while i < edits.len() && (i == first || lines(&edits[i].0).start < region.end) {
+                let byte_range = &edits[i].0;
+                assert!(
+                    previous_end <= byte_range.start
+                        && byte_range.start <= byte_range.end
+                        && content.is_char_boundary(byte_range.start)
+                        && content.is_char_boundary(byte_range.end),
+                    "invalid edit range {byte_range:?}"
+                );
+                previous_end = byte_range.end;
+                region.end = lines(byte_range).end;
+                i += 1;
+            }
+            let bytes = self.lines_to_utf8s(region.clone());
+            let mut text = String::new();
+            let mut copied = bytes.start;
+            for (byte_range, replacement) in &edits[first..i] {
+                text.push_str(&content[copied..byte_range.start]);
+                text.push_str(replacement);
+                copied = byte_range.end;
+            }
+            text.push_str(&content[copied..bytes.end]);
+            regions.push((region, new_converter(text.as_bytes())));
+        }
+
+        // Replace the regions from back to front, so that the positions of the regions in front
+        // of them stay valid.
+        for (lines, edited) in regions.iter().rev() {
+            let bytes = self.lines_to_utf8s(lines.clone());
+            let len = edited.line_table.len() as usize;
+            self.line_table
+                .splice(lines.clone(), &edited.line_table, bytes.start as u32);
+            let splice = |bit_rank: &mut GapBitRank, part: &GapBitRank| {
+                bit_rank.splice(bytes.clone(), part.iter_range(0..len), len);
+            };
+            splice(&mut self.utf8_to_line, &edited.utf8_to_line);
+            splice(&mut self.utf8_to_char, &edited.utf8_to_char);
+            splice(&mut self.utf8_to_utf16, &edited.utf8_to_utf16);
+            #[cfg(feature = "visual-columns")]
+            splice(&mut self.tabs, &edited.tabs);
+            // Clusters never span a newline, so whole lines can be segmented on their own.
+            #[cfg(feature = "graphemes")]
+            splice(&mut self.utf8_to_grapheme, &edited.utf8_to_grapheme);
+            #[cfg(feature = "display-width")]
+            splice(&mut self.utf8_to_display, &edited.utf8_to_display);
+        }
+        self.move_anchors(edits);
+    }
+}
+
+/// The most common conversions on a [`StringOffsets`] archived with [`rkyv`], which are answered
+/// in place without deserializing the archive.
+#[cfg(feature = "rkyv")]
+impl ArchivedStringOffsets {
+    /// Returns the number of lines in the string, see [`StringOffsets::lines`].
+    pub fn lines(&self) -> usize {
+        self.line_table.count()
+    }
+
+    /// Return the byte offset of the first character on the specified (zero-based) line, see
+    /// [`StringOffsets::line_to_utf8_begin`].
+    pub fn line_to_utf8_begin(&self, line_number: usize) -> usize {
+        self.line_table.begin(line_number) as usize
+    }
+
+    /// Return the zero-based line number of the line containing the specified UTF-8 offset, see
+    /// [`StringOffsets::utf8_to_line`].
+    pub fn utf8_to_line(&self, byte_number: usize) -> usize {
+        self.utf8_to_line.rank(byte_number)
+    }
+
+    /// Converts a UTF-8 offset to a UTF-32 offset, see [`StringOffsets::utf8_to_char`].
+    pub fn utf8_to_char(&self, byte_number: usize) -> usize {
+        self.utf8_to_char.rank(byte_number)
+    }
+
+    /// Converts a UTF-8 offset to a UTF-16 offset, see [`StringOffsets::utf8_to_utf16`].
+    pub fn utf8_to_utf16(&self, byte_number: usize) -> usize {
+        self.utf8_to_utf16.rank(byte_number)
+    }
+
+    /// Converts a UTF-8 offset to a zero-based line number and UTF-32 offset within the line, see
+    /// [`StringOffsets::utf8_to_char_pos`].
+    pub fn utf8_to_char_pos(&self, byte_number: usize) -> Pos {
+        let line = self.utf8_to_line(byte_number);
+        let line_start = self.utf8_to_char(self.line_to_utf8_begin(line));
+        Pos {
+            line,
+            col: self.utf8_to_char(byte_number) - line_start,
+        }
+    }
+
+    /// Converts a UTF-8 offset to a zero-based line number and UTF-16 offset within the line, see
+    /// [`StringOffsets::utf8_to_utf16_pos`].
+    pub fn utf8_to_utf16_pos(&self, byte_number: usize) -> Pos {
+        let line = self.utf8_to_line(byte_number);
+        let line_start = self.utf8_to_utf16(self.line_to_utf8_begin(line));
+        Pos {
+            line,
+            col: self.utf8_to_utf16(byte_number) - line_start,
+        }
     }
 }
 
@@ -397,15 +1062,84 @@ fn new_converter(content: &[u8]) -> StringOffsets {
         line_builder.push(content.len() - 1);
     }
 
+    // The length of the string is stored separately.
+    line_begins.pop();
     StringOffsets {
-        line_begins,
-        utf8_to_line: line_builder.finish(),
-        whitespace_only,
-        utf8_to_char: utf8_builder.finish(),
-        utf8_to_utf16: utf16_builder.finish(),
+        line_table: LineTable::new(line_begins, whitespace_only, content.len() as u32),
+        utf8_to_line: line_builder.finish().into(),
+        utf8_to_char: utf8_builder.finish().into(),
+        utf8_to_utf16: utf16_builder.finish().into(),
+        #[cfg(feature = "graphemes")]
+        utf8_to_grapheme: grapheme_ends(content).into(),
+        #[cfg(feature = "display-width")]
+        utf8_to_display: display_columns(content).into(),
+        #[cfg(feature = "visual-columns")]
+        tabs: tabs(content).into(),
+        anchors: Default::default(),
     }
 }
 
+/// Returns the set of the last bytes of all extended grapheme clusters. Every byte of invalid
+/// UTF-8 is treated as a cluster of its own.
+#[cfg(feature = "graphemes")]
+fn grapheme_ends(content: &[u8]) -> BitRank {
+    use unicode_segmentation::UnicodeSegmentation;
+
+    let mut builder = BitRankBuilder::with_capacity(content.len());
+    let mut offset = 0;
+    for chunk in content.utf8_chunks() {
+        for (i, grapheme) in chunk.valid().grapheme_indices(true) {
+            builder.push(offset + i + grapheme.len() - 1);
+        }
+        offset += chunk.valid().len();
+        for _ in chunk.invalid() {
+            builder.push(offset);
+            offset += 1;
+        }
+    }
+    builder.finish()
+}
+
+/// Returns the set of positions where every character has as many of its last bytes set as it
+/// occupies display columns. Every byte of invalid UTF-8 occupies one column, like the
+/// replacement character it is shown as.
+#[cfg(feature = "display-width")]
+fn display_columns(content: &[u8]) -> BitRank {
+    use unicode_width::UnicodeWidthChar;
+
+    let mut builder = BitRankBuilder::with_capacity(content.len());
+    let mut offset = 0;
+    for chunk in content.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            let len = c.len_utf8();
+            // Characters are at least as long as they are wide, e.g. CJK characters take 3 bytes.
+            let width = c.width().unwrap_or(0).min(len);
+            builder.push_run(offset + len - width..offset + len);
+            offset += len;
+        }
+        builder.push_run(offset..offset + chunk.invalid().len());
+        offset += chunk.invalid().len();
+    }
+    builder.finish()
+}
+
+/// Returns a converted range, checking in debug builds that it isn't reversed. The conversions
+/// are monotonic, so this only fails for reversed input ranges.
+fn checked_range(range: Range<usize>) -> Range<usize> {
+    debug_assert!(range.start <= range.end, "reversed range {range:?}");
+    range
+}
+
+/// Returns the set of the positions of all tab characters.
+#[cfg(feature = "visual-columns")]
+fn tabs(content: &[u8]) -> BitRank {
+    content
+        .iter()
+        .enumerate()
+        .filter_map(|(i, c)| (*c == b'\t').then_some(i))
+        .collect()
+}
+
 /// Returns the number of bytes a UTF-8 char occupies, given the first byte of the UTF-8 encoding.
 /// Returns 0 if the byte is not a valid first byte of a UTF-8 char.
 fn utf8_width(c: u8) -> usize {
@@ -629,4 +1363,405 @@ line1
         let lines = StringOffsets::from_bytes(&content);
         assert_eq!(lines.utf8_to_utf16_pos(16384), pos(1, 0));
     }
+
+    /// Checks that all conversions of `offsets` agree with a converter built from `content`.
+    fn assert_offsets_eq(offsets: &StringOffsets, content: &str) {
+        let expected = StringOffsets::new(content);
+        assert_eq!(offsets.lines(), expected.lines(), "{content:?}");
+        for line in 0..=expected.lines() {
+            assert_eq!(offsets.line_to_utf8s(line), expected.line_to_utf8s(line));
+            assert_eq!(
+                offsets.only_whitespaces(line),
+                expected.only_whitespaces(line)
+            );
+        }
+        for i in 0..=content.len() + 1 {
+            assert_eq!(offsets.utf8_to_char_pos(i), expected.utf8_to_char_pos(i));
+            assert_eq!(offsets.utf8_to_utf16_pos(i), expected.utf8_to_utf16_pos(i));
+        }
+    }
+
+    #[test]
+    fn test_utf8s_with_context() {
+        let content = "zero\none\ntwo\nthree\nfour";
+        let offsets = StringOffsets::new(content);
+        let snippet =
+            |bytes, before, after| &content[offsets.utf8s_with_context(bytes, before, after)];
+        assert_eq!(snippet(10..11, 0, 0), "two\n");
+        assert_eq!(snippet(10..11, 1, 1), "one\ntwo\nthree\n");
+        assert_eq!(snippet(6..12, 0, 0), "one\ntwo\n");
+        assert_eq!(snippet(6..12, 5, 5), content);
+        assert_eq!(snippet(14..14, 0, 0), "three\n");
+        assert_eq!(snippet(23..23, 1, 1), "three\nfour");
+    }
+
+    #[test]
+    fn test_apply_edit() {
+        let content = "☀️hello\n  \n🗺️world\nlast";
+        let edits = [
+            (0..0, "x"),
+            (0..content.len(), ""),
+            (0..content.len(), "\n\n"),
+            (6..6, "\n"),
+            (11..12, ""),
+            (11..13, "a\r\n 👋"),
+            (12..14, " "),
+            (content.len()..content.len(), "\nmore"),
+            (content.len() - 2..content.len(), "st\n"),
+            (3..content.len() - 1, "\t"),
+        ];
+        for (range, replacement) in edits {
+            let mut offsets = StringOffsets::new(content);
+            offsets.apply_edit(range.clone(), replacement, content);
+            let mut edited = content.to_string();
+            edited.replace_range(range, replacement);
+            assert_offsets_eq(&offsets, &edited);
+        }
+
+        // A sequence of edits on a text spanning several blocks.
+        let mut content = "line\n  ❤️ text\n\n".repeat(2000);
+        let mut offsets = StringOffsets::new(&content);
+        for (start, replacement) in [(100, "🗺️"), (30000, "\n\n "), (5, ""), (16380, "abc\n")]
+        {
+            let boundary = |i| (i..).find(|&i| content.is_char_boundary(i)).unwrap();
+            let (start, end) = (boundary(start), boundary(start + 7));
+            offsets.apply_edit(start..end, replacement, &content);
+            content.replace_range(start..end, replacement);
+            assert_offsets_eq(&offsets, &content);
+        }
+        let mut empty = StringOffsets::new("");
+        empty.apply_edit(0..0, "a\nb", "");
+        assert_offsets_eq(&empty, "a\nb");
+    }
+
+    #[test]
+    fn test_apply_edits() {
+        let content = "☀️hello\n  \n🗺️world\nlast";
+        let edits = [
+            vec![(0..0, "x"), (6..6, "\n"), (11..13, "a\r\n 👋")],
+            vec![(6..8, ""), (8..9, "y"), (9..9, "z"), (28..32, "")],
+            vec![(0..12, ""), (15..28, "\n")],
+            vec![(12..12, "\t"), (14..15, ""), (32..32, "\n")],
+            vec![],
+        ];
+        for edits in edits {
+            let mut offsets = StringOffsets::new(content);
+            offsets.apply_edits(&edits, content);
+            let mut edited = content.to_string();
+            for (range, replacement) in edits.iter().rev() {
+                edited.replace_range(range.clone(), replacement);
+            }
+            assert_offsets_eq(&offsets, &edited);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_apply_edits_unsorted() {
+        let content = "line\nline\n";
+        StringOffsets::new(content).apply_edits(&[(6..7, ""), (0..1, "")], content);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_apply_edit_inside_char() {
+        StringOffsets::new("🗺️").apply_edit(1..2, "", "🗺️");
+    }
+
+    #[cfg(feature = "graphemes")]
+    #[test]
+    fn test_graphemes() {
+        use unicode_segmentation::UnicodeSegmentation;
+
+        let content = "e\u{301}👨‍👩‍👧 🇩🇪x\r\n👋🏽\n\nend";
+        let offsets = StringOffsets::new(content);
+        let starts: Vec<_> = content.grapheme_indices(true).map(|(i, _)| i).collect();
+        for (grapheme, &start) in starts.iter().enumerate() {
+            assert_eq!(offsets.grapheme_to_utf8(grapheme), start);
+            assert_eq!(offsets.utf8_to_grapheme(start), grapheme);
+        }
+        assert_eq!(offsets.grapheme_to_utf8(starts.len()), content.len());
+        assert_eq!(offsets.grapheme_to_utf8(starts.len() + 5), content.len());
+        assert_eq!(offsets.utf8_to_grapheme(content.len()), starts.len());
+        // Offsets within the ZWJ sequence map to the cluster containing them.
+        for i in 3..21 {
+            assert_eq!(offsets.utf8_to_grapheme(i), 1, "offset {i}");
+        }
+        assert_eq!(offsets.utf8_to_grapheme_pos(30), pos(0, 4));
+        assert_eq!(offsets.utf8_to_grapheme_pos(31), pos(0, 5));
+        assert_eq!(offsets.utf8_to_grapheme_pos(33), pos(1, 0));
+        assert_eq!(offsets.grapheme_pos_to_utf8(0, 4), 30);
+        assert_eq!(offsets.grapheme_pos_to_utf8(0, 100), 33);
+        assert_eq!(offsets.grapheme_pos_to_utf8(1, 1), 41);
+        assert_eq!(offsets.grapheme_pos_to_utf8(3, 2), content.len() - 1);
+
+        let mut edited = StringOffsets::new(content);
+        edited.apply_edit(1..3, "\u{302}", content);
+        let mut edited_content = content.to_string();
+        edited_content.replace_range(1..3, "\u{302}");
+        let expected = StringOffsets::new(&edited_content);
+        for i in 0..=edited_content.len() {
+            assert_eq!(edited.utf8_to_grapheme(i), expected.utf8_to_grapheme(i));
+        }
+    }
+
+    #[cfg(feature = "display-width")]
+    #[test]
+    fn test_display_width() {
+        use unicode_width::UnicodeWidthChar;
+
+        let content = "a\t名前e\u{301}x\n😀 b\n";
+        let offsets = StringOffsets::new(content);
+        let mut col = 0;
+        for (byte_number, c) in content.char_indices() {
+            let line = offsets.utf8_to_line(byte_number);
+            if byte_number == offsets.line_to_utf8_begin(line) {
+                col = 0;
+            }
+            assert_eq!(offsets.utf8_to_display_pos(byte_number), pos(line, col));
+            let width = c.width().unwrap_or(0);
+            if width > 0 {
+                for i in 0..width {
+                    assert_eq!(offsets.display_pos_to_utf8(line, col + i), byte_number);
+                }
+            }
+            // Offsets within a character map to its first column.
+            for i in 1..c.len_utf8() {
+                assert_eq!(offsets.utf8_to_display_pos(byte_number + i), pos(line, col));
+            }
+            col += width;
+        }
+        assert_eq!(offsets.line_display_width(0), 7);
+        assert_eq!(offsets.line_display_width(1), 4);
+        assert_eq!(offsets.display_pos_to_utf8(0, 7), 13);
+        assert_eq!(offsets.display_pos_to_utf8(0, 100), 13);
+        assert_eq!(offsets.display_pos_to_utf8(5, 0), content.len());
+
+        let mut edited = StringOffsets::new(content);
+        edited.apply_edit(2..5, "ab", content);
+        assert_eq!(edited.utf8_to_display_pos(4), pos(0, 3));
+        assert_eq!(edited.line_display_width(0), 7);
+    }
+
+    #[cfg(feature = "visual-columns")]
+    #[test]
+    fn test_visual_columns() {
+        let content = "\tab\t\tc\u{301}d\t\n  \te\n";
+        let offsets = StringOffsets::new(content);
+        for tab_width in [1, 2, 4, 8] {
+            // Expand the tabs and compare the columns with the expanded line.
+            let mut col = 0;
+            for (byte_number, c) in content.char_indices() {
+                let line = offsets.utf8_to_line(byte_number);
+                if byte_number == offsets.line_to_utf8_begin(line) {
+                    col = 0;
+                }
+                assert_eq!(
+                    offsets.utf8_to_visual_pos(byte_number, tab_width),
+                    pos(line, col),
+                    "offset {byte_number}, tab width {tab_width}"
+                );
+                let end = if c == '\t' {
+                    (col / tab_width + 1) * tab_width
+                } else {
+                    col + 1
+                };
+                for visual_col in col..end {
+                    assert_eq!(
+                        offsets.visual_pos_to_utf8(line, visual_col, tab_width),
+                        byte_number,
+                        "column {visual_col}, tab width {tab_width}"
+                    );
+                }
+                col = end;
+            }
+        }
+        assert_eq!(offsets.visual_pos_to_utf8(0, 100, 4), 11);
+        assert_eq!(offsets.visual_pos_to_utf8(1, 100, 4), content.len());
+        assert_eq!(offsets.utf8_to_visual_pos(content.len(), 4), pos(2, 0));
+
+        let mut edited = StringOffsets::new(content);
+        edited.apply_edit(1..3, "\t", content);
+        assert_eq!(edited.utf8_to_visual_pos(3, 4), pos(0, 12));
+    }
+
+    #[test]
+    fn test_utf16_to_utf8() {
+        let content = "a👋b\n❤️🗺️\n\nx";
+        let offsets = StringOffsets::new(content);
+        let mut utf16_number = 0;
+        for (byte_number, c) in content.char_indices() {
+            assert_eq!(offsets.utf16_to_utf8(utf16_number), byte_number);
+            if c.len_utf16() == 2 {
+                // Between the two units of a surrogate pair.
+                assert_eq!(offsets.utf16_to_utf8(utf16_number + 1), byte_number);
+            }
+            let pos = offsets.utf8_to_utf16_pos(byte_number);
+            assert_eq!(offsets.utf16_pos_to_utf8(pos.line, pos.col), byte_number);
+            utf16_number += c.len_utf16();
+        }
+        assert_eq!(offsets.utf16_to_utf8(utf16_number), content.len());
+        assert_eq!(offsets.utf16_to_utf8(utf16_number + 10), content.len());
+        assert_eq!(offsets.utf16_pos_to_utf8(0, 10), 7);
+        assert_eq!(offsets.utf16_pos_to_utf8(2, 1), 22);
+        assert_eq!(offsets.utf16_pos_to_utf8(3, 1), content.len());
+        assert_eq!(offsets.utf16_pos_to_utf8(4, 0), content.len());
+    }
+
+    #[test]
+    fn test_utf16_ranges() {
+        let content = "a👋b\n❤️🗺️\n\nx";
+        let offsets = StringOffsets::new(content);
+        assert_eq!(offsets.utf8s_to_utf16s(1..13), 1..7);
+        assert_eq!(offsets.utf16s_to_utf8s(1..7), 1..13);
+        // Offsets within surrogate pairs round down to the start of the character.
+        assert_eq!(offsets.utf16s_to_utf8s(2..8), 1..13);
+        assert_eq!(offsets.utf8s_to_utf16s(20..30), 10..13);
+        assert_eq!(offsets.utf16s_to_utf8s(10..30), 20..23);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "reversed range")]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn test_reversed_range() {
+        StringOffsets::new("abc").utf8s_to_chars(2..1);
+    }
+
+    #[test]
+    fn test_char_boundaries() {
+        let content = "a👋\n❤️\u{80}x";
+        let offsets = StringOffsets::new(content);
+        for byte_number in 0..=content.len() + 2 {
+            let floor = (0..=byte_number.min(content.len()))
+                .rev()
+                .find(|&i| content.is_char_boundary(i))
+                .unwrap();
+            let ceil = (byte_number.min(content.len())..=content.len())
+                .find(|&i| content.is_char_boundary(i))
+                .unwrap();
+            assert_eq!(offsets.floor_char_boundary(byte_number), floor);
+            assert_eq!(offsets.ceil_char_boundary(byte_number), ceil);
+        }
+        assert_eq!(StringOffsets::new("").ceil_char_boundary(1), 0);
+    }
+
+    #[test]
+    fn test_position_encodings() {
+        let content = "a👋b\n❤️🗺️\n\nx";
+        let offsets = StringOffsets::new(content);
+        for encoding in [
+            PositionEncoding::Utf8,
+            PositionEncoding::Utf16,
+            PositionEncoding::Utf32,
+        ] {
+            let mut col = 0;
+            for (byte_number, c) in content.char_indices() {
+                let line = offsets.utf8_to_line(byte_number);
+                if byte_number == offsets.line_to_utf8_begin(line) {
+                    col = 0;
+                }
+                let width = match encoding {
+                    PositionEncoding::Utf8 => c.len_utf8(),
+                    PositionEncoding::Utf16 => c.len_utf16(),
+                    PositionEncoding::Utf32 => 1,
+                };
+                assert_eq!(
+                    offsets.offset_to_position(byte_number, encoding),
+                    pos(line, col)
+                );
+                for i in 0..width {
+                    assert_eq!(
+                        offsets.position_to_offset(pos(line, col + i), encoding),
+                        byte_number,
+                        "{encoding:?}"
+                    );
+                }
+                col += width;
+            }
+            assert_eq!(
+                offsets.offset_to_position(content.len() + 5, encoding),
+                pos(4, 0)
+            );
+            assert_eq!(offsets.position_to_offset(pos(0, 100), encoding), 7);
+            assert_eq!(
+                offsets.position_to_offset(pos(4, 0), encoding),
+                content.len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_heap_size_bytes() {
+        let small = StringOffsets::new("a\nb\n");
+        let large = StringOffsets::new(&"abc\n".repeat(10000));
+        // Every non-empty bitrank needs at least one block with 2KiB of bits.
+        assert!(small.heap_size_bytes() >= 3 * 2048);
+        assert!(large.heap_size_bytes() > 10000 * 4 + small.heap_size_bytes());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let content = "☀️hello\n🗺️world\n  \n";
+        let offsets = StringOffsets::new(content);
+        let json = serde_json::to_string(&offsets).unwrap();
+        let decoded: StringOffsets = serde_json::from_str(&json).unwrap();
+        for i in 0..=content.len() {
+            assert_eq!(decoded.utf8_to_utf16_pos(i), offsets.utf8_to_utf16_pos(i));
+            assert_eq!(decoded.utf8_to_char_pos(i), offsets.utf8_to_char_pos(i));
+        }
+        assert!(decoded.only_whitespaces(2));
+        // Edited converters are serialized like new ones.
+        let mut edited = StringOffsets::new("☀️hello\nworld\n  \n");
+        edited.apply_edit(12..12, "🗺️", "☀️hello\nworld\n  \n");
+        assert_eq!(serde_json::to_string(&edited).unwrap(), json);
+        // Line tables which don't match the bitranks are rejected.
+        let json = serde_json::to_string(&StringOffsets::new("ab\n")).unwrap();
+        let tampered = json
+            .replace(r#""len":3"#, r#""len":40000"#)
+            .replace(r#""begins":[0]"#, r#""begins":[0,40000]"#)
+            .replace(
+                r#""whitespace_only":[false]"#,
+                r#""whitespace_only":[false,false]"#,
+            );
+        assert_ne!(tampered, json);
+        assert!(serde_json::from_str::<StringOffsets>(&tampered).is_err());
+        let tampered = json.replace(r#""len":3"#, r#""len":40000"#);
+        assert!(serde_json::from_str::<StringOffsets>(&tampered).is_err());
+        let pos: Pos = serde_json::from_str(r#"{"line":1,"col":2}"#).unwrap();
+        assert_eq!(pos, Pos { line: 1, col: 2 });
+        let encoding: PositionEncoding = serde_json::from_str(r#""utf-32""#).unwrap();
+        assert_eq!(encoding, PositionEncoding::Utf32);
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn test_rkyv_archived() {
+        let content = "☀️hello\n🗺️world\n  \n";
+        let mut offsets = StringOffsets::new("☀️hello\nworld\n  \n");
+        offsets.apply_edit(12..12, "🗺️", "☀️hello\nworld\n  \n");
+        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&offsets).unwrap();
+        // Edited converters are archived like new ones.
+        assert_eq!(
+            bytes[..],
+            rkyv::to_bytes::<rkyv::rancor::Error>(&StringOffsets::new(content)).unwrap()[..]
+        );
+        let archived = rkyv::access::<ArchivedStringOffsets, rkyv::rancor::Error>(&bytes).unwrap();
+        assert_eq!(archived.lines(), offsets.lines());
+        for i in 0..=content.len() + 1 {
+            assert_eq!(archived.utf8_to_utf16_pos(i), offsets.utf8_to_utf16_pos(i));
+            assert_eq!(archived.utf8_to_char_pos(i), offsets.utf8_to_char_pos(i));
+        }
+        // Truncated archives fail validation.
+        assert!(rkyv::access::<ArchivedStringOffsets, rkyv::rancor::Error>(
+            &bytes[..bytes.len() / 2]
+        )
+        .is_err());
+        // So do line tables which don't match the bitranks.
+        let mut tampered = StringOffsets::new("ab\n");
+        tampered.line_table = LineTable::new(vec![0, 40000], vec![false, false], 40000);
+        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&tampered).unwrap();
+        assert!(rkyv::access::<ArchivedStringOffsets, rkyv::rancor::Error>(&bytes).is_err());
+    }
 }
@@ -0,0 +1,158 @@
+//! The start and the whitespace flag of every line, stored with a gap at the last edit like the
+//! bitranks, see [`crate::bitrank::GapBitRank`].
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+#[cfg(any(feature = "serde", feature = "rkyv"))]
+mod serialize;
+
+/// The byte position at which every line starts and whether it consists only of whitespace
+/// characters.
+///
+/// Both vectors have a gap at the lines of the last edit. The lines behind the gap store their
+/// distance from the end of the string instead of their start, so that they stay valid when an
+/// edit in front of them changes the length of the string.
+pub(crate) struct LineTable {
+    begins: Vec<u32>,
+    whitespace_only: Vec<bool>,
+    /// The first line in the gap.
+    gap_start: usize,
+    /// The index of line `gap_start` in the vectors.
+    gap_end: usize,
+    /// The length of the string.
+    len: u32,
+}
+
+impl LineTable {
+    /// Creates the lines from the start and the whitespace flag of every line and the length of
+    /// the string.
+    pub(crate) fn new(begins: Vec<u32>, whitespace_only: Vec<bool>, len: u32) -> Self {
+        debug_assert_eq!(begins.len(), whitespace_only.len());
+        // All lines are in front of the empty gap.
+        let count = begins.len();
+        Self {
+            begins,
+            whitespace_only,
+            gap_start: count,
+            gap_end: count,
+            len,
+        }
+    }
+
+    fn gap_len(&self) -> usize {
+        self.gap_end - self.gap_start
+    }
+
+    /// The number of lines.
+    pub(crate) fn count(&self) -> usize {
+        self.begins.len() - self.gap_len()
+    }
+
+    /// The length of the string.
+    pub(crate) fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// The byte position at which the line starts, or the length of the string for the line after
+    /// the last one.
+    pub(crate) fn begin(&self, line_number: usize) -> u32 {
+        if line_number < self.gap_start {
+            self.begins[line_number]
+        } else {
+            self.begins
+                .get(line_number + self.gap_len())
+                .map_or(self.len, |distance| self.len - distance)
+        }
+    }
+
+    /// The same as [`LineTable::begin`], but without bounds checks.
+    ///
+    /// # Safety
+    ///
+    /// `line_number` must not be greater than [`LineTable::count`].
+    pub(crate) unsafe fn begin_unchecked(&self, line_number: usize) -> u32 {
+        if line_number < self.gap_start {
+            // SAFETY: The lines in front of the gap are stored at their index.
+            unsafe { *self.begins.get_unchecked(line_number) }
+        } else if line_number < self.count() {
+            // SAFETY: The lines behind the gap are stored behind it, and there are `count` lines.
+            self.len - unsafe { *self.begins.get_unchecked(line_number + self.gap_len()) }
+        } else {
+            self.len
+        }
+    }
+
+    /// Whether the line consists only of whitespace characters, or `None` past the last line.
+    pub(crate) fn whitespace_only(&self, line_number: usize) -> Option<bool> {
+        if line_number < self.gap_start {
+            Some(self.whitespace_only[line_number])
+        } else {
+            self.whitespace_only
+                .get(line_number + self.gap_len())
+                .copied()
+        }
+    }
+
+    pub(crate) fn heap_size_bytes(&self) -> usize {
+        self.begins.capacity() * core::mem::size_of::<u32>()
+            + self.whitespace_only.capacity() * core::mem::size_of::<bool>()
+    }
+
+    /// Replaces the lines in `range` with the lines of `replacement`, whose positions are shifted
+    /// by `offset`, the start of the first replaced line.
+    pub(crate) fn splice(&mut self, range: Range<usize>, replacement: &LineTable, offset: u32) {
+        assert!(
+            range.start <= range.end && range.end <= self.count(),
+            "invalid line range {range:?}"
+        );
+        let removed = self.begin(range.end) - self.begin(range.start);
+        self.move_gap(range.start);
+        self.gap_end += range.len();
+        self.len = self.len - removed + replacement.len;
+
+        let count = replacement.count();
+        let missing = count.saturating_sub(self.gap_len());
+        if missing > 0 {
+            // Grow the gap by a fraction of the lines, so that growing takes amortized constant
+            // time.
+            let grow = missing.max(self.begins.len() / 8);
+            let at = self.gap_end;
+            self.begins.splice(at..at, core::iter::repeat_n(0, grow));
+            self.whitespace_only
+                .splice(at..at, core::iter::repeat_n(false, grow));
+            self.gap_end += grow;
+        }
+        for line in 0..count {
+            self.begins[self.gap_start + line] = replacement.begin(line) + offset;
+            self.whitespace_only[self.gap_start + line] =
+                replacement.whitespace_only(line).unwrap_or(true);
+        }
+        self.gap_start += count;
+    }
+
+    /// Moves the gap to `line_number` by moving the lines between its current and its new
+    /// position to the other side of the gap.
+    ///
+    /// Even an empty gap has to be moved, since the lines on its two sides are stored
+    /// differently.
+    fn move_gap(&mut self, line_number: usize) {
+        let gap_len = self.gap_len();
+        if line_number < self.gap_start {
+            // The lines move behind the gap, so they store their distance from the end.
+            for line in (line_number..self.gap_start).rev() {
+                self.begins[line + gap_len] = self.len - self.begins[line];
+            }
+            self.whitespace_only
+                .copy_within(line_number..self.gap_start, line_number + gap_len);
+        } else if line_number > self.gap_start {
+            for line in self.gap_start..line_number {
+                self.begins[line] = self.len - self.begins[line + gap_len];
+            }
+            self.whitespace_only
+                .copy_within(self.gap_end..line_number + gap_len, self.gap_start);
+        }
+        self.gap_start = line_number;
+        self.gap_end = line_number + gap_len;
+    }
+}
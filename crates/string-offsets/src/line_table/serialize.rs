@@ -0,0 +1,187 @@
+//! The serialized form of a [`LineTable`].
+//!
+//! A [`LineTable`] is serialized and archived as [`Lines`] without the gap, so that the
+//! serialized data doesn't depend on the edit history and can't describe an invalid gap.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::LineTable;
+
+/// The lines of a [`LineTable`] without the gap, which is the form in which it is serialized.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    rkyv(bytecheck(verify))
+)]
+pub(crate) struct Lines {
+    begins: Vec<u32>,
+    whitespace_only: Vec<bool>,
+    len: u32,
+}
+
+impl From<&LineTable> for Lines {
+    fn from(table: &LineTable) -> Self {
+        let count = table.count();
+        Self {
+            begins: (0..count).map(|line| table.begin(line)).collect(),
+            whitespace_only: (0..count)
+                .filter_map(|line| table.whitespace_only(line))
+                .collect(),
+            len: table.len,
+        }
+    }
+}
+
+impl From<Lines> for LineTable {
+    /// Converts lines which passed [`check_lines`].
+    fn from(lines: Lines) -> Self {
+        Self::new(lines.begins, lines.whitespace_only, lines.len)
+    }
+}
+
+/// An error which is returned when deserialized lines are inconsistent.
+#[derive(Debug)]
+struct InvalidLines;
+
+impl fmt::Display for InvalidLines {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid line table")
+    }
+}
+
+impl core::error::Error for InvalidLines {}
+
+/// Checks that there is a whitespace flag for every line and that the lines start at 0 and at
+/// non-decreasing positions up to the length of the string.
+fn check_lines(
+    begins: impl ExactSizeIterator<Item = u32>,
+    whitespace_only: usize,
+    len: u32,
+) -> Result<(), InvalidLines> {
+    if begins.len() != whitespace_only {
+        return Err(InvalidLines);
+    }
+    let mut previous = None;
+    for begin in begins {
+        if begin > len || previous.map_or(begin != 0, |previous| begin < previous) {
+            return Err(InvalidLines);
+        }
+        previous = Some(begin);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for LineTable {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&Lines::from(self), serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for LineTable {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let lines: Lines = serde::Deserialize::deserialize(deserializer)?;
+        check_lines(
+            lines.begins.iter().copied(),
+            lines.whitespace_only.len(),
+            lines.len,
+        )
+        .map_err(serde::de::Error::custom)?;
+        Ok(lines.into())
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl rkyv::Archive for LineTable {
+    type Archived = ArchivedLines;
+    type Resolver = LinesResolver;
+
+    fn resolve(&self, resolver: Self::Resolver, out: rkyv::Place<Self::Archived>) {
+        // The lines are collected a second time, since the resolver only refers to the
+        // serialized vectors.
+        rkyv::Archive::resolve(&Lines::from(self), resolver, out);
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<S: rkyv::rancor::Fallible + ?Sized> rkyv::Serialize<S> for LineTable
+where
+    Lines: rkyv::Serialize<S>,
+{
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        rkyv::Serialize::serialize(&Lines::from(self), serializer)
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<D: rkyv::rancor::Fallible + ?Sized> rkyv::Deserialize<LineTable, D> for ArchivedLines
+where
+    ArchivedLines: rkyv::Deserialize<Lines, D>,
+{
+    fn deserialize(&self, deserializer: &mut D) -> Result<LineTable, D::Error> {
+        let lines: Lines = rkyv::Deserialize::deserialize(self, deserializer)?;
+        Ok(lines.into())
+    }
+}
+
+// SAFETY: `verify` only rejects archives, it doesn't rely on anything besides the checked bytes.
+#[cfg(feature = "rkyv")]
+unsafe impl<C: rkyv::rancor::Fallible + ?Sized> rkyv::bytecheck::Verify<C> for ArchivedLines
+where
+    C::Error: rkyv::rancor::Source,
+{
+    fn verify(&self, _context: &mut C) -> Result<(), C::Error> {
+        check_lines(
+            self.begins.iter().map(|begin| begin.to_native()),
+            self.whitespace_only.len(),
+            self.len.to_native(),
+        )
+        .map_err(<C::Error as rkyv::rancor::Source>::new)
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl ArchivedLines {
+    /// The number of lines, see [`LineTable::count`].
+    pub(crate) fn count(&self) -> usize {
+        self.begins.len()
+    }
+
+    /// The length of the string, see [`LineTable::len`].
+    pub(crate) fn len(&self) -> u32 {
+        self.len.to_native()
+    }
+
+    /// The byte position at which the line starts, see [`LineTable::begin`].
+    pub(crate) fn begin(&self, line_number: usize) -> u32 {
+        self.begins
+            .get(line_number)
+            .map_or(self.len(), |begin| begin.to_native())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn test_rkyv_invalid_lines() {
+        use super::{ArchivedLines, Lines};
+
+        let access = |begins: Vec<u32>| {
+            let lines = Lines {
+                whitespace_only: vec![false; begins.len()],
+                begins,
+                len: 10,
+            };
+            let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&lines).unwrap();
+            rkyv::access::<ArchivedLines, rkyv::rancor::Error>(&bytes).is_ok()
+        };
+        assert!(access(vec![0, 4, 4, 10]));
+        assert!(!access(vec![1, 4]));
+        assert!(!access(vec![0, 4, 3]));
+        assert!(!access(vec![0, 11]));
+    }
+}
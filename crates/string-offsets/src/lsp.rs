@@ -0,0 +1,80 @@
+//! Conversions between byte offsets and the positions of the Language Server Protocol, as defined
+//! by [`lsp-types`](https://docs.rs/lsp-types).
+
+use core::ops::Range;
+
+use crate::StringOffsets;
+
+impl StringOffsets {
+    /// Converts an LSP position, whose character offset counts UTF-16 code units like every LSP
+    /// client supports, to a UTF-8 offset. See [`StringOffsets::utf16_pos_to_utf8`].
+    ///
+    /// This requires the `lsp-types` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsp_types::Position;
+    /// use string_offsets::StringOffsets;
+    ///
+    /// let offsets = StringOffsets::new("a\n🗺️ map\n");
+    /// assert_eq!(offsets.position_to_utf8(Position::new(1, 3)), 9);
+    /// ```
+    pub fn position_to_utf8(&self, position: lsp_types::Position) -> usize {
+        self.utf16_pos_to_utf8(position.line as usize, position.character as usize)
+    }
+
+    /// Converts a UTF-8 offset range to an LSP range with UTF-16 character offsets. Lines and
+    /// character offsets which don't fit into a `u32` saturate.
+    ///
+    /// This requires the `lsp-types` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsp_types::{Position, Range};
+    /// use string_offsets::StringOffsets;
+    ///
+    /// let offsets = StringOffsets::new("a\n🗺️ map\n");
+    /// assert_eq!(
+    ///     offsets.utf8_range_to_lsp(9..13),
+    ///     Range::new(Position::new(1, 3), Position::new(1, 7)),
+    /// );
+    /// ```
+    pub fn utf8_range_to_lsp(&self, bytes: Range<usize>) -> lsp_types::Range {
+        let position = |byte_number| {
+            let pos = self.utf8_to_utf16_pos(byte_number);
+            lsp_types::Position::new(saturate(pos.line), saturate(pos.col))
+        };
+        lsp_types::Range::new(position(bytes.start), position(bytes.end))
+    }
+}
+
+fn saturate(n: usize) -> u32 {
+    u32::try_from(n).unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{Position, Range};
+
+    use crate::StringOffsets;
+
+    #[test]
+    fn test_lsp_types() {
+        let content = "a👋b\n❤️🗺️\n\nx";
+        let offsets = StringOffsets::new(content);
+        for (byte_number, _) in content.char_indices() {
+            let range = offsets.utf8_range_to_lsp(byte_number..content.len());
+            assert_eq!(offsets.position_to_utf8(range.start), byte_number);
+            assert_eq!(offsets.position_to_utf8(range.end), content.len());
+        }
+        assert_eq!(
+            offsets.utf8_range_to_lsp(1..7),
+            Range::new(Position::new(0, 1), Position::new(1, 0))
+        );
+        // Character offsets past the end of the line map to the end of the line.
+        assert_eq!(offsets.position_to_utf8(Position::new(0, 10)), 7);
+        assert_eq!(offsets.position_to_utf8(Position::new(9, 0)), content.len());
+    }
+}
@@ -0,0 +1,208 @@
+//! A named source text together with its [`StringOffsets`], for handing the text to diagnostic
+//! renderers without building a second line index.
+
+use core::fmt;
+
+use crate::StringOffsets;
+
+/// A named source text together with the [`StringOffsets`] describing it.
+///
+/// This implements `codespan_reporting::files::Files` like `SimpleFile` does and
+/// `miette::SourceCode` like `NamedSource` does, but looks lines up in the [`StringOffsets`], so
+/// that diagnostics can be rendered without a second line index or scanning the source for each
+/// span.
+///
+/// This requires the `codespan-reporting` or the `miette` feature.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "codespan-reporting")] {
+/// use codespan_reporting::files::Files;
+/// use string_offsets::SourceFile;
+///
+/// let file = SourceFile::new("main.rs", "fn main() {\n    oops\n}\n");
+/// assert_eq!(file.line_index((), 16).ok(), Some(1));
+/// assert_eq!(file.line_range((), 1).ok(), Some(12..21));
+/// # }
+/// ```
+pub struct SourceFile<Name, Source> {
+    name: Name,
+    source: Source,
+    offsets: StringOffsets,
+}
+
+impl<Name, Source> SourceFile<Name, Source>
+where
+    Name: fmt::Display,
+    Source: AsRef<str>,
+{
+    /// Creates a source file and the [`StringOffsets`] of its text.
+    pub fn new(name: Name, source: Source) -> Self {
+        let offsets = StringOffsets::new(source.as_ref());
+        Self {
+            name,
+            source,
+            offsets,
+        }
+    }
+
+    /// The name of the file.
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+
+    /// The source text of the file.
+    pub fn source(&self) -> &Source {
+        &self.source
+    }
+
+    /// The [`StringOffsets`] of the source text.
+    pub fn offsets(&self) -> &StringOffsets {
+        &self.offsets
+    }
+
+    /// The index of the last line as counted by `codespan-reporting`. Unlike
+    /// [`StringOffsets::lines`], this counts the empty line after a trailing newline, which is
+    /// where diagnostics at the end of the file are shown.
+    #[cfg(feature = "codespan-reporting")]
+    fn last_line(&self) -> usize {
+        let source = self.source.as_ref();
+        if source.is_empty() || source.ends_with('\n') {
+            self.offsets.lines()
+        } else {
+            self.offsets.lines() - 1
+        }
+    }
+}
+
+#[cfg(feature = "codespan-reporting")]
+impl<'a, Name, Source> codespan_reporting::files::Files<'a> for SourceFile<Name, Source>
+where
+    Name: 'a + fmt::Display + Clone,
+    Source: 'a + AsRef<str>,
+{
+    type FileId = ();
+    type Name = Name;
+    type Source = &'a str;
+
+    fn name(&'a self, (): ()) -> Result<Name, codespan_reporting::files::Error> {
+        Ok(self.name.clone())
+    }
+
+    fn source(&'a self, (): ()) -> Result<&'a str, codespan_reporting::files::Error> {
+        Ok(self.source.as_ref())
+    }
+
+    fn line_index(
+        &'a self,
+        (): (),
+        byte_index: usize,
+    ) -> Result<usize, codespan_reporting::files::Error> {
+        Ok(self.offsets.utf8_to_line(byte_index).min(self.last_line()))
+    }
+
+    fn line_range(
+        &'a self,
+        (): (),
+        line_index: usize,
+    ) -> Result<core::ops::Range<usize>, codespan_reporting::files::Error> {
+        if line_index > self.last_line() {
+            return Err(codespan_reporting::files::Error::LineTooLarge {
+                given: line_index,
+                max: self.last_line(),
+            });
+        }
+        Ok(self.offsets.line_to_utf8s(line_index))
+    }
+}
+
+#[cfg(feature = "miette")]
+impl<Name, Source> miette::SourceCode for SourceFile<Name, Source>
+where
+    Name: fmt::Display + Send + Sync,
+    Source: AsRef<str> + Send + Sync,
+{
+    fn read_span<'a>(
+        &'a self,
+        span: &miette::SourceSpan,
+        context_lines_before: usize,
+        context_lines_after: usize,
+    ) -> Result<Box<dyn miette::SpanContents<'a> + 'a>, miette::MietteError> {
+        let source = self.source.as_ref();
+        let bytes = span.offset()..span.offset() + span.len();
+        if bytes.end > source.len() {
+            return Err(miette::MietteError::OutOfBounds);
+        }
+        let snippet =
+            self.offsets
+                .utf8s_with_context(bytes, context_lines_before, context_lines_after);
+        // The snippet consists of whole lines, so it starts at column 0.
+        let lines = self.offsets.utf8s_to_lines(snippet.clone());
+        Ok(Box::new(miette::MietteSpanContents::new_named(
+            self.name.to_string(),
+            &source.as_bytes()[snippet.clone()],
+            snippet.into(),
+            lines.start,
+            0,
+            lines.len(),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SourceFile;
+
+    #[cfg(feature = "codespan-reporting")]
+    #[test]
+    fn test_codespan_files() {
+        use codespan_reporting::files::{Files, SimpleFile};
+
+        for source in ["", "a", "\n", "a\n❤️\n\nb", "a\r\nb\n"] {
+            let file = SourceFile::new("test", source);
+            let simple = SimpleFile::new("test", source);
+            for byte_index in 0..=source.len() + 1 {
+                assert_eq!(
+                    file.line_index((), byte_index).ok(),
+                    simple.line_index((), byte_index).ok()
+                );
+            }
+            for line_index in 0..source.len() + 3 {
+                assert_eq!(
+                    file.line_range((), line_index).ok(),
+                    simple.line_range((), line_index).ok()
+                );
+            }
+            assert_eq!(
+                file.location((), source.len()).ok(),
+                simple.location((), source.len()).ok()
+            );
+        }
+    }
+
+    #[cfg(feature = "miette")]
+    #[test]
+    fn test_miette_source_code() {
+        use miette::{MietteError, SourceCode, SourceSpan};
+
+        let file = SourceFile::new("test", "a\n❤️b\n\nc\nd");
+        let contents = file.read_span(&SourceSpan::from(8..9), 1, 1).unwrap();
+        assert_eq!(contents.data(), "a\n❤️b\n\n".as_bytes());
+        assert_eq!(*contents.span(), SourceSpan::from(0..11));
+        assert_eq!((contents.line(), contents.column()), (0, 0));
+        assert_eq!(contents.line_count(), 3);
+        assert_eq!(contents.name(), Some("test"));
+
+        let contents = file.read_span(&SourceSpan::from(12..14), 0, 5).unwrap();
+        assert_eq!(contents.data(), b"c\nd");
+        assert_eq!((contents.line(), contents.line_count()), (3, 2));
+        // An empty span at the end still shows the last line.
+        let contents = file.read_span(&SourceSpan::from(14..14), 0, 0).unwrap();
+        assert_eq!(contents.data(), b"d");
+        assert!(matches!(
+            file.read_span(&SourceSpan::from(14..15), 0, 0),
+            Err(MietteError::OutOfBounds)
+        ));
+    }
+}
@@ -0,0 +1,141 @@
+//! [`proptest`](mod@proptest) strategies for property tests against [`BitRank`] and
+//! [`StringOffsets`](crate::StringOffsets).
+//!
+//! Strategies for `BitRank` yield the elements alongside the set, which serve as the oracle for
+//! its answers. A `StringOffsets` is built from a generated [`text`].
+//!
+//! This requires the `testing` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use proptest::prelude::*;
+//! use string_offsets::testing::bit_ranks;
+//!
+//! proptest!(|((positions, br) in bit_ranks(0..100_000, 0..100))| {
+//!     for (rank, &position) in positions.iter().enumerate() {
+//!         prop_assert_eq!(br.select(rank), Some(position));
+//!     }
+//! });
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use proptest::char;
+use proptest::collection::{btree_set, vec, SizeRange};
+use proptest::prelude::*;
+
+use crate::BitRank;
+
+/// The number of bits covered by a block of a [`BitRank`] with the default block size.
+const BITS_PER_BLOCK: usize = 128 * 128;
+
+/// Strictly increasing positions within `universe`, whose number is in `len`.
+///
+/// `universe` must contain at least as many positions as the largest length.
+pub fn sorted_positions(
+    universe: Range<usize>,
+    len: impl Into<SizeRange>,
+) -> impl Strategy<Value = Vec<usize>> {
+    btree_set(universe, len).prop_map(|positions| positions.into_iter().collect())
+}
+
+/// Strictly increasing positions spread over a few blocks, including positions right around the
+/// block boundaries and runs of consecutive positions, which are the edge cases of the rank and
+/// select implementations.
+pub fn boundary_positions() -> impl Strategy<Value = Vec<usize>> {
+    let block = 0..4usize;
+    let near_boundary = (block.clone(), -3..3isize)
+        .prop_map(|(block, offset)| (block * BITS_PER_BLOCK).saturating_add_signed(offset));
+    let run = (block, 0..BITS_PER_BLOCK, 0..300usize)
+        .prop_map(|(block, start, len)| (block * BITS_PER_BLOCK + start..).take(len));
+    (
+        sorted_positions(0..4 * BITS_PER_BLOCK, 0..100),
+        vec(near_boundary, 0..10),
+        vec(run, 0..3),
+    )
+        .prop_map(|(mut positions, near_boundary, runs)| {
+            positions.extend(near_boundary);
+            positions.extend(runs.into_iter().flatten());
+            positions.sort_unstable();
+            positions.dedup();
+            positions
+        })
+}
+
+/// A [`BitRank`] together with its elements in increasing order. See [`sorted_positions`] for the
+/// arguments.
+pub fn bit_ranks(
+    universe: Range<usize>,
+    len: impl Into<SizeRange>,
+) -> impl Strategy<Value = (Vec<usize>, BitRank)> {
+    sorted_positions(universe, len).prop_map(with_bit_rank)
+}
+
+/// A [`BitRank`] built from [`boundary_positions`], together with its elements.
+pub fn boundary_bit_ranks() -> impl Strategy<Value = (Vec<usize>, BitRank)> {
+    boundary_positions().prop_map(with_bit_rank)
+}
+
+fn with_bit_rank(positions: Vec<usize>) -> (Vec<usize>, BitRank) {
+    let br = positions.iter().copied().collect();
+    (positions, br)
+}
+
+/// Strings mixing characters of all UTF-8 and UTF-16 lengths with line breaks, with up to
+/// `max_chars` characters.
+pub fn text(max_chars: usize) -> impl Strategy<Value = String> {
+    let chars = prop_oneof![
+        4 => char::range('a', 'z'),
+        1 => Just('\n'),
+        1 => Just('\r'),
+        1 => char::range('à', 'ÿ'),
+        1 => char::range('一', '龥'),
+        1 => char::range('😀', '🙏'),
+        1 => any::<char>(),
+    ];
+    vec(chars, 0..=max_chars).prop_map(String::from_iter)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{bit_ranks, boundary_bit_ranks, text};
+    use crate::StringOffsets;
+
+    proptest! {
+        #[test]
+        fn test_bit_ranks_match_positions((positions, br) in bit_ranks(0..100_000, 0..200)) {
+            prop_assert_eq!(br.iter().collect::<Vec<_>>(), positions.clone());
+            for (rank, &position) in positions.iter().enumerate() {
+                prop_assert_eq!(br.rank(position), rank);
+                prop_assert_eq!(br.select(rank), Some(position));
+            }
+        }
+
+        #[test]
+        fn test_boundary_bit_ranks_match_positions((positions, br) in boundary_bit_ranks()) {
+            prop_assert!(positions.windows(2).all(|w| w[0] < w[1]));
+            prop_assert_eq!(br.max_rank(), positions.len());
+            for (rank, &position) in positions.iter().enumerate() {
+                prop_assert_eq!(br.rank(position + 1), rank + 1);
+                prop_assert_eq!(br.select(rank), Some(position));
+            }
+        }
+
+        #[test]
+        fn test_string_offsets_match_text(text in text(100)) {
+            let offsets = StringOffsets::new(&text);
+            let chars = text.chars().count();
+            prop_assert_eq!(offsets.utf8_to_char(text.len()), chars);
+            prop_assert_eq!(offsets.char_to_utf8(chars), text.len());
+            prop_assert_eq!(
+                offsets.utf8_to_utf16(text.len()),
+                text.encode_utf16().count()
+            );
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Conversions from and to the [`text-size`](https://docs.rs/text-size) types which
+//! rust-analyzer-style tooling uses for UTF-8 offsets and ranges.
+
+use core::ops::Range;
+
+use ::text_size::{TextRange, TextSize};
+
+use crate::{Offset, Pos, PositionEncoding, StringOffsets, Utf8Offset};
+
+/// A [`TextSize`] is a UTF-8 offset. Offsets which don't fit into its `u32` saturate.
+///
+/// This requires the `text-size` feature.
+impl Offset for TextSize {
+    fn to_utf8(self, _offsets: &StringOffsets) -> usize {
+        self.into()
+    }
+
+    fn from_utf8(_offsets: &StringOffsets, byte_number: usize) -> Self {
+        saturate(byte_number)
+    }
+}
+
+impl StringOffsets {
+    /// The [`TextRange`] of a line including the newline, see [`StringOffsets::line_to_utf8s`].
+    ///
+    /// This requires the `text-size` feature.
+    pub fn line_to_text_range(&self, line_number: usize) -> TextRange {
+        to_text_range(self.line_to_utf8s(line_number))
+    }
+
+    /// The range of line numbers containing a [`TextRange`], see
+    /// [`StringOffsets::utf8s_to_lines`].
+    ///
+    /// This requires the `text-size` feature.
+    pub fn text_range_to_lines(&self, range: TextRange) -> Range<usize> {
+        self.utf8s_to_lines(range.into())
+    }
+
+    /// Converts a [`TextRange`] to a range of UTF-32 offsets, see
+    /// [`StringOffsets::utf8s_to_chars`].
+    ///
+    /// This requires the `text-size` feature.
+    pub fn text_range_to_chars(&self, range: TextRange) -> Range<usize> {
+        self.utf8s_to_chars(range.into())
+    }
+
+    /// Converts a range of UTF-32 offsets to a [`TextRange`], see
+    /// [`StringOffsets::chars_to_utf8s`].
+    ///
+    /// This requires the `text-size` feature.
+    pub fn chars_to_text_range(&self, chars: Range<usize>) -> TextRange {
+        to_text_range(self.chars_to_utf8s(chars))
+    }
+
+    /// Converts a [`TextRange`] to a range in another unit, like [`StringOffsets::convert_range`].
+    ///
+    /// This requires the `text-size` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_offsets::{StringOffsets, Utf16Offset, Utf16Range};
+    /// use text_size::{TextRange, TextSize};
+    ///
+    /// let offsets = StringOffsets::new("🗺️ map");
+    /// let range = TextRange::new(TextSize::new(7), TextSize::new(10));
+    /// let utf16: Utf16Range = offsets.convert_text_range(range);
+    /// assert_eq!(utf16, Utf16Offset(3)..Utf16Offset(6));
+    /// assert_eq!(offsets.to_text_range(utf16), range);
+    /// ```
+    pub fn convert_text_range<T: Offset>(&self, range: TextRange) -> Range<T> {
+        self.convert_range(range.start()..range.end())
+    }
+
+    /// Converts a range in any unit to a [`TextRange`], like [`StringOffsets::convert_range`].
+    ///
+    /// This requires the `text-size` feature.
+    pub fn to_text_range(&self, range: Range<impl Offset>) -> TextRange {
+        let bytes: Range<Utf8Offset> = self.convert_range(range);
+        to_text_range(bytes.start.0..bytes.end.0)
+    }
+
+    /// Converts a [`TextSize`] to a position in the given encoding, see
+    /// [`StringOffsets::offset_to_position`].
+    ///
+    /// This requires the `text-size` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_offsets::{Pos, PositionEncoding, StringOffsets};
+    /// use text_size::TextSize;
+    ///
+    /// let offsets = StringOffsets::new("a\n🗺️ map");
+    /// let pos = offsets.text_size_to_position(TextSize::new(10), PositionEncoding::Utf16);
+    /// assert_eq!(pos, Pos { line: 1, col: 4 });
+    /// assert_eq!(
+    ///     offsets.position_to_text_size(pos, PositionEncoding::Utf16),
+    ///     TextSize::new(10)
+    /// );
+    /// ```
+    pub fn text_size_to_position(&self, offset: TextSize, encoding: PositionEncoding) -> Pos {
+        self.offset_to_position(offset.into(), encoding)
+    }
+
+    /// Converts a position in the given encoding to a [`TextSize`], see
+    /// [`StringOffsets::position_to_offset`].
+    ///
+    /// This requires the `text-size` feature.
+    pub fn position_to_text_size(&self, pos: Pos, encoding: PositionEncoding) -> TextSize {
+        saturate(self.position_to_offset(pos, encoding))
+    }
+}
+
+fn saturate(byte_number: usize) -> TextSize {
+    TextSize::try_from(byte_number).unwrap_or(TextSize::new(u32::MAX))
+}
+
+fn to_text_range(bytes: Range<usize>) -> TextRange {
+    TextRange::new(saturate(bytes.start), saturate(bytes.end))
+}
+
+#[cfg(test)]
+mod tests {
+    use ::text_size::{TextRange, TextSize};
+
+    use crate::{
+        CharOffset, CharRange, LineIdx, Pos, PositionEncoding, StringOffsets, Utf16Offset,
+    };
+
+    #[test]
+    fn test_text_size() {
+        // The emoji are 7 UTF-8 bytes, 3 UTF-16 code units and 2 characters.
+        let offsets = StringOffsets::new("a🗺️\nb🗺️c");
+        let range = |start, end| TextRange::new(TextSize::new(start), TextSize::new(end));
+        assert_eq!(
+            offsets.convert::<Utf16Offset>(TextSize::new(8)),
+            Utf16Offset(4)
+        );
+        assert_eq!(offsets.convert::<TextSize>(CharOffset(3)), TextSize::new(8));
+        assert_eq!(offsets.line_of(TextSize::new(9)), LineIdx(1));
+
+        let pos = Pos { line: 1, col: 4 };
+        assert_eq!(
+            offsets.text_size_to_position(TextSize::new(17), PositionEncoding::Utf16),
+            pos
+        );
+        assert_eq!(
+            offsets.position_to_text_size(pos, PositionEncoding::Utf16),
+            TextSize::new(17)
+        );
+
+        assert_eq!(offsets.line_to_text_range(1), range(9, 18));
+        assert_eq!(offsets.text_range_to_lines(range(1, 10)), 0..2);
+        assert_eq!(offsets.text_range_to_chars(range(1, 10)), 1..5);
+        assert_eq!(offsets.chars_to_text_range(1..5), range(1, 10));
+        let chars: CharRange = offsets.convert_text_range(range(1, 10));
+        assert_eq!(chars, CharOffset(1)..CharOffset(5));
+        assert_eq!(offsets.to_text_range(chars), range(1, 10));
+        // Both ends within the surrogate pair of the map emoji round down to its start.
+        assert_eq!(
+            offsets.to_text_range(Utf16Offset(2)..Utf16Offset(2)),
+            range(1, 1)
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "reversed range")]
+    fn test_text_size_reversed_range() {
+        let offsets = StringOffsets::new("a🗺️\nb🗺️c");
+        offsets.to_text_range(Utf16Offset(6)..Utf16Offset(1));
+    }
+}
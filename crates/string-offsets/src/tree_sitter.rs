@@ -0,0 +1,114 @@
+//! Conversions between byte offsets and the points of [`tree-sitter`](https://docs.rs/tree-sitter),
+//! whose columns count UTF-8 bytes within the row.
+
+use core::ops::Range;
+
+use ::tree_sitter::{InputEdit, Point};
+
+use crate::{Pos, PositionEncoding, StringOffsets};
+
+impl StringOffsets {
+    /// Converts a UTF-8 offset to a tree-sitter point, i.e. the row and the byte offset within it.
+    /// See [`StringOffsets::offset_to_position`].
+    ///
+    /// This requires the `tree-sitter` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_offsets::StringOffsets;
+    /// use tree_sitter::Point;
+    ///
+    /// let offsets = StringOffsets::new("a\n🗺️ map\n");
+    /// assert_eq!(offsets.utf8_to_point(9), Point::new(1, 7));
+    /// ```
+    pub fn utf8_to_point(&self, byte_number: usize) -> Point {
+        let pos = self.offset_to_position(byte_number, PositionEncoding::Utf8);
+        Point::new(pos.line, pos.col)
+    }
+
+    /// Converts a tree-sitter point to a UTF-8 offset. See [`StringOffsets::position_to_offset`].
+    ///
+    /// This requires the `tree-sitter` feature.
+    pub fn point_to_utf8(&self, point: Point) -> usize {
+        let pos = Pos {
+            line: point.row,
+            col: point.column,
+        };
+        self.position_to_offset(pos, PositionEncoding::Utf8)
+    }
+
+    /// Like [`StringOffsets::apply_edit`], but also returns the edit as a tree-sitter
+    /// [`InputEdit`], so that a syntax tree of `content` can be updated with `Tree::edit`.
+    ///
+    /// This requires the `tree-sitter` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_offsets::StringOffsets;
+    /// use tree_sitter::Point;
+    ///
+    /// let mut offsets = StringOffsets::new("hello\nworld\n");
+    /// let edit = offsets.apply_tree_sitter_edit(2..9, "🗺️\n  ", "hello\nworld\n");
+    /// // The content is now "he🗺️\n  ld\n".
+    /// assert_eq!(edit.old_end_position, Point::new(1, 3));
+    /// assert_eq!(edit.new_end_position, Point::new(1, 2));
+    /// ```
+    pub fn apply_tree_sitter_edit(
+        &mut self,
+        byte_range: Range<usize>,
+        replacement: &str,
+        content: &str,
+    ) -> InputEdit {
+        let start_position = self.utf8_to_point(byte_range.start);
+        let old_end_position = self.utf8_to_point(byte_range.end);
+        let new_end_byte = byte_range.start + replacement.len();
+        let start_byte = byte_range.start;
+        let old_end_byte = byte_range.end;
+        self.apply_edit(byte_range, replacement, content);
+        InputEdit {
+            start_byte,
+            old_end_byte,
+            new_end_byte,
+            start_position,
+            old_end_position,
+            new_end_position: self.utf8_to_point(new_end_byte),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tree_sitter::{InputEdit, Point};
+
+    use crate::StringOffsets;
+
+    #[test]
+    fn test_tree_sitter() {
+        let content = "a\n❤️b\nc";
+        let mut offsets = StringOffsets::new(content);
+        for (byte_number, _) in content.char_indices().chain([(content.len(), ' ')]) {
+            let point = offsets.utf8_to_point(byte_number);
+            assert_eq!(offsets.point_to_utf8(point), byte_number);
+        }
+        assert_eq!(offsets.utf8_to_point(5), Point::new(1, 3));
+        // Columns past the end of the row map to the end of the row.
+        assert_eq!(offsets.point_to_utf8(Point::new(0, 5)), 2);
+
+        let edit = offsets.apply_tree_sitter_edit(5..9, "x\nyz", content);
+        assert_eq!(
+            edit,
+            InputEdit {
+                start_byte: 5,
+                old_end_byte: 9,
+                new_end_byte: 9,
+                start_position: Point::new(1, 3),
+                old_end_position: Point::new(1, 7),
+                new_end_position: Point::new(2, 2),
+            }
+        );
+        let edit = offsets.apply_tree_sitter_edit(0..0, "", "a\n❤x\nyz\nc");
+        assert_eq!(edit.start_position, edit.new_end_position);
+    }
+}
@@ -0,0 +1,177 @@
+//! Newtypes for offsets in the different units, so that the compiler rejects passing e.g. a UTF-16
+//! offset where a UTF-8 offset is expected.
+
+use core::ops::Range;
+
+use crate::{checked_range, StringOffsets};
+
+/// An offset in UTF-8 bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Utf8Offset(pub usize);
+
+/// An offset in UTF-16 code units.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Utf16Offset(pub usize);
+
+/// An offset in Unicode code points.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CharOffset(pub usize);
+
+/// A zero-based line number.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LineIdx(pub usize);
+
+/// A range of UTF-8 bytes.
+pub type Utf8Range = Range<Utf8Offset>;
+
+/// A range of UTF-16 code units.
+pub type Utf16Range = Range<Utf16Offset>;
+
+/// A range of Unicode code points.
+pub type CharRange = Range<CharOffset>;
+
+/// An offset type which [`StringOffsets::convert`] can convert from and to.
+pub trait Offset: Copy {
+    /// The UTF-8 offset corresponding to this offset.
+    fn to_utf8(self, offsets: &StringOffsets) -> usize;
+
+    /// The offset corresponding to the UTF-8 offset `byte_number`.
+    fn from_utf8(offsets: &StringOffsets, byte_number: usize) -> Self;
+}
+
+impl Offset for Utf8Offset {
+    fn to_utf8(self, _offsets: &StringOffsets) -> usize {
+        self.0
+    }
+
+    fn from_utf8(_offsets: &StringOffsets, byte_number: usize) -> Self {
+        Self(byte_number)
+    }
+}
+
+impl Offset for Utf16Offset {
+    fn to_utf8(self, offsets: &StringOffsets) -> usize {
+        offsets.utf16_to_utf8(self.0)
+    }
+
+    fn from_utf8(offsets: &StringOffsets, byte_number: usize) -> Self {
+        Self(offsets.utf8_to_utf16(byte_number))
+    }
+}
+
+impl Offset for CharOffset {
+    fn to_utf8(self, offsets: &StringOffsets) -> usize {
+        offsets.char_to_utf8(self.0)
+    }
+
+    fn from_utf8(offsets: &StringOffsets, byte_number: usize) -> Self {
+        Self(offsets.utf8_to_char(byte_number))
+    }
+}
+
+impl StringOffsets {
+    /// Converts an offset to another unit, e.g. a [`Utf16Offset`] to a [`Utf8Offset`]. The
+    /// conversions are the same as those of the `usize` based methods.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_offsets::{CharOffset, StringOffsets, Utf16Offset, Utf8Offset};
+    ///
+    /// let offsets = StringOffsets::new("🗺️ map");
+    /// let byte: Utf8Offset = offsets.convert(Utf16Offset(4));
+    /// assert_eq!(byte, Utf8Offset(8));
+    /// assert_eq!(offsets.convert::<CharOffset>(byte), CharOffset(3));
+    /// ```
+    pub fn convert<T: Offset>(&self, offset: impl Offset) -> T {
+        T::from_utf8(self, offset.to_utf8(self))
+    }
+
+    /// Converts a range to another unit, e.g. a [`Utf16Range`] to a [`Utf8Range`].
+    ///
+    /// Both ends are converted like [`StringOffsets::convert`] does. Like the other range
+    /// conversions, e.g. [`StringOffsets::utf8s_to_chars`], this checks in debug builds that the
+    /// range isn't reversed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use string_offsets::{StringOffsets, Utf16Offset, Utf8Offset, Utf8Range};
+    ///
+    /// let offsets = StringOffsets::new("🗺️ map");
+    /// let bytes: Utf8Range = offsets.convert_range(Utf16Offset(3)..Utf16Offset(6));
+    /// assert_eq!(bytes, Utf8Offset(7)..Utf8Offset(10));
+    /// ```
+    pub fn convert_range<T: Offset>(&self, range: Range<impl Offset>) -> Range<T> {
+        let bytes = checked_range(range.start.to_utf8(self)..range.end.to_utf8(self));
+        T::from_utf8(self, bytes.start)..T::from_utf8(self, bytes.end)
+    }
+
+    /// The line containing the offset. Newline characters count as part of the preceding line.
+    pub fn line_of(&self, offset: impl Offset) -> LineIdx {
+        LineIdx(self.utf8_to_line(offset.to_utf8(self)))
+    }
+
+    /// The offset of the first character of a line.
+    pub fn line_begin<T: Offset>(&self, line: LineIdx) -> T {
+        T::from_utf8(self, self.line_to_utf8_begin(line.0))
+    }
+
+    /// The offset one past the end of a line (the offset of the start of the next line).
+    pub fn line_end<T: Offset>(&self, line: LineIdx) -> T {
+        T::from_utf8(self, self.line_to_utf8_end(line.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        CharOffset, CharRange, LineIdx, StringOffsets, Utf16Offset, Utf16Range, Utf8Offset,
+        Utf8Range,
+    };
+
+    #[test]
+    fn test_typed_offsets() {
+        // The emoji are 7 UTF-8 bytes, 3 UTF-16 code units and 2 characters.
+        let offsets = StringOffsets::new("a🗺️\nb🗺️c");
+        assert_eq!(
+            offsets.convert::<Utf16Offset>(Utf8Offset(8)),
+            Utf16Offset(4)
+        );
+        assert_eq!(offsets.convert::<CharOffset>(Utf16Offset(4)), CharOffset(3));
+        assert_eq!(offsets.convert::<Utf8Offset>(CharOffset(3)), Utf8Offset(8));
+        assert_eq!(offsets.convert::<Utf8Offset>(Utf8Offset(5)), Utf8Offset(5));
+
+        assert_eq!(offsets.line_of(Utf8Offset(8)), LineIdx(0));
+        assert_eq!(offsets.line_of(CharOffset(4)), LineIdx(1));
+        assert_eq!(
+            offsets.line_begin::<Utf16Offset>(LineIdx(1)),
+            Utf16Offset(5)
+        );
+        assert_eq!(offsets.line_end::<CharOffset>(LineIdx(1)), CharOffset(8));
+        assert_eq!(offsets.line_end::<Utf8Offset>(LineIdx(5)), Utf8Offset(18));
+    }
+
+    #[test]
+    fn test_typed_ranges() {
+        let offsets = StringOffsets::new("a🗺️\nb🗺️c");
+        let utf16: Utf16Range = offsets.convert_range(Utf8Offset(1)..Utf8Offset(10));
+        assert_eq!(utf16, Utf16Offset(1)..Utf16Offset(6));
+        let chars: CharRange = offsets.convert_range(utf16);
+        assert_eq!(chars, CharOffset(1)..CharOffset(5));
+        let bytes: Utf8Range = offsets.convert_range(chars);
+        assert_eq!(bytes, Utf8Offset(1)..Utf8Offset(10));
+
+        // Both ends within the surrogate pair of the map emoji round down to its start.
+        let bytes: Utf8Range = offsets.convert_range(Utf16Offset(2)..Utf16Offset(2));
+        assert_eq!(bytes, Utf8Offset(1)..Utf8Offset(1));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "reversed range")]
+    fn test_typed_reversed_range() {
+        let offsets = StringOffsets::new("a🗺️\nb🗺️c");
+        let _: Utf8Range = offsets.convert_range(Utf16Offset(6)..Utf16Offset(1));
+    }
+}
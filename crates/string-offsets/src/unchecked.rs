@@ -0,0 +1,125 @@
+//! Conversions which skip the bounds checks, for hot loops whose input was validated up front.
+//!
+//! Their safety relies on the bitranks agreeing with the line table, which holds for converters
+//! built and edited by this crate. Deserialization with serde and the validation of `rkyv`
+//! archives reject converters for which it doesn't hold. Archives which are accessed without
+//! validation have to come from a trusted source.
+
+use crate::StringOffsets;
+
+impl StringOffsets {
+    /// The same as [`StringOffsets::utf8_to_char`], but without checking whether `byte_number` is
+    /// within the string.
+    ///
+    /// # Safety
+    ///
+    /// `byte_number` must be smaller than the length of the string, i.e. the
+    /// [`StringOffsets::line_to_utf8_begin`] of [`StringOffsets::lines`], otherwise the behavior
+    /// is undefined. The bitranks of converters deserialized with serde or from validated `rkyv`
+    /// archives are checked to cover this length.
+    pub unsafe fn utf8_to_char_unchecked(&self, byte_number: usize) -> usize {
+        // SAFETY: The last byte of the string is always set, which deserialization checks as well,
+        // so the caller's guarantee implies the one of `rank_unchecked`.
+        unsafe { self.utf8_to_char.rank_unchecked(byte_number) }
+    }
+
+    /// The same as [`StringOffsets::utf8_to_utf16`], but without checking whether `byte_number`
+    /// is within the string.
+    ///
+    /// # Safety
+    ///
+    /// `byte_number` must be smaller than the length of the string, i.e. the
+    /// [`StringOffsets::line_to_utf8_begin`] of [`StringOffsets::lines`], otherwise the behavior
+    /// is undefined. The bitranks of converters deserialized with serde or from validated `rkyv`
+    /// archives are checked to cover this length.
+    pub unsafe fn utf8_to_utf16_unchecked(&self, byte_number: usize) -> usize {
+        // SAFETY: The last byte of the string is always set, which deserialization checks as well,
+        // so the caller's guarantee implies the one of `rank_unchecked`.
+        unsafe { self.utf8_to_utf16.rank_unchecked(byte_number) }
+    }
+
+    /// The same as [`StringOffsets::utf8_to_line`], but without checking whether `byte_number` is
+    /// within the string.
+    ///
+    /// # Safety
+    ///
+    /// `byte_number` must be smaller than the length of the string, i.e. the
+    /// [`StringOffsets::line_to_utf8_begin`] of [`StringOffsets::lines`], otherwise the behavior
+    /// is undefined. The bitranks of converters deserialized with serde or from validated `rkyv`
+    /// archives are checked to cover this length.
+    pub unsafe fn utf8_to_line_unchecked(&self, byte_number: usize) -> usize {
+        // SAFETY: The last byte of the string always ends a line, which deserialization checks as
+        // well, so the caller's guarantee implies the one of `rank_unchecked`.
+        unsafe { self.utf8_to_line.rank_unchecked(byte_number) }
+    }
+
+    /// The same as [`StringOffsets::line_to_utf8_begin`], but without clamping `line_number` to
+    /// the number of lines.
+    ///
+    /// # Safety
+    ///
+    /// `line_number` must not be greater than [`StringOffsets::lines`], otherwise the behavior is
+    /// undefined.
+    pub unsafe fn line_to_utf8_begin_unchecked(&self, line_number: usize) -> usize {
+        debug_assert!(line_number <= self.lines(), "line out of bounds");
+        // SAFETY: The line table has an entry for every line.
+        unsafe { self.line_table.begin_unchecked(line_number) as usize }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::StringOffsets;
+
+    #[test]
+    fn test_unchecked_conversions() {
+        let content = "a👋\n\nb🗺️\n".repeat(100);
+        let mut offsets = StringOffsets::new(&content);
+        check_unchecked_conversions(&offsets, content.len());
+        // The invariants the conversions rely on must hold after edits as well.
+        offsets.apply_edit(content.len() - 1..content.len(), "c", &content);
+        check_unchecked_conversions(&offsets, content.len());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_unchecked_tampered() {
+        // A line table which claims a longer string than the bitranks cover would let the
+        // unchecked conversions read past the blocks, so it is rejected.
+        let json = serde_json::to_string(&StringOffsets::new("ab\n")).unwrap();
+        let tampered = json
+            .replace(r#""len":3"#, r#""len":40000"#)
+            .replace(r#""begins":[0]"#, r#""begins":[0,40000]"#)
+            .replace(
+                r#""whitespace_only":[false]"#,
+                r#""whitespace_only":[false,false]"#,
+            );
+        assert_ne!(tampered, json);
+        assert!(serde_json::from_str::<StringOffsets>(&tampered).is_err());
+    }
+
+    fn check_unchecked_conversions(offsets: &StringOffsets, len: usize) {
+        for byte_number in 0..len {
+            unsafe {
+                assert_eq!(
+                    offsets.utf8_to_char_unchecked(byte_number),
+                    offsets.utf8_to_char(byte_number)
+                );
+                assert_eq!(
+                    offsets.utf8_to_utf16_unchecked(byte_number),
+                    offsets.utf8_to_utf16(byte_number)
+                );
+                assert_eq!(
+                    offsets.utf8_to_line_unchecked(byte_number),
+                    offsets.utf8_to_line(byte_number)
+                );
+            }
+        }
+        for line_number in 0..=offsets.lines() {
+            assert_eq!(
+                unsafe { offsets.line_to_utf8_begin_unchecked(line_number) },
+                offsets.line_to_utf8_begin(line_number)
+            );
+        }
+    }
+}